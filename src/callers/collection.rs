@@ -1,9 +1,11 @@
 use neon::prelude::*;
 use crate::Cx;
-use crate::internal::query::{Query};
-use crate::internal::store::{Collection};
-use crate::internal::parser::{Parser, JSONParser};
+use crate::internal::query::{Query, Update};
+use crate::internal::store::{Collection, WriteMode, IdMode, ReturnDocument};
+use crate::internal::parser::{Parser, JSONParser, OutputOptions};
 use crate::callers::JsBoxWrapperHelper;
+use crate::callers::utils::js_tson_helper::{to_tson, document_to_tson, from_tson};
+use crate::callers::utils::js_object_helper::{get_bool, get_integer, MAX_SAFE_INTEGER};
 
 pub struct CollectionWrapper {
     internal: Collection,
@@ -26,27 +28,166 @@ impl CollectionWrapper {
 
         Ok(cx.string(name))
     }
-    pub fn js_insert(mut cx: Cx) -> JsResult<JsUndefined> {
-        let id = cx.argument::<JsString>(0)?.value(&mut cx);
-        let json = cx.argument::<JsString>(1)?.value(&mut cx);
+    pub fn js_insert(cx: Cx) -> JsResult<JsUndefined> {
+        Self::write(cx, WriteMode::Insert)
+    }
+    pub fn js_replace(cx: Cx) -> JsResult<JsUndefined> {
+        Self::write(cx, WriteMode::Replace)
+    }
+    pub fn js_upsert(cx: Cx) -> JsResult<JsUndefined> {
+        Self::write(cx, WriteMode::Upsert)
+    }
+    pub fn js_query(mut cx: Cx) -> JsResult<JsArray> {
+        let query = Self::query(&mut cx, 0)?;
 
         let collection = Self::this(&mut cx);
 
-        let parser = JSONParser::new_with_id(id.clone(), json);
-        let tson = parser.parse();
+        let documents = collection.internal.find(&query)
+            .or_else(|err| cx.throw_error(err.to_string()))?;
 
-        collection.internal.insert(id.as_bytes(), tson);
+        let array = JsArray::new(&mut cx, documents.len() as u32);
 
-        Ok(cx.undefined())
+        for (i, tson) in documents.into_iter().enumerate() {
+            let document = from_tson(&mut cx, tson.as_slice())?;
+            array.set(&mut cx, i as u32, document)?;
+        }
+
+        Ok(array)
+    }
+    pub fn js_delete_many(mut cx: Cx) -> JsResult<JsNumber> {
+        let query = Self::query(&mut cx, 0)?;
+
+        let collection = Self::this(&mut cx);
+
+        let deleted = collection.internal.delete_many(&query)
+            .or_else(|err| cx.throw_error(err.to_string()))?;
+
+        Ok(cx.number(deleted as f64))
+    }
+    pub fn js_export_jsonl(mut cx: Cx) -> JsResult<JsNumber> {
+        let path = cx.argument::<JsString>(0)?.value(&mut cx);
+        let query = Self::query(&mut cx, 1)?;
+        let options = Self::output_options(&mut cx, 2)?;
+
+        let collection = Self::this(&mut cx);
+
+        let exported = collection.internal.export_jsonl(path, &query, options)
+            .or_else(|err| cx.throw_error(err.to_string()))?;
+
+        Ok(cx.number(exported as f64))
+    }
+    pub fn js_import_jsonl(mut cx: Cx) -> JsResult<JsNumber> {
+        let path = cx.argument::<JsString>(0)?.value(&mut cx);
+        let mode = cx.argument::<JsString>(1)?.value(&mut cx);
+
+        let mode = match mode.as_str() {
+            "keep" => IdMode::Keep,
+            "generate" => IdMode::Generate,
+            "failOnConflict" => IdMode::FailOnConflict,
+            val => return cx.throw_error(format!("Unexpected id mode: {}", val)),
+        };
+
+        let collection = Self::this(&mut cx);
+
+        let imported = collection.internal.import_jsonl(path, mode)
+            .or_else(|err| cx.throw_error(err.to_string()))?;
+
+        Ok(cx.number(imported as f64))
+    }
+    pub fn js_find_one_and_update(mut cx: Cx) -> JsResult<JsValue> {
+        let update = match Self::json_or_tson(&mut cx, 1)? {
+            Ok(json) => Update::new(json),
+            Err(tson) => Update::from_tson(tson),
+        }.or_else(|err| cx.throw_error(err.to_string()))?;
+        Self::find_one_and_update(cx, update)
+    }
+    pub fn js_find_one_and_replace(mut cx: Cx) -> JsResult<JsValue> {
+        let update = match Self::json_or_tson(&mut cx, 1)? {
+            Ok(json) => Update::replacement(json),
+            Err(tson) => Update::replacement_from_tson(tson),
+        }.or_else(|err| cx.throw_error(err.to_string()))?;
+        Self::find_one_and_update(cx, update)
     }
-    pub fn js_query(mut cx: Cx) -> JsResult<JsUndefined> {
-        let json = cx.argument::<JsString>(0)?.value(&mut cx);
+}
+
+impl CollectionWrapper {
+    /// Documents are either JSON strings or JS objects, objects may hold
+    /// `Buffer`s which are stored as binaries.
+    fn write(mut cx: Cx, mode: WriteMode) -> JsResult<JsUndefined> {
+        let id = cx.argument::<JsString>(0)?.value(&mut cx);
+        let document = cx.argument::<JsValue>(1)?;
+
+        let tson = match document.downcast::<JsString, _>(&mut cx) {
+            Ok(json) => JSONParser::new_with_id(id.clone(), json.value(&mut cx)).parse()
+                .or_else(|err| cx.throw_error(err.to_string()))?,
+            Err(_) => {
+                let document = document.downcast_or_throw::<JsObject, _>(&mut cx)?;
+                document_to_tson(&mut cx, document, &id)?
+            },
+        };
 
         let collection = Self::this(&mut cx);
-        let iterator = collection.internal.query_request_all();
 
-        let query = Query::new(json);
+        collection.internal.write(id.as_bytes(), tson, mode)
+            .or_else(|err| cx.throw_error(err.to_string()))?;
 
         Ok(cx.undefined())
     }
+    fn find_one_and_update(mut cx: Cx, update: Update) -> JsResult<JsValue> {
+        let query = Self::query(&mut cx, 0)?;
+        let return_document = cx.argument::<JsString>(2)?.value(&mut cx);
+
+        let return_document = match return_document.as_str() {
+            "before" => ReturnDocument::Before,
+            "after" => ReturnDocument::After,
+            val => return cx.throw_error(format!("Unexpected returnDocument: {}", val)),
+        };
+
+        let collection = Self::this(&mut cx);
+
+        let document = collection.internal.find_one_and_update(&query, &update, return_document)
+            .or_else(|err| cx.throw_error(err.to_string()))?;
+
+        match document {
+            Some(tson) => from_tson(&mut cx, tson.as_slice()),
+            None => Ok(cx.null().upcast()),
+        }
+    }
+    fn query(cx: &mut Cx, i: i32) -> NeonResult<Query> {
+        match Self::json_or_tson(cx, i)? {
+            Ok(json) => Query::new(json),
+            Err(tson) => Query::from_tson(tson),
+        }.or_else(|err| cx.throw_error(err.to_string()))
+    }
+    fn output_options(cx: &mut Cx, i: i32) -> NeonResult<OutputOptions> {
+        let mut options = OutputOptions::default();
+
+        let object = match cx.argument_opt(i) {
+            Some(object) => object.downcast_or_throw::<JsObject, _>(cx)?,
+            None => return Ok(options),
+        };
+
+        options.extended = get_bool(cx, object, "extended")?.unwrap_or(false);
+        // At most 10 spaces, the same as `JSON.stringify`.
+        options.indent = get_integer(cx, object, "indent", 0.0, 10.0)?.map(|indent| indent as usize);
+        options.sort_keys = get_bool(cx, object, "sortKeys")?.unwrap_or(false);
+        options.ascii_only = get_bool(cx, object, "asciiOnly")?.unwrap_or(false);
+        options.max_depth = get_integer(cx, object, "maxDepth", 0.0, MAX_SAFE_INTEGER)?.map(|depth| depth as usize);
+        options.max_size = get_integer(cx, object, "maxSize", 0.0, MAX_SAFE_INTEGER)?.map(|size| size as usize);
+
+        Ok(options)
+    }
+    /// Filters and updates are either JSON strings, or JS objects converted
+    /// straight to TSON.
+    fn json_or_tson(cx: &mut Cx, i: i32) -> NeonResult<Result<String, Vec<u8>>> {
+        let value = cx.argument::<JsValue>(i)?;
+
+        match value.downcast::<JsString, _>(cx) {
+            Ok(json) => Ok(Ok(json.value(cx))),
+            Err(_) => {
+                let object = value.downcast_or_throw::<JsObject, _>(cx)?;
+                Ok(Err(to_tson(cx, object)?))
+            },
+        }
+    }
 }
\ No newline at end of file
@@ -1,7 +1,9 @@
 use neon::prelude::*;
 use crate::Cx;
-use crate::internal::store::{ Database, Collection };
+use crate::internal::store::{Database, DatabaseOptions, CollectionOptions, Compression, WalSync};
 use crate::callers::{CollectionWrapper, JsBoxWrapperHelper};
+use crate::callers::utils::js_tson_helper::from_tson;
+use crate::callers::utils::js_object_helper::{get_bool, get_integer, get_string, check_integer, MAX_SAFE_INTEGER};
 
 impl Finalize for DatabaseWrapper {}
 impl JsBoxWrapperHelper for DatabaseWrapper {}
@@ -13,15 +15,308 @@ pub struct DatabaseWrapper {
 impl DatabaseWrapper {
     pub fn js_new(mut cx: Cx) -> JsResult<JsBox<DatabaseWrapper>> {
         let path = cx.argument::<JsString>(0)?.value(&mut cx);
+        let options = Self::options_argument(&mut cx, 1)?;
 
-        Ok(cx.boxed(DatabaseWrapper { internal: Database::new(path) }))
+        let database = Database::new(path, options)
+            .or_else(|err| cx.throw_error(err.to_string()))?;
+
+        Ok(cx.boxed(DatabaseWrapper { internal: database }))
+    }
+    pub fn js_open_read_only(mut cx: Cx) -> JsResult<JsBox<DatabaseWrapper>> {
+        let path = cx.argument::<JsString>(0)?.value(&mut cx);
+        let options = Self::options_argument(&mut cx, 1)?;
+
+        let database = Database::open_read_only(path, options)
+            .or_else(|err| cx.throw_error(err.to_string()))?;
+
+        Ok(cx.boxed(DatabaseWrapper { internal: database }))
+    }
+    pub fn js_open_as_secondary(mut cx: Cx) -> JsResult<JsBox<DatabaseWrapper>> {
+        let path = cx.argument::<JsString>(0)?.value(&mut cx);
+        let secondary_path = cx.argument::<JsString>(1)?.value(&mut cx);
+        let options = Self::options_argument(&mut cx, 2)?;
+
+        let database = Database::open_as_secondary(path, secondary_path, options)
+            .or_else(|err| cx.throw_error(err.to_string()))?;
+
+        Ok(cx.boxed(DatabaseWrapper { internal: database }))
+    }
+    pub fn js_catch_up(mut cx: Cx) -> JsResult<JsUndefined> {
+        let database = Self::this(&mut cx);
+
+        database.internal.catch_up()
+            .or_else(|err| cx.throw_error(err.to_string()))?;
+
+        Ok(cx.undefined())
     }
     pub fn js_collection(mut cx: Cx) -> JsResult<JsBox<CollectionWrapper>> {
         let name = cx.argument::<JsString>(0)?.value(&mut cx);
+        let mut options = CollectionOptions::default();
+        if let Some(object) = cx.argument_opt(1) {
+            let object = object.downcast_or_throw::<JsObject, _>(&mut cx)?;
+            if let Some(key_dictionary) = get_bool(&mut cx, object, "keyDictionary")? {
+                options.key_dictionary = key_dictionary;
+            }
+        }
         let database = Self::this(&mut cx);
 
-        let collection = database.internal.collection(name);
+        let collection = database.internal.collection_with_options(name, options)
+            .or_else(|err| cx.throw_error(err.to_string()))?;
 
         Ok(cx.boxed(CollectionWrapper::new(collection)))
     }
-}
\ No newline at end of file
+    pub fn js_list_collections(mut cx: Cx) -> JsResult<JsArray> {
+        let database = Self::this(&mut cx);
+
+        let collections = database.internal.list_collections()
+            .or_else(|err| cx.throw_error(err.to_string()))?;
+        let array = JsArray::new(&mut cx, collections.len() as u32);
+
+        for (i, info) in collections.into_iter().enumerate() {
+            let object = JsObject::new(&mut cx);
+
+            let name = cx.string(info.name);
+            object.set(&mut cx, "name", name)?;
+
+            let created_at = cx.number(info.created_at as f64);
+            object.set(&mut cx, "createdAt", created_at)?;
+
+            // Catalog entries may leave options out.
+            let options = match info.options.as_slice() {
+                [] => JsObject::new(&mut cx).upcast(),
+                options => from_tson(&mut cx, options)?,
+            };
+            object.set(&mut cx, "options", options)?;
+
+            let indexes = JsArray::new(&mut cx, info.indexes.len() as u32);
+            for (j, index) in info.indexes.into_iter().enumerate() {
+                let index = cx.string(index);
+                indexes.set(&mut cx, j as u32, index)?;
+            }
+            object.set(&mut cx, "indexes", indexes)?;
+
+            array.set(&mut cx, i as u32, object)?;
+        }
+
+        Ok(array)
+    }
+    pub fn js_drop_collection(mut cx: Cx) -> JsResult<JsUndefined> {
+        let name = cx.argument::<JsString>(0)?.value(&mut cx);
+        let database = Self::this(&mut cx);
+
+        database.internal.drop_collection(&name)
+            .or_else(|err| cx.throw_error(err.to_string()))?;
+
+        Ok(cx.undefined())
+    }
+    pub fn js_rename_collection(mut cx: Cx) -> JsResult<JsUndefined> {
+        let from = cx.argument::<JsString>(0)?.value(&mut cx);
+        let to = cx.argument::<JsString>(1)?.value(&mut cx);
+        let database = Self::this(&mut cx);
+
+        database.internal.rename_collection(&from, to)
+            .or_else(|err| cx.throw_error(err.to_string()))?;
+
+        Ok(cx.undefined())
+    }
+    pub fn js_create_index(mut cx: Cx) -> JsResult<JsUndefined> {
+        let name = cx.argument::<JsString>(0)?.value(&mut cx);
+        let index = cx.argument::<JsString>(1)?.value(&mut cx);
+        let database = Self::this(&mut cx);
+
+        database.internal.create_index(&name, index)
+            .or_else(|err| cx.throw_error(err.to_string()))?;
+
+        Ok(cx.undefined())
+    }
+    pub fn js_collection_stats(mut cx: Cx) -> JsResult<JsObject> {
+        let name = cx.argument::<JsString>(0)?.value(&mut cx);
+        let database = Self::this(&mut cx);
+
+        let stats = database.internal.collection_stats(&name)
+            .or_else(|err| cx.throw_error(err.to_string()))?;
+
+        let object = JsObject::new(&mut cx);
+
+        let count = cx.number(stats.count as f64);
+        object.set(&mut cx, "count", count)?;
+
+        let size = cx.number(stats.size as f64);
+        object.set(&mut cx, "size", size)?;
+
+        let index_sizes = JsObject::new(&mut cx);
+        for (index, size) in stats.index_sizes.into_iter() {
+            let size = cx.number(size as f64);
+            index_sizes.set(&mut cx, index.as_str(), size)?;
+        }
+        object.set(&mut cx, "indexSizes", index_sizes)?;
+
+        Ok(object)
+    }
+    pub fn js_backup(mut cx: Cx) -> JsResult<JsUndefined> {
+        let dir = cx.argument::<JsString>(0)?.value(&mut cx);
+        let database = Self::this(&mut cx);
+
+        database.internal.backup(&dir)
+            .or_else(|err| cx.throw_error(err.to_string()))?;
+
+        Ok(cx.undefined())
+    }
+    pub fn js_upgrade_format(mut cx: Cx) -> JsResult<JsNumber> {
+        let database = Self::this(&mut cx);
+
+        let upgraded = database.internal.upgrade_format()
+            .or_else(|err| cx.throw_error(err.to_string()))?;
+
+        Ok(cx.number(upgraded as f64))
+    }
+    pub fn js_verify(mut cx: Cx) -> JsResult<JsArray> {
+        let quarantine = match cx.argument_opt(0) {
+            Some(quarantine) => quarantine.downcast_or_throw::<JsBoolean, _>(&mut cx)?.value(&mut cx),
+            None => false,
+        };
+        let database = Self::this(&mut cx);
+
+        let corrupt = database.internal.verify(quarantine)
+            .or_else(|err| cx.throw_error(err.to_string()))?;
+        let array = JsArray::new(&mut cx, corrupt.len() as u32);
+
+        for (i, (collection, document)) in corrupt.into_iter().enumerate() {
+            let object = JsObject::new(&mut cx);
+
+            let collection = cx.string(collection);
+            object.set(&mut cx, "collection", collection)?;
+
+            let id = cx.string(String::from_utf8_lossy(&document.id));
+            object.set(&mut cx, "id", id)?;
+
+            let error = cx.string(document.error.to_string());
+            object.set(&mut cx, "error", error)?;
+
+            array.set(&mut cx, i as u32, object)?;
+        }
+
+        Ok(array)
+    }
+    pub fn js_list_backups(mut cx: Cx) -> JsResult<JsArray> {
+        let dir = cx.argument::<JsString>(0)?.value(&mut cx);
+
+        let backups = Database::list_backups(&dir)
+            .or_else(|err| cx.throw_error(err.to_string()))?;
+        let array = JsArray::new(&mut cx, backups.len() as u32);
+
+        for (i, info) in backups.into_iter().enumerate() {
+            let object = JsObject::new(&mut cx);
+
+            let id = cx.number(info.id);
+            object.set(&mut cx, "id", id)?;
+
+            let timestamp = cx.number(info.timestamp as f64);
+            object.set(&mut cx, "timestamp", timestamp)?;
+
+            let size = cx.number(info.size as f64);
+            object.set(&mut cx, "size", size)?;
+
+            let num_files = cx.number(info.num_files);
+            object.set(&mut cx, "numFiles", num_files)?;
+
+            array.set(&mut cx, i as u32, object)?;
+        }
+
+        Ok(array)
+    }
+    pub fn js_restore_backup(mut cx: Cx) -> JsResult<JsUndefined> {
+        let dir = cx.argument::<JsString>(0)?.value(&mut cx);
+        let id = cx.argument::<JsNumber>(1)?.value(&mut cx);
+        let id = check_integer(&mut cx, "id", id, 0.0, u32::MAX as f64)?;
+        let target = cx.argument::<JsString>(2)?.value(&mut cx);
+
+        Database::restore_backup(&dir, id as u32, &target)
+            .or_else(|err| cx.throw_error(err.to_string()))?;
+
+        Ok(cx.undefined())
+    }
+    pub fn js_purge_old_backups(mut cx: Cx) -> JsResult<JsUndefined> {
+        let dir = cx.argument::<JsString>(0)?.value(&mut cx);
+        let keep = cx.argument::<JsNumber>(1)?.value(&mut cx);
+        let keep = check_integer(&mut cx, "keep", keep, 0.0, MAX_SAFE_INTEGER)?;
+
+        Database::purge_old_backups(&dir, keep as usize)
+            .or_else(|err| cx.throw_error(err.to_string()))?;
+
+        Ok(cx.undefined())
+    }
+    pub fn js_create_checkpoint(mut cx: Cx) -> JsResult<JsUndefined> {
+        let dir = cx.argument::<JsString>(0)?.value(&mut cx);
+        let database = Self::this(&mut cx);
+
+        database.internal.create_checkpoint(&dir)
+            .or_else(|err| cx.throw_error(err.to_string()))?;
+
+        Ok(cx.undefined())
+    }
+    pub fn js_close(mut cx: Cx) -> JsResult<JsUndefined> {
+        let database = Self::this(&mut cx);
+
+        database.internal.close()
+            .or_else(|err| cx.throw_error(err.to_string()))?;
+
+        Ok(cx.undefined())
+    }
+    pub fn js_destroy(mut cx: Cx) -> JsResult<JsUndefined> {
+        let path = cx.argument::<JsString>(0)?.value(&mut cx);
+
+        Database::destroy(path)
+            .or_else(|err| cx.throw_error(err.to_string()))?;
+
+        Ok(cx.undefined())
+    }
+}
+
+impl DatabaseWrapper {
+    fn options_argument(cx: &mut Cx, i: i32) -> NeonResult<DatabaseOptions> {
+        match cx.argument_opt(i) {
+            Some(options) => {
+                let options = options.downcast_or_throw::<JsObject, _>(cx)?;
+                Self::parse_options(cx, options)
+            },
+            None => Ok(DatabaseOptions::default()),
+        }
+    }
+    fn parse_options<'a>(cx: &mut Cx<'a>, object: Handle<'a, JsObject>) -> NeonResult<DatabaseOptions> {
+        let mut options = DatabaseOptions::default();
+
+        if let Some(create_if_missing) = get_bool(cx, object, "createIfMissing")? {
+            options.create_if_missing = create_if_missing;
+        }
+        if let Some(read_only) = get_bool(cx, object, "readOnly")? {
+            options.read_only = read_only;
+        }
+        if let Some(compression) = get_string(cx, object, "compression")? {
+            options.compression = match compression.as_str() {
+                "none" => Some(Compression::None),
+                "lz4" => Some(Compression::Lz4),
+                "zstd" => Some(Compression::Zstd),
+                val => return cx.throw_error(format!("Unexpected compression: {}", val)),
+            };
+        }
+        if let Some(wal_sync) = get_string(cx, object, "walSync")? {
+            options.wal_sync = match wal_sync.as_str() {
+                "sync" => WalSync::Sync,
+                "buffered" => WalSync::Buffered,
+                "disabled" => WalSync::Disabled,
+                val => return cx.throw_error(format!("Unexpected walSync: {}", val)),
+            };
+        }
+
+        let max_i32 = i32::MAX as f64;
+
+        options.block_cache_size = get_integer(cx, object, "blockCacheSize", 0.0, MAX_SAFE_INTEGER)?.map(|size| size as usize);
+        options.write_buffer_size = get_integer(cx, object, "writeBufferSize", 0.0, MAX_SAFE_INTEGER)?.map(|size| size as usize);
+        // -1 keeps every file open.
+        options.max_open_files = get_integer(cx, object, "maxOpenFiles", -1.0, max_i32)?.map(|files| files as i32);
+        options.bloom_filter_bits = get_integer(cx, object, "bloomFilterBits", 0.0, max_i32)?.map(|bits| bits as i32);
+
+        Ok(options)
+    }
+}
@@ -0,0 +1,40 @@
+use neon::prelude::*;
+
+// neon has no BigInt type, BigInts go through the global functions instead.
+
+pub fn is_bigint<'a, C: Context<'a>>(cx: &mut C, value: Handle<'a, JsValue>) -> NeonResult<bool> {
+    let global = cx.global();
+    let object = global.get(cx, "Object")?.downcast_or_throw::<JsObject, _>(cx)?;
+    let prototype = object.get(cx, "prototype")?.downcast_or_throw::<JsObject, _>(cx)?;
+    let to_string = prototype.get(cx, "toString")?.downcast_or_throw::<JsFunction, _>(cx)?;
+
+    let no_args: Vec<Handle<JsValue>> = Vec::new();
+    let tag = to_string.call(cx, value, no_args)?
+        .downcast_or_throw::<JsString, _>(cx)?
+        .value(cx);
+
+    Ok(tag == "[object BigInt]")
+}
+
+/// Reads a BigInt as its decimal digits.
+pub fn bigint_to_string<'a, C: Context<'a>>(cx: &mut C, value: Handle<'a, JsValue>) -> NeonResult<String> {
+    let global = cx.global();
+    let string = global.get(cx, "String")?.downcast_or_throw::<JsFunction, _>(cx)?;
+
+    let this = cx.undefined();
+    let digits = string.call(cx, this, vec![value])?
+        .downcast_or_throw::<JsString, _>(cx)?
+        .value(cx);
+
+    Ok(digits)
+}
+
+/// Creates a BigInt from its decimal digits.
+pub fn bigint<'a, C: Context<'a>>(cx: &mut C, digits: &str) -> JsResult<'a, JsValue> {
+    let global = cx.global();
+    let bigint = global.get(cx, "BigInt")?.downcast_or_throw::<JsFunction, _>(cx)?;
+
+    let this = cx.undefined();
+    let digits = cx.string(digits);
+    bigint.call(cx, this, vec![digits])
+}
@@ -0,0 +1,52 @@
+use neon::prelude::*;
+
+/// Largest integer JS numbers hold exactly.
+pub const MAX_SAFE_INTEGER: f64 = 9007199254740991.0;
+
+/// Reads an optional property, `undefined` and `null` both count as missing.
+pub fn get_optional<'a, C, V>(cx: &mut C, object: Handle<'a, JsObject>, key: &str) -> NeonResult<Option<Handle<'a, V>>>
+    where
+        C: Context<'a>,
+        V: Value,
+{
+    let value = object.get(cx, key)?;
+
+    if value.is_a::<JsUndefined, _>(cx) || value.is_a::<JsNull, _>(cx) {
+        return Ok(None);
+    }
+
+    Ok(Some(value.downcast_or_throw::<V, _>(cx)?))
+}
+
+pub fn get_bool<'a, C: Context<'a>>(cx: &mut C, object: Handle<'a, JsObject>, key: &str) -> NeonResult<Option<bool>> {
+    let value = get_optional::<C, JsBoolean>(cx, object, key)?;
+    Ok(value.map(|value| value.value(cx)))
+}
+
+pub fn get_number<'a, C: Context<'a>>(cx: &mut C, object: Handle<'a, JsObject>, key: &str) -> NeonResult<Option<f64>> {
+    let value = get_optional::<C, JsNumber>(cx, object, key)?;
+    Ok(value.map(|value| value.value(cx)))
+}
+
+/// Reads an optional integer from `min` to `max`, see `check_integer`.
+pub fn get_integer<'a, C: Context<'a>>(cx: &mut C, object: Handle<'a, JsObject>, key: &str, min: f64, max: f64) -> NeonResult<Option<f64>> {
+    match get_number(cx, object, key)? {
+        Some(number) => Ok(Some(check_integer(cx, key, number, min, max)?)),
+        None => Ok(None),
+    }
+}
+
+/// Throws on fractions, NaN and anything out of `min..=max`, rather than
+/// letting a cast truncate them.
+pub fn check_integer<'a, C: Context<'a>>(cx: &mut C, name: &str, number: f64, min: f64, max: f64) -> NeonResult<f64> {
+    if number.fract() != 0.0 || number < min || number > max {
+        return cx.throw_range_error(format!("{} must be an integer from {} to {}", name, min, max));
+    }
+
+    Ok(number)
+}
+
+pub fn get_string<'a, C: Context<'a>>(cx: &mut C, object: Handle<'a, JsObject>, key: &str) -> NeonResult<Option<String>> {
+    let value = get_optional::<C, JsString>(cx, object, key)?;
+    Ok(value.map(|value| value.value(cx)))
+}
\ No newline at end of file
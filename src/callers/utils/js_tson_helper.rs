@@ -0,0 +1,196 @@
+use neon::prelude::*;
+use neon::types::JsDate;
+use crate::internal::parser::{TSONValue, tson_delimiters};
+use crate::internal::parser::parsed::Parsed;
+use crate::internal::parser::tson_value::{object_members, array_elements};
+use crate::internal::parser::extended_json::format_object_id;
+use crate::callers::utils::js_bigint_helper::{is_bigint, bigint_to_string, bigint};
+
+const BINARY_GENERIC: u8 = 0x00;
+
+/// Writes a JS object as TSON without going through JSON. Dates, BigInts and
+/// Buffers keep their types.
+pub fn to_tson<'a, C: Context<'a>>(cx: &mut C, object: Handle<'a, JsObject>) -> NeonResult<Vec<u8>> {
+    let mut parsed = Parsed::new();
+    write_object(cx, &mut parsed, object, None)?;
+    Ok(parsed.get_parsed())
+}
+
+/// Writes a JS document as TSON, with `id` as its first member in place of
+/// any `_id` it has.
+pub fn document_to_tson<'a, C: Context<'a>>(cx: &mut C, document: Handle<'a, JsObject>, id: &str) -> NeonResult<Vec<u8>> {
+    let mut parsed = Parsed::new();
+    write_object(cx, &mut parsed, document, Some(id))?;
+    Ok(parsed.get_parsed())
+}
+
+/// Builds the JS value of a TSON value. Dates come back as `Date`s, 64-bit and
+/// wider integers as `BigInt`s and binaries as `Buffer`s. Fractional decimals
+/// can only be approximated by numbers, ObjectIds come back as hex strings.
+pub fn from_tson<'a, C: Context<'a>>(cx: &mut C, tson: &[u8]) -> JsResult<'a, JsValue> {
+    let value = match TSONValue::read(tson) {
+        TSONValue::Object(object) => {
+            let js_object = JsObject::new(cx);
+            for (key, value) in object_members(object) {
+                let value = from_tson(cx, value)?;
+                js_object.set(cx, String::from_utf8_lossy(key).as_ref(), value)?;
+            }
+            js_object.upcast()
+        },
+        TSONValue::Array(array) => {
+            let elements = array_elements(array);
+            let js_array = JsArray::new(cx, elements.len() as u32);
+            for (i, element) in elements.into_iter().enumerate() {
+                let element = from_tson(cx, element)?;
+                js_array.set(cx, i as u32, element)?;
+            }
+            js_array.upcast()
+        },
+        TSONValue::String(string) => cx.string(String::from_utf8_lossy(string)).upcast(),
+        TSONValue::Number(number) => cx.number(number).upcast(),
+        TSONValue::Date(millis) => JsDate::new_lossy(cx, millis as f64).upcast(),
+        TSONValue::Int64(number) => bigint(cx, &number.to_string())?,
+        TSONValue::Decimal128(decimal) if decimal.scale == 0 => bigint(cx, &decimal.to_string())?,
+        TSONValue::Decimal128(decimal) => cx.number(decimal.to_f64()).upcast(),
+        TSONValue::Binary(_, bytes) => {
+            let mut buffer = JsBuffer::new(cx, bytes.len() as u32)?;
+            cx.borrow_mut(&mut buffer, |data| data.as_mut_slice::<u8>().copy_from_slice(bytes));
+            buffer.upcast()
+        },
+        TSONValue::ObjectId(id) => cx.string(format_object_id(id)).upcast(),
+        TSONValue::True => cx.boolean(true).upcast(),
+        TSONValue::False => cx.boolean(false).upcast(),
+        TSONValue::Null => cx.null().upcast(),
+    };
+
+    Ok(value)
+}
+
+fn write_value<'a, C: Context<'a>>(cx: &mut C, parsed: &mut Parsed, value: Handle<'a, JsValue>) -> NeonResult<()> {
+    if value.is_a::<JsNull, _>(cx) || is_skipped(cx, value) {
+        parsed.write(tson_delimiters::NULL);
+    } else if let Ok(boolean) = value.downcast::<JsBoolean, _>(cx) {
+        match boolean.value(cx) {
+            true => parsed.write(tson_delimiters::TRUE),
+            false => parsed.write(tson_delimiters::FALSE),
+        }
+    } else if let Ok(number) = value.downcast::<JsNumber, _>(cx) {
+        parsed.write(tson_delimiters::NUMBER);
+        parsed.write_slice(&number.value(cx).to_le_bytes());
+    } else if let Ok(string) = value.downcast::<JsString, _>(cx) {
+        let string = string.value(cx);
+        parsed.write(tson_delimiters::STRING);
+        parsed.write_slice(&(string.len() as u32).to_le_bytes());
+        parsed.write_slice(string.as_bytes());
+    } else if let Ok(buffer) = value.downcast::<JsBuffer, _>(cx) {
+        let bytes = cx.borrow(&buffer, |data| data.as_slice::<u8>().to_vec());
+        parsed.write(tson_delimiters::BINARY);
+        parsed.write_slice(&(bytes.len() as u32).to_le_bytes());
+        parsed.write(BINARY_GENERIC);
+        parsed.write_slice(bytes.as_slice());
+    } else if let Ok(date) = value.downcast::<JsDate, _>(cx) {
+        // Invalid dates are null, same as JSON.stringify.
+        match date.is_valid(cx) {
+            true => {
+                parsed.write(tson_delimiters::DATE);
+                parsed.write_slice(&(date.value(cx) as i64).to_le_bytes());
+            },
+            false => parsed.write(tson_delimiters::NULL),
+        }
+    } else if let Ok(array) = value.downcast::<JsArray, _>(cx) {
+        write_array(cx, parsed, array)?;
+    } else if let Ok(object) = value.downcast::<JsObject, _>(cx) {
+        write_object(cx, parsed, object, None)?;
+    } else if is_bigint(cx, value)? {
+        write_bigint(cx, parsed, value)?;
+    } else {
+        return cx.throw_type_error("Unsupported value in document.");
+    }
+
+    Ok(())
+}
+
+/// BigInts are 64-bit integers when they fit, and decimals up to 128 bits.
+fn write_bigint<'a, C: Context<'a>>(cx: &mut C, parsed: &mut Parsed, value: Handle<'a, JsValue>) -> NeonResult<()> {
+    let digits = bigint_to_string(cx, value)?;
+
+    if let Ok(number) = digits.parse::<i64>() {
+        parsed.write(tson_delimiters::INT64);
+        parsed.write_slice(&number.to_le_bytes());
+    } else if let Ok(number) = digits.parse::<i128>() {
+        parsed.write(tson_delimiters::DECIMAL128);
+        parsed.write_slice(&number.to_le_bytes());
+        parsed.write(0); // scale
+    } else {
+        return cx.throw_range_error(format!("BigInt {} doesn't fit in 128 bits.", digits));
+    }
+
+    Ok(())
+}
+
+fn write_object<'a, C: Context<'a>>(
+    cx: &mut C,
+    parsed: &mut Parsed,
+    object: Handle<'a, JsObject>,
+    id: Option<&str>,
+) -> NeonResult<()> {
+    let start = begin_collection(parsed, tson_delimiters::OBJECT_BEGIN);
+
+    if let Some(id) = id {
+        write_key(parsed, "_id");
+        parsed.write(tson_delimiters::STRING);
+        parsed.write_slice(&(id.len() as u32).to_le_bytes());
+        parsed.write_slice(id.as_bytes());
+    }
+
+    for key in object.get_own_property_names(cx)?.to_vec(cx)? {
+        let key = key.downcast_or_throw::<JsString, _>(cx)?.value(cx);
+        let value = object.get(cx, key.as_str())?;
+
+        // Same as JSON.stringify, undefined and function members are left out.
+        if (id.is_some() && key == "_id") || is_skipped(cx, value) {
+            continue;
+        }
+
+        write_key(parsed, key.as_str());
+        write_value(cx, parsed, value)?;
+    }
+
+    end_collection(parsed, start, tson_delimiters::OBJECT_END);
+
+    Ok(())
+}
+
+fn write_array<'a, C: Context<'a>>(cx: &mut C, parsed: &mut Parsed, array: Handle<'a, JsArray>) -> NeonResult<()> {
+    let start = begin_collection(parsed, tson_delimiters::ARRAY_BEGIN);
+
+    for element in array.to_vec(cx)?.into_iter() {
+        write_value(cx, parsed, element)?;
+    }
+
+    end_collection(parsed, start, tson_delimiters::ARRAY_END);
+
+    Ok(())
+}
+
+fn write_key(parsed: &mut Parsed, key: &str) {
+    parsed.write(tson_delimiters::STRING);
+    parsed.write_slice(&(key.len() as u32).to_le_bytes());
+    parsed.write_slice(key.as_bytes());
+}
+
+fn begin_collection(parsed: &mut Parsed, begin: u8) -> usize {
+    parsed.write(begin);
+    parsed.write_slice(&[0; 4]);
+    parsed.get_parsed_len()
+}
+
+fn end_collection(parsed: &mut Parsed, start: usize, end: u8) {
+    let len = (parsed.get_parsed_len() - start) as u32;
+    parsed.rewrite_slice(start - 4, &len.to_le_bytes());
+    parsed.write(end);
+}
+
+fn is_skipped<'a, C: Context<'a>>(cx: &mut C, value: Handle<'a, JsValue>) -> bool {
+    value.is_a::<JsUndefined, _>(cx) || value.is_a::<JsFunction, _>(cx)
+}
@@ -1 +1,4 @@
-pub mod js_box_wrapper_helper;
\ No newline at end of file
+pub mod js_box_wrapper_helper;
+pub mod js_object_helper;
+pub mod js_bigint_helper;
+pub mod js_tson_helper;
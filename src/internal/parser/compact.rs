@@ -0,0 +1,395 @@
+use crate::internal::parser::delimiters::tson_delimiters;
+use crate::internal::parser::decimal::Decimal;
+use crate::internal::parser::tson_value::{
+    TSONValue, object_members, array_elements, write_object, write_array, write_string,
+    write_number, write_date, write_int64, write_decimal, write_binary, write_object_id,
+};
+use crate::internal::parser::tson_validator::{InvalidTSON, MAX_DEPTH};
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+// Compact TSON keeps the TSON delimiters for most values, but writes counts,
+// lengths and integers as LEB128 varints and drops collection ends. Short
+// strings and small integers fit in their delimiter.
+const INTEGER: u8 = 0x10; // integral number, zigzag
+const SHORT_STRING: u8 = 0x40; // up to 0x5F, length in the low bits
+const SMALL_INT: u8 = 0x80; // up to 0xFF, value in the low bits
+
+const SHORT_STRING_MAX: usize = 0x1F;
+const SMALL_INT_MAX: i64 = 0x7F;
+/// Integral numbers up to 2^53 convert to i64 and back exactly.
+const INTEGER_MAX: f64 = 9007199254740992.0;
+
+/// Object keys mapped to ids, so each compact value refers to a key by its
+/// id instead of repeating it. Ids are given in order and never reused.
+#[derive(Default)]
+pub struct Dictionary {
+    ids: HashMap<Vec<u8>, u32>,
+    keys: Vec<Vec<u8>>,
+}
+
+impl Dictionary {
+    pub fn id(&self, key: &[u8]) -> Option<u32> {
+        self.ids.get(key).copied()
+    }
+    pub fn key(&self, id: u32) -> Option<&[u8]> {
+        self.keys.get(id as usize).map(Vec::as_slice)
+    }
+    pub fn insert(&mut self, key: Vec<u8>) -> u32 {
+        let id = self.keys.len() as u32;
+        self.ids.insert(key.clone(), id);
+        self.keys.push(key);
+        id
+    }
+    /// Forgets every id from `len` on.
+    pub fn truncate(&mut self, len: usize) {
+        for key in self.keys.drain(len..) {
+            self.ids.remove(&key);
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+/// Every object key in `tson`, nested ones included.
+pub fn keys(tson: &[u8]) -> Vec<&[u8]> {
+    let mut keys = Vec::new();
+    collect_keys(TSONValue::read(tson), &mut keys);
+    keys
+}
+
+fn collect_keys<'a>(value: TSONValue<'a>, keys: &mut Vec<&'a [u8]>) {
+    match value {
+        TSONValue::Object(object) => {
+            for (key, value) in object_members(object) {
+                keys.push(key);
+                collect_keys(TSONValue::read(value), keys);
+            }
+        },
+        TSONValue::Array(array) => {
+            for element in array_elements(array) {
+                collect_keys(TSONValue::read(element), keys);
+            }
+        },
+        _ => (),
+    }
+}
+
+/// Writes `tson` compactly. Keys found in `dictionary` are written as ids,
+/// the others inline.
+pub fn compress(tson: &[u8], dictionary: Option<&Dictionary>) -> Vec<u8> {
+    let mut compact = Vec::with_capacity(tson.len());
+    write_value(&mut compact, TSONValue::read(tson), dictionary);
+    compact
+}
+
+fn write_value(out: &mut Vec<u8>, value: TSONValue, dictionary: Option<&Dictionary>) {
+    match value {
+        TSONValue::Object(object) => {
+            let members = object_members(object);
+            out.push(tson_delimiters::OBJECT_BEGIN);
+            write_varint(out, members.len() as u128);
+            for (key, value) in members {
+                match dictionary.and_then(|dictionary| dictionary.id(key)) {
+                    Some(id) => write_varint(out, (id as u128) << 1 | 1),
+                    None => {
+                        write_varint(out, (key.len() as u128) << 1);
+                        out.extend_from_slice(key);
+                    },
+                }
+                write_value(out, TSONValue::read(value), dictionary);
+            }
+        },
+        TSONValue::Array(array) => {
+            let elements = array_elements(array);
+            out.push(tson_delimiters::ARRAY_BEGIN);
+            write_varint(out, elements.len() as u128);
+            for element in elements {
+                write_value(out, TSONValue::read(element), dictionary);
+            }
+        },
+        TSONValue::String(string) if string.len() <= SHORT_STRING_MAX => {
+            out.push(SHORT_STRING | string.len() as u8);
+            out.extend_from_slice(string);
+        },
+        TSONValue::String(string) => {
+            out.push(tson_delimiters::STRING);
+            write_varint(out, string.len() as u128);
+            out.extend_from_slice(string);
+        },
+        // -0 isn't integral for our purposes, it wouldn't read back negative.
+        TSONValue::Number(number) if number.fract() == 0.0 && number.abs() <= INTEGER_MAX
+            && !(number == 0.0 && number.is_sign_negative()) => {
+            let integer = number as i64;
+            if (0..=SMALL_INT_MAX).contains(&integer) {
+                out.push(SMALL_INT | integer as u8);
+            } else {
+                out.push(INTEGER);
+                write_varint(out, zigzag(integer as i128));
+            }
+        },
+        TSONValue::Number(number) => {
+            out.push(tson_delimiters::NUMBER);
+            out.extend_from_slice(&number.to_le_bytes());
+        },
+        TSONValue::Date(millis) => {
+            out.push(tson_delimiters::DATE);
+            write_varint(out, zigzag(millis as i128));
+        },
+        TSONValue::Int64(number) => {
+            out.push(tson_delimiters::INT64);
+            write_varint(out, zigzag(number as i128));
+        },
+        TSONValue::Decimal128(decimal) => {
+            out.push(tson_delimiters::DECIMAL128);
+            write_varint(out, zigzag(decimal.coefficient));
+            out.push(decimal.scale);
+        },
+        TSONValue::Binary(subtype, bytes) => {
+            out.push(tson_delimiters::BINARY);
+            write_varint(out, bytes.len() as u128);
+            out.push(subtype);
+            out.extend_from_slice(bytes);
+        },
+        TSONValue::ObjectId(bytes) => {
+            out.push(tson_delimiters::OBJECT_ID);
+            out.extend_from_slice(bytes);
+        },
+        TSONValue::True => out.push(tson_delimiters::TRUE),
+        TSONValue::False => out.push(tson_delimiters::FALSE),
+        TSONValue::Null => out.push(tson_delimiters::NULL),
+    }
+}
+
+/// Reads a compact value back into TSON, without trusting any of its
+/// lengths. Fails on key ids missing from `dictionary`.
+pub fn expand(compact: &[u8], dictionary: Option<&Dictionary>) -> Result<Vec<u8>, InvalidTSON> {
+    let mut reader = Reader { compact, i: 0, depth: 0, dictionary };
+    let tson = reader.value()?;
+
+    if reader.i != compact.len() {
+        return Err(invalid(reader.i, "trailing bytes after the value"));
+    }
+
+    Ok(tson)
+}
+
+struct Reader<'a> {
+    compact: &'a [u8],
+    i: usize,
+    depth: usize,
+    dictionary: Option<&'a Dictionary>,
+}
+
+impl<'a> Reader<'a> {
+    fn value(&mut self) -> Result<Vec<u8>, InvalidTSON> {
+        let begin = self.i;
+        let delimiter = self.byte()?;
+
+        let tson = match delimiter {
+            tson_delimiters::OBJECT_BEGIN => {
+                self.enter(begin)?;
+                let mut members = Vec::new();
+                for _ in 0..self.varint()? {
+                    let key = self.key()?;
+                    members.push((key, self.value()?));
+                }
+                self.depth -= 1;
+                write_object(&members)
+            },
+            tson_delimiters::ARRAY_BEGIN => {
+                self.enter(begin)?;
+                let mut elements = Vec::new();
+                for _ in 0..self.varint()? {
+                    elements.push(self.value()?);
+                }
+                self.depth -= 1;
+                write_array(&elements)
+            },
+            tson_delimiters::STRING => {
+                let len = self.length()?;
+                write_string(self.take(len)?)
+            },
+            tson_delimiters::NUMBER => write_number(f64::from_le_bytes(self.take(8)?.try_into().unwrap())),
+            INTEGER => write_number(self.signed(begin, i64::MAX as i128)? as f64),
+            tson_delimiters::DATE => write_date(self.signed(begin, i64::MAX as i128)? as i64),
+            tson_delimiters::INT64 => write_int64(self.signed(begin, i64::MAX as i128)? as i64),
+            tson_delimiters::DECIMAL128 => {
+                let coefficient = self.signed(begin, i128::MAX)?;
+                write_decimal(Decimal::new(coefficient, self.byte()?))
+            },
+            tson_delimiters::BINARY => {
+                let len = self.length()?;
+                let subtype = self.byte()?;
+                write_binary(subtype, self.take(len)?)
+            },
+            tson_delimiters::OBJECT_ID => write_object_id(self.take(12)?.try_into().unwrap()),
+            tson_delimiters::TRUE | tson_delimiters::FALSE | tson_delimiters::NULL => vec![delimiter],
+            delimiter if delimiter & SMALL_INT != 0 => write_number((delimiter & !SMALL_INT) as f64),
+            delimiter if delimiter & !(SHORT_STRING_MAX as u8) == SHORT_STRING => {
+                write_string(self.take((delimiter & SHORT_STRING_MAX as u8) as usize)?)
+            },
+            _ => return Err(invalid(begin, "unknown delimiter")),
+        };
+
+        Ok(tson)
+    }
+    fn key(&mut self) -> Result<Vec<u8>, InvalidTSON> {
+        let begin = self.i;
+        let key = self.varint()?;
+
+        if key & 1 == 1 {
+            return (key >> 1).try_into().ok()
+                .and_then(|id| self.dictionary?.key(id))
+                .map(<[u8]>::to_vec)
+                .ok_or_else(|| invalid(begin, "unknown key id"));
+        }
+
+        let len = (key >> 1).try_into().map_err(|_| invalid(begin, "length runs past the end of the value"))?;
+        Ok(self.take(len)?.to_vec())
+    }
+    fn enter(&mut self, begin: usize) -> Result<(), InvalidTSON> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(invalid(begin, "nested too deep"));
+        }
+        Ok(())
+    }
+    fn byte(&mut self) -> Result<u8, InvalidTSON> {
+        let byte = self.compact.get(self.i).copied().ok_or_else(|| invalid(self.i, "unexpected end of value"))?;
+        self.i += 1;
+        Ok(byte)
+    }
+    fn take(&mut self, len: usize) -> Result<&'a [u8], InvalidTSON> {
+        match self.i.checked_add(len) {
+            Some(end) if end <= self.compact.len() => {
+                let bytes = &self.compact[self.i..end];
+                self.i = end;
+                Ok(bytes)
+            },
+            _ => Err(invalid(self.i, "length runs past the end of the value")),
+        }
+    }
+    fn length(&mut self) -> Result<usize, InvalidTSON> {
+        let begin = self.i;
+        self.varint()?.try_into().map_err(|_| invalid(begin, "length runs past the end of the value"))
+    }
+    /// A zigzag varint no larger than `max` either way.
+    fn signed(&mut self, begin: usize, max: i128) -> Result<i128, InvalidTSON> {
+        let value = unzigzag(self.varint()?);
+        if value > max || value < -max - 1 {
+            return Err(invalid(begin, "integer out of range"));
+        }
+        Ok(value)
+    }
+    fn varint(&mut self) -> Result<u128, InvalidTSON> {
+        let begin = self.i;
+        let mut value: u128 = 0;
+
+        for shift in (0..128).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7F) as u128) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(invalid(begin, "varint too long"))
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u128) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn zigzag(value: i128) -> u128 {
+    ((value << 1) ^ (value >> 127)) as u128
+}
+
+fn unzigzag(value: u128) -> i128 {
+    (value >> 1) as i128 ^ -((value & 1) as i128)
+}
+
+fn invalid(offset: usize, reason: &'static str) -> InvalidTSON {
+    InvalidTSON { offset, reason }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document() -> Vec<u8> {
+        let long = "a string longer than a short one";
+
+        write_object(&[
+            ("small", write_number(7.0)),
+            ("integer", write_number(-123456.0)),
+            ("fraction", write_number(0.25)),
+            ("negative zero", write_number(-0.0)),
+            ("short", write_string(b"abc")),
+            ("long", write_string(long.as_bytes())),
+            ("date", write_date(-86400000)),
+            ("int64", write_int64(i64::MIN)),
+            ("decimal", write_decimal(Decimal::new(-12345, 3))),
+            ("binary", write_binary(4, &[0, 1, 2])),
+            ("object id", write_object_id(&[7; 12])),
+            ("nested", write_object(&[
+                ("array", write_array(&[vec![tson_delimiters::TRUE], vec![tson_delimiters::FALSE], vec![tson_delimiters::NULL]])),
+                ("empty", write_object::<&str, Vec<u8>>(&[])),
+            ])),
+        ])
+    }
+
+    #[test]
+    fn round_trips_without_a_dictionary() {
+        let tson = document();
+        let compact = compress(&tson, None);
+
+        assert!(compact.len() < tson.len());
+        assert_eq!(expand(&compact, None).unwrap(), tson);
+    }
+
+    #[test]
+    fn round_trips_with_a_dictionary() {
+        let tson = document();
+        let mut dictionary = Dictionary::default();
+        for key in keys(&tson).into_iter().filter(|key| key.len() > 4) {
+            dictionary.insert(key.to_vec());
+        }
+
+        let compact = compress(&tson, Some(&dictionary));
+
+        assert!(compact.len() < compress(&tson, None).len());
+        assert_eq!(expand(&compact, Some(&dictionary)).unwrap(), tson);
+    }
+
+    #[test]
+    fn fails_on_keys_missing_from_the_dictionary() {
+        let tson = document();
+        let mut dictionary = Dictionary::default();
+        dictionary.insert(b"small".to_vec());
+
+        let compact = compress(&tson, Some(&dictionary));
+        dictionary.truncate(0);
+
+        assert!(expand(&compact, Some(&dictionary)).is_err());
+        assert!(expand(&compact, None).is_err());
+    }
+
+    #[test]
+    fn fails_on_truncated_values() {
+        let compact = compress(&document(), None);
+
+        for len in 0..compact.len() {
+            assert!(expand(&compact[..len], None).is_err(), "{} bytes", len);
+        }
+    }
+}
@@ -0,0 +1,303 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+const MAX_SAFE_INTEGER: i128 = 1 << 53;
+
+/// Exact decimal number, `coefficient * 10^-scale`.
+#[derive(Clone, Copy, Debug)]
+pub struct Decimal {
+    pub coefficient: i128,
+    pub scale: u8,
+}
+
+/// Numbers of any TSON numeric type, compared by their exact values.
+#[derive(Clone, Copy)]
+pub enum Numeric {
+    Float(f64),
+    Exact(Decimal),
+}
+
+/// The narrowest TSON type that holds a number exactly.
+pub enum Narrowest {
+    Number(f64),
+    Int64(i64),
+    Decimal(Decimal),
+}
+
+impl Decimal {
+    pub fn new(coefficient: i128, scale: u8) -> Decimal {
+        Decimal { coefficient, scale }
+    }
+    /// Parses a JSON number literal, `None` when it doesn't fit 128 bits.
+    pub fn parse(literal: &[u8]) -> Option<Decimal> {
+        let (negative, literal) = match literal.split_first() {
+            Some((b'-', rest)) => (true, rest),
+            _ => (false, literal),
+        };
+
+        let mut coefficient: i128 = 0;
+        let mut scale: i64 = 0;
+        let mut digits = 0;
+        let mut in_fraction = false;
+        let mut i = 0;
+
+        while i < literal.len() {
+            match literal[i] {
+                digit @ b'0'..=b'9' => {
+                    coefficient = coefficient.checked_mul(10)?.checked_add((digit - b'0') as i128)?;
+                    digits += 1;
+                    if in_fraction {
+                        scale += 1;
+                    }
+                },
+                b'.' if !in_fraction => in_fraction = true,
+                b'e' | b'E' => break,
+                _ => return None,
+            }
+            i += 1;
+        }
+
+        if digits == 0 {
+            return None;
+        }
+
+        if i < literal.len() {
+            let exponent: i64 = std::str::from_utf8(&literal[i + 1..]).ok()?.parse().ok()?;
+            scale = scale.checked_sub(exponent)?;
+        }
+
+        while scale < 0 {
+            coefficient = coefficient.checked_mul(10)?;
+            scale += 1;
+        }
+
+        if scale > u8::MAX as i64 {
+            return None;
+        }
+
+        let coefficient = if negative { -coefficient } else { coefficient };
+
+        Some(Decimal::new(coefficient, scale as u8))
+    }
+    /// The exact decimal value of a finite float, when it fits 128 bits.
+    pub fn from_f64(number: f64) -> Option<Decimal> {
+        if !number.is_finite() {
+            return None;
+        }
+
+        let bits = number.to_bits();
+        let negative = bits >> 63 == 1;
+        let exponent = ((bits >> 52) & 0x7FF) as i32;
+        let fraction = (bits & 0xF_FFFF_FFFF_FFFF) as i128;
+
+        let (mut mantissa, mut exponent) = match exponent {
+            0 => (fraction, -1074),
+            _ => (fraction | 1 << 52, exponent - 1075),
+        };
+
+        while mantissa != 0 && mantissa % 2 == 0 && exponent < 0 {
+            mantissa >>= 1;
+            exponent += 1;
+        }
+
+        let decimal = if exponent >= 0 {
+            Decimal::new(mantissa.checked_mul(2i128.checked_pow(exponent as u32)?)?, 0)
+        } else {
+            // m * 2^-e = m * 5^e / 10^e
+            let scale = -exponent as u32;
+            if scale > u8::MAX as u32 {
+                return None;
+            }
+            Decimal::new(mantissa.checked_mul(5i128.checked_pow(scale)?)?, scale as u8)
+        };
+
+        let decimal = if negative { Decimal::new(-decimal.coefficient, decimal.scale) } else { decimal };
+
+        Some(decimal.normalize())
+    }
+    /// The float closest to the decimal.
+    pub fn to_f64(&self) -> f64 {
+        self.to_string().parse().unwrap()
+    }
+    /// Strips trailing zeros from the coefficient.
+    pub fn normalize(mut self) -> Decimal {
+        while self.scale > 0 && self.coefficient % 10 == 0 {
+            self.coefficient /= 10;
+            self.scale -= 1;
+        }
+        self
+    }
+    /// Finds the narrowest type holding the decimal exactly. Integers within
+    /// ±2^53 stay floats, and so do fractions whose float prints back as the
+    /// same decimal, like 0.1.
+    pub fn narrowest(self) -> Narrowest {
+        let decimal = self.normalize();
+
+        if decimal.scale == 0 {
+            if decimal.coefficient.abs() <= MAX_SAFE_INTEGER {
+                return Narrowest::Number(decimal.coefficient as f64);
+            }
+            if decimal.coefficient >= i64::MIN as i128 && decimal.coefficient <= i64::MAX as i128 {
+                return Narrowest::Int64(decimal.coefficient as i64);
+            }
+            return Narrowest::Decimal(decimal);
+        }
+
+        // Floats print as the shortest decimal that reads back as them.
+        let number = decimal.to_f64();
+
+        match Decimal::parse(number.to_string().as_bytes()).map(Decimal::normalize) {
+            Some(printed) if printed.coefficient == decimal.coefficient && printed.scale == decimal.scale => {
+                Narrowest::Number(number)
+            },
+            _ => Narrowest::Decimal(decimal),
+        }
+    }
+    /// Compares exactly, falling back to floats when rescaling overflows.
+    pub fn compare(&self, other: &Decimal) -> Option<Ordering> {
+        let scale = self.scale.max(other.scale);
+
+        match (self.rescale(scale), other.rescale(scale)) {
+            (Some(a), Some(b)) => Some(a.cmp(&b)),
+            _ => self.to_f64().partial_cmp(&other.to_f64()),
+        }
+    }
+    fn rescale(&self, scale: u8) -> Option<i128> {
+        self.coefficient.checked_mul(10i128.checked_pow((scale - self.scale) as u32)?)
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let digits = self.coefficient.unsigned_abs().to_string();
+        let sign = if self.coefficient < 0 { "-" } else { "" };
+        let scale = self.scale as usize;
+
+        if scale == 0 {
+            return write!(f, "{}{}", sign, digits);
+        }
+
+        if digits.len() > scale {
+            let (integer, fraction) = digits.split_at(digits.len() - scale);
+            write!(f, "{}{}.{}", sign, integer, fraction)
+        } else {
+            write!(f, "{}0.{:0>width$}", sign, digits, width = scale)
+        }
+    }
+}
+
+impl Numeric {
+    pub fn compare(&self, other: &Numeric) -> Option<Ordering> {
+        match (self, other) {
+            (Numeric::Float(a), Numeric::Float(b)) => a.partial_cmp(b),
+            (Numeric::Exact(a), Numeric::Exact(b)) => a.compare(b),
+            (Numeric::Float(a), Numeric::Exact(b)) => compare_float(*a, b),
+            (Numeric::Exact(a), Numeric::Float(b)) => compare_float(*b, a).map(Ordering::reverse),
+        }
+    }
+}
+
+// Rounding is monotonic, so when the decimal rounds to a different float the
+// float comparison is already exact. Ties are settled on exact values.
+fn compare_float(float: f64, decimal: &Decimal) -> Option<Ordering> {
+    match float.partial_cmp(&decimal.to_f64())? {
+        Ordering::Equal => match Decimal::from_f64(float) {
+            Some(exact) => exact.compare(decimal),
+            None => Some(Ordering::Equal),
+        },
+        ordering => Some(ordering),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(literal: &str) -> Option<(i128, u8)> {
+        Decimal::parse(literal.as_bytes()).map(|decimal| (decimal.coefficient, decimal.scale))
+    }
+
+    fn decimal(literal: &str) -> Decimal {
+        Decimal::parse(literal.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn parses_literals() {
+        assert_eq!(parse("0"), Some((0, 0)));
+        assert_eq!(parse("1.50"), Some((150, 2)));
+        assert_eq!(parse("-0.5e-2"), Some((-5, 3)));
+        assert_eq!(parse("12E3"), Some((12000, 0)));
+        assert_eq!(parse("1e+2"), Some((100, 0)));
+    }
+
+    #[test]
+    fn rejects_literals_past_128_bits() {
+        assert_eq!(parse("1e300"), None);
+        assert_eq!(parse("1e-300"), None);
+        assert_eq!(parse("340282366920938463463374607431768211456"), None);
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("-"), None);
+    }
+
+    #[test]
+    fn keeps_floats_that_print_back_the_same() {
+        for literal in ["0.1", "1.1", "3.14", "1.50", "0.30000000000000004", "-2.5e-3", "9007199254740992"].iter() {
+            match decimal(literal).narrowest() {
+                Narrowest::Number(number) => assert_eq!(number, literal.parse::<f64>().unwrap()),
+                _ => panic!("{} should be a number", literal),
+            }
+        }
+    }
+
+    #[test]
+    fn widens_what_floats_cant_hold() {
+        assert!(matches!(decimal("9007199254740993").narrowest(), Narrowest::Int64(9007199254740993)));
+        assert!(matches!(decimal("-9223372036854775808").narrowest(), Narrowest::Int64(i64::MIN)));
+        assert!(matches!(
+            decimal("9223372036854775808").narrowest(),
+            Narrowest::Decimal(Decimal { coefficient: 9223372036854775808, scale: 0 })
+        ));
+        assert!(matches!(
+            decimal("0.1000000000000000000001").narrowest(),
+            Narrowest::Decimal(Decimal { coefficient: 1000000000000000000001, scale: 22 })
+        ));
+    }
+
+    #[test]
+    fn compares_exactly() {
+        assert_eq!(decimal("1.50").compare(&decimal("1.5")), Some(Ordering::Equal));
+        assert_eq!(decimal("0.1").compare(&decimal("0.09")), Some(Ordering::Greater));
+        assert_eq!(decimal("-1").compare(&decimal("1")), Some(Ordering::Less));
+        assert_eq!(
+            decimal("0.1000000000000000000001").compare(&decimal("0.1")),
+            Some(Ordering::Greater),
+        );
+    }
+
+    #[test]
+    fn compares_as_floats_when_rescaling_overflows() {
+        let large = Decimal::new(i128::MAX, 0);
+        let small = Decimal::new(1, 38);
+
+        assert_eq!(large.compare(&small), Some(Ordering::Greater));
+        assert_eq!(small.compare(&large), Some(Ordering::Less));
+    }
+
+    #[test]
+    fn compares_floats_with_exact_values() {
+        // Rounds to 0.5 as a float, but is below it.
+        let below_half = Numeric::Exact(decimal("0.4999999999999999999"));
+
+        assert_eq!(Numeric::Float(0.5).compare(&below_half), Some(Ordering::Greater));
+        assert_eq!(below_half.compare(&Numeric::Float(0.5)), Some(Ordering::Less));
+        assert_eq!(Numeric::Float(0.5).compare(&Numeric::Exact(decimal("0.50"))), Some(Ordering::Equal));
+        assert_eq!(Numeric::Float(f64::NAN).compare(&below_half), None);
+    }
+
+    #[test]
+    fn prints_decimals() {
+        assert_eq!(decimal("-0.05").to_string(), "-0.05");
+        assert_eq!(decimal("12.345").to_string(), "12.345");
+        assert_eq!(decimal("7").to_string(), "7");
+    }
+}
@@ -12,6 +12,9 @@ pub mod json_delimiters {
     pub const SEPARATOR:    u8 = b',';
 }
 
+/// Objects hold their members as a key string followed by the value, arrays
+/// their elements one after the other. 0x09 and 0x0A were pair and separator
+/// bytes in the legacy layout, see `legacy_tson`.
 pub mod tson_delimiters {
     pub const OBJECT_BEGIN: u8 = 0x00;
     pub const OBJECT_END:   u8 = 0x01;
@@ -22,6 +25,9 @@ pub mod tson_delimiters {
     pub const TRUE:         u8 = 0x06;
     pub const FALSE:        u8 = 0x07;
     pub const NULL:         u8 = 0x08;
-    pub const PAIR:         u8 = 0x09;
-    pub const SEPARATOR:    u8 = 0x0A;
+    pub const DATE:         u8 = 0x0B;
+    pub const INT64:        u8 = 0x0C;
+    pub const DECIMAL128:   u8 = 0x0D;
+    pub const BINARY:       u8 = 0x0E;
+    pub const OBJECT_ID:    u8 = 0x0F;
 }
\ No newline at end of file
@@ -0,0 +1,424 @@
+use crate::internal::parser::delimiters::tson_delimiters;
+use crate::internal::parser::tson_value::{
+    TSONValue,
+    object_members,
+    write_number,
+    write_date,
+    write_int64,
+    write_decimal,
+    write_binary,
+    write_object_id,
+};
+use crate::internal::utils::base64;
+use crate::internal::parser::decimal::Decimal;
+use std::str;
+
+// 10000-01-01T00:00:00Z
+const MAX_ISO_MILLIS: i64 = 253_402_300_800_000;
+
+/// Maps an Extended JSON wrapper, Canonical or Relaxed, onto the native TSON
+/// value it stands for. Anything else is left alone, wrappers with a value
+/// they can't hold and the deprecated types TSON has none for are errors.
+pub fn convert(object: &[u8]) -> Result<Option<Vec<u8>>, &'static str> {
+    // Cheap check for a first key starting with $ before reading members.
+    if object.len() < 11 || object[5] != tson_delimiters::STRING || object[10] != b'$' {
+        return Ok(None);
+    }
+
+    let members = object_members(object);
+
+    match members.as_slice() {
+        [(key, value)] => convert_member(key, value),
+        // Legacy binaries from older mongoexport versions.
+        [(b"$binary", value), (b"$type", subtype)] => convert_binary(value, subtype).map(Some).ok_or(INVALID_BINARY),
+        _ => Ok(None),
+    }
+}
+
+fn convert_member(key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>, &'static str> {
+    let value = TSONValue::read(value);
+
+    let (converted, expected) = match key {
+        b"$oid" => (
+            string(value).and_then(parse_object_id).map(|id| write_object_id(&id)),
+            "$oid needs 24 hex digits",
+        ),
+        b"$numberInt" => (
+            string(value).and_then(parse_int32).map(|number| write_number(number as f64)),
+            "$numberInt needs a 32-bit integer string",
+        ),
+        b"$numberLong" => (
+            string(value).and_then(parse_int64).map(write_int64),
+            "$numberLong needs a 64-bit integer string",
+        ),
+        b"$numberDouble" => (
+            string(value).and_then(parse_number).map(write_number),
+            "$numberDouble needs a number string",
+        ),
+        b"$numberDecimal" => (
+            string(value).and_then(Decimal::parse).map(write_decimal),
+            "$numberDecimal needs a decimal string",
+        ),
+        b"$date" => (
+            convert_date(value),
+            "$date needs an ISO-8601 string or whole milliseconds within 64 bits",
+        ),
+        b"$binary" => (
+            convert_binary_object(value),
+            INVALID_BINARY,
+        ),
+        b"$symbol" => return Err("symbols aren't supported, store them as strings"),
+        b"$undefined" => return Err("undefined isn't supported, store it as null"),
+        _ => return Ok(None),
+    };
+
+    converted.map(Some).ok_or(expected)
+}
+
+const INVALID_BINARY: &str = "$binary needs base64 bytes and a hex subType";
+
+fn string(value: TSONValue<'_>) -> Option<&[u8]> {
+    match value {
+        TSONValue::String(string) => Some(string),
+        _ => None,
+    }
+}
+
+/// Milliseconds given as a plain number must be whole and fit in an i64,
+/// -2^63 and 2^63 are both exact as floats.
+fn convert_date(value: TSONValue) -> Option<Vec<u8>> {
+    match value {
+        TSONValue::Number(millis) if millis.fract() == 0.0 && millis >= i64::MIN as f64 && millis < i64::MAX as f64 => {
+            Some(write_date(millis as i64))
+        },
+        TSONValue::Int64(millis) => Some(write_date(millis)),
+        TSONValue::String(date) => parse_date(date).map(write_date),
+        _ => None,
+    }
+}
+
+fn convert_binary_object(value: TSONValue) -> Option<Vec<u8>> {
+    let binary = match value {
+        TSONValue::Object(binary) => binary,
+        _ => return None,
+    };
+
+    match object_members(binary).as_slice() {
+        [(b"base64", bytes), (b"subType", subtype)] => convert_binary(bytes, subtype),
+        [(b"subType", subtype), (b"base64", bytes)] => convert_binary(bytes, subtype),
+        _ => None,
+    }
+}
+
+fn convert_binary(bytes: &[u8], subtype: &[u8]) -> Option<Vec<u8>> {
+    match (TSONValue::read(bytes), TSONValue::read(subtype)) {
+        (TSONValue::String(bytes), TSONValue::String(subtype)) => {
+            let subtype = u8::from_str_radix(str::from_utf8(subtype).ok()?, 16).ok()?;
+            Some(write_binary(subtype, base64::decode(bytes)?.as_slice()))
+        },
+        _ => None,
+    }
+}
+
+/// Writes numbers JSON can't represent as `$numberDouble` wrappers.
+pub fn write_number_wrapper(number: f64) -> Option<String> {
+    let number = if number.is_nan() {
+        "NaN"
+    } else if number == f64::INFINITY {
+        "Infinity"
+    } else if number == f64::NEG_INFINITY {
+        "-Infinity"
+    } else {
+        return None;
+    };
+
+    Some(format!("{{\"$numberDouble\":\"{}\"}}", number))
+}
+
+/// Relaxed Extended JSON writes dates between 1970 and 9999 as ISO-8601
+/// strings, and the rest as canonical `$numberLong` milliseconds.
+pub fn write_date_wrapper(millis: i64) -> String {
+    if (0..MAX_ISO_MILLIS).contains(&millis) {
+        format!("{{\"$date\":\"{}\"}}", format_date(millis))
+    } else {
+        format!("{{\"$date\":{{\"$numberLong\":\"{}\"}}}}", millis)
+    }
+}
+
+pub fn write_int64_wrapper(number: i64) -> String {
+    format!("{{\"$numberLong\":\"{}\"}}", number)
+}
+
+pub fn write_decimal_wrapper(decimal: Decimal) -> String {
+    format!("{{\"$numberDecimal\":\"{}\"}}", decimal)
+}
+
+pub fn write_binary_wrapper(subtype: u8, bytes: &[u8]) -> String {
+    format!("{{\"$binary\":{{\"base64\":\"{}\",\"subType\":\"{:02x}\"}}}}", base64::encode(bytes), subtype)
+}
+
+pub fn write_object_id_wrapper(id: &[u8]) -> String {
+    format!("{{\"$oid\":\"{}\"}}", format_object_id(id))
+}
+
+/// ObjectIds read as 24 lowercase hex digits, the same as `ObjectId.toString`.
+pub fn format_object_id(id: &[u8]) -> String {
+    id.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn parse_object_id(hex: &[u8]) -> Option<[u8; 12]> {
+    if hex.len() != 24 {
+        return None;
+    }
+
+    let mut id = [0; 12];
+    for (byte, digits) in id.iter_mut().zip(hex.chunks(2)) {
+        *byte = u8::from_str_radix(str::from_utf8(digits).ok()?, 16).ok()?;
+    }
+
+    Some(id)
+}
+
+fn parse_number(number: &[u8]) -> Option<f64> {
+    str::from_utf8(number).ok()?.parse().ok()
+}
+
+fn parse_int32(number: &[u8]) -> Option<i32> {
+    str::from_utf8(number).ok()?.parse().ok()
+}
+
+fn parse_int64(number: &[u8]) -> Option<i64> {
+    str::from_utf8(number).ok()?.parse().ok()
+}
+
+/// Parses an ISO-8601 date time such as `2021-03-04T05:06:07.890Z` or
+/// `2021-03-04T05:06:07+03:00` into milliseconds since the epoch.
+pub fn parse_date(date: &[u8]) -> Option<i64> {
+    let date = str::from_utf8(date).ok()?;
+
+    if date.len() < 20 || !date.is_char_boundary(19) {
+        return None;
+    }
+
+    let (date_time, rest) = date.split_at(19);
+    let bytes = date_time.as_bytes();
+
+    if !date_time.is_ascii() || bytes[4] != b'-' || bytes[7] != b'-' || bytes[10] != b'T' || bytes[13] != b':' || bytes[16] != b':' {
+        return None;
+    }
+
+    let year: i64 = date_time[0..4].parse().ok()?;
+    let month: i64 = date_time[5..7].parse().ok()?;
+    let day: i64 = date_time[8..10].parse().ok()?;
+    let hour: i64 = date_time[11..13].parse().ok()?;
+    let minute: i64 = date_time[14..16].parse().ok()?;
+    let second: i64 = date_time[17..19].parse().ok()?;
+
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let (millis, offset) = match rest.strip_prefix('.') {
+        Some(fraction) => {
+            let digits = fraction.bytes().take_while(u8::is_ascii_digit).count();
+            if digits == 0 {
+                return None;
+            }
+            let millis = format!("{:0<3}", &fraction[..digits.min(3)]).parse::<i64>().ok()?;
+            (millis, &fraction[digits..])
+        },
+        None => (0, rest),
+    };
+
+    let offset = match offset {
+        "Z" => 0,
+        offset if offset.len() == 6 && offset.is_ascii() && &offset[3..4] == ":" => {
+            let sign = match &offset[..1] {
+                "+" => 1,
+                "-" => -1,
+                _ => return None,
+            };
+            let hours: i64 = offset[1..3].parse().ok()?;
+            let minutes: i64 = offset[4..6].parse().ok()?;
+            sign * (hours * 60 + minutes)
+        },
+        _ => return None,
+    };
+
+    let days = days_from_civil(year, month, day);
+    let seconds = days * 86_400 + hour * 3_600 + minute * 60 + second - offset * 60;
+
+    Some(seconds * 1_000 + millis)
+}
+
+/// Formats milliseconds since the epoch as `YYYY-MM-DDTHH:MM:SS.sssZ`, the same
+/// as `Date.prototype.toISOString`.
+pub fn format_date(millis: i64) -> String {
+    let days = millis.div_euclid(86_400_000);
+    let millis = millis.rem_euclid(86_400_000);
+    let (year, month, day) = civil_from_days(days);
+
+    let year = if (0..=9999).contains(&year) {
+        format!("{:04}", year)
+    } else {
+        format!("{:+07}", year)
+    };
+
+    format!(
+        "{}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1_000 % 60,
+        millis % 1_000,
+    )
+}
+
+// Howard Hinnant's days_from_civil, days since 1970-01-01 in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+// Inverse of days_from_civil.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = if days >= 0 { days } else { days - 146_096 } / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::internal::parser::{Parser, JSONParser, TSONParser, OutputOptions};
+    use crate::internal::parser::delimiters::tson_delimiters;
+
+    fn tson(json: &str) -> Vec<u8> {
+        JSONParser::new(json.to_string()).parse().unwrap()
+    }
+
+    fn output(json: &str, extended: bool) -> String {
+        let options = OutputOptions { extended, ..OutputOptions::default() };
+        String::from_utf8(TSONParser::with_options(tson(json), options).parse().unwrap()).unwrap()
+    }
+
+    fn extended(json: &str) -> String {
+        output(json, true)
+    }
+
+    fn error(json: &str) -> String {
+        JSONParser::new(json.to_string()).parse().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn round_trips_object_ids() {
+        let json = r#"{"_id":{"$oid":"5f1d7a3b9c1e4a2b3c4d5e6f"}}"#;
+
+        assert_eq!(tson(json)[13], tson_delimiters::OBJECT_ID);
+        assert_eq!(extended(json), json);
+        assert_eq!(output(json, false), r#"{"_id":"5f1d7a3b9c1e4a2b3c4d5e6f"}"#);
+        assert_eq!(extended(r#"{"$oid":"5F1D7A3B9C1E4A2B3C4D5E6F"}"#), r#"{"$oid":"5f1d7a3b9c1e4a2b3c4d5e6f"}"#);
+    }
+
+    #[test]
+    fn round_trips_dates() {
+        let relaxed = r#"{"$date":"2020-01-01T00:00:00.000Z"}"#;
+
+        assert_eq!(extended(relaxed), relaxed);
+        assert_eq!(extended(r#"{"$date":"2020-01-01T03:00:00+03:00"}"#), relaxed);
+        assert_eq!(extended(r#"{"$date":{"$numberLong":"1577836800000"}}"#), relaxed);
+        assert_eq!(extended(r#"{"$date":1577836800000}"#), relaxed);
+
+        // Canonical outside the years relaxed dates can hold.
+        let canonical = r#"{"$date":{"$numberLong":"-1"}}"#;
+        assert_eq!(extended(canonical), canonical);
+        assert_eq!(extended(r#"{"$date":-1}"#), canonical);
+    }
+
+    #[test]
+    fn rejects_dates_that_are_not_whole_milliseconds() {
+        for json in [r#"{"$date":1.5}"#, r#"{"$date":1e19}"#, r#"{"$date":-1e19}"#, r#"{"$date":"yesterday"}"#, r#"{"$date":true}"#].iter() {
+            assert!(error(json).contains("$date needs an ISO-8601 string or whole milliseconds within 64 bits"), "{}", json);
+        }
+    }
+
+    #[test]
+    fn round_trips_numbers() {
+        assert_eq!(extended(r#"{"$numberInt":"42"}"#), "42");
+        assert_eq!(extended(r#"{"$numberDouble":"1.5"}"#), "1.5");
+        assert_eq!(extended(r#"{"$numberDouble":"-1.0"}"#), "-1");
+
+        for json in [r#"{"$numberDouble":"Infinity"}"#, r#"{"$numberDouble":"-Infinity"}"#, r#"{"$numberDouble":"NaN"}"#].iter() {
+            assert_eq!(extended(json), *json);
+            assert_eq!(output(json, false), "null");
+        }
+
+        let long = r#"{"$numberLong":"9223372036854775807"}"#;
+        assert_eq!(extended(long), long);
+        assert_eq!(output(long, false), "9223372036854775807");
+        // Relaxed integers past 2^53 read back as longs.
+        assert_eq!(extended("9007199254740993"), r#"{"$numberLong":"9007199254740993"}"#);
+
+        let decimal = r#"{"$numberDecimal":"1.10"}"#;
+        assert_eq!(extended(decimal), decimal);
+        assert_eq!(output(decimal, false), "1.10");
+    }
+
+    #[test]
+    fn rejects_numbers_their_wrappers_cant_hold() {
+        assert!(error(r#"{"$numberInt":"2147483648"}"#).contains("$numberInt needs a 32-bit integer string"));
+        assert!(error(r#"{"$numberInt":"1.5"}"#).contains("$numberInt needs a 32-bit integer string"));
+        assert!(error(r#"{"$numberLong":"9223372036854775808"}"#).contains("$numberLong needs a 64-bit integer string"));
+        assert!(error(r#"{"$numberLong":1}"#).contains("$numberLong needs a 64-bit integer string"));
+        assert!(error(r#"{"$numberDouble":"one"}"#).contains("$numberDouble needs a number string"));
+        assert!(error(r#"{"$numberDecimal":"1e999"}"#).contains("$numberDecimal needs a decimal string"));
+        assert!(error(r#"{"$oid":"5f1d"}"#).contains("$oid needs 24 hex digits"));
+        assert!(error(r#"{"$oid":"5f1d7a3b9c1e4a2b3c4d5e6g"}"#).contains("$oid needs 24 hex digits"));
+    }
+
+    #[test]
+    fn round_trips_binaries() {
+        let canonical = r#"{"$binary":{"base64":"AQID","subType":"80"}}"#;
+
+        assert_eq!(extended(canonical), canonical);
+        assert_eq!(extended(r#"{"$binary":{"subType":"80","base64":"AQID"}}"#), canonical);
+        assert_eq!(extended(r#"{"$binary":"AQID","$type":"80"}"#), canonical);
+        assert_eq!(output(canonical, false), r#""AQID""#);
+
+        assert!(error(r#"{"$binary":{"base64":"AQID"}}"#).contains("$binary needs base64 bytes and a hex subType"));
+        assert!(error(r#"{"$binary":"AQID","$type":"zz"}"#).contains("$binary needs base64 bytes and a hex subType"));
+    }
+
+    #[test]
+    fn rejects_types_tson_has_none_for() {
+        assert!(error(r#"{"a":{"$symbol":"s"}}"#).contains("symbols aren't supported"));
+        assert!(error(r#"{"a":{"$undefined":true}}"#).contains("undefined isn't supported"));
+    }
+
+    #[test]
+    fn leaves_other_objects_alone() {
+        for json in [r#"{"$set":{"a":1}}"#, r#"{"$oid":"5f1d7a3b9c1e4a2b3c4d5e6f","a":1}"#, r#"{"a":{"$date":null,"b":1}}"#].iter() {
+            assert_eq!(extended(json), *json);
+        }
+    }
+
+    #[test]
+    fn points_errors_at_the_wrapper() {
+        let error = error("{\"a\":\n  {\"$date\":1.5}}");
+        assert!(error.contains("line 2"), "{}", error);
+    }
+}
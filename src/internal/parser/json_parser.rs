@@ -1,12 +1,45 @@
-use crate::internal::parser::parser::{Parser};
+use crate::internal::parser::parser::{Parser, Loop};
 use crate::internal::parser::delimiters::{json_delimiters, tson_delimiters};
 use crate::internal::parser::value_cursor::ValueCursor;
 use crate::internal::parser::parsed::Parsed;
+use crate::internal::parser::parse_error::{ParseError, SourceMap};
+use crate::internal::parser::extended_json;
+use crate::internal::parser::decimal::{Decimal, Narrowest};
 
+const CONTEXT: &str = "JSON";
+
+/// What the grammar allows at the current position.
+#[derive(Clone, Copy, PartialEq)]
+enum Expect {
+    Value,
+    /// A value or `]`, right after `[`.
+    FirstValue,
+    Key,
+    /// A key or `}`, right after `{`.
+    FirstKey,
+    Pair,
+    /// `,` or the end of the enclosing collection.
+    Next,
+    End,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Collection {
+    Object,
+    Array,
+}
+
+/// Validating RFC 8259 tokenizer, writing TSON as it goes.
 pub struct JSONParser {
     cursor: ValueCursor,
     parsed: Parsed,
-    stack: Vec<usize>,
+    stack: Vec<(Collection, usize)>,
+    expect: Expect,
+    /// JSON offset of the token being parsed.
+    token: usize,
+    /// TSON offset of every token with its JSON offset.
+    offsets: Vec<(usize, usize)>,
+    error: Option<ParseError>,
 }
 
 impl JSONParser {
@@ -14,24 +47,34 @@ impl JSONParser {
         let len = json.len();
         Self::with_capacity(json, len)
     }
+    /// Parses a JSON object with `id` injected as its first member.
     pub fn new_with_id(id: String, json: String) -> JSONParser {
         let capacity = json.len() + id.len();
 
         let mut parser = Self::with_capacity(json, capacity);
 
+        parser.skip_whitespace();
+        if parser.peek() != Some(json_delimiters::OBJECT_BEGIN) {
+            let err = parser.unexpected(parser.cursor.get_index(), "a document object");
+            parser.fail(err);
+            return parser;
+        }
+
+        parser.token = parser.cursor.get_index();
+        parser.offsets.push((0, parser.token));
+        parser.cursor.skip_next();
         parser.write_object_begin();
-        parser.cursor.skip_by(1);
 
         let key = "_id".as_bytes();
         parser.parsed.write(tson_delimiters::STRING);
         parser.write_length(key.len() as u32);
         parser.parsed.write_slice(key);
-        parser.write_pair();
 
         parser.parsed.write(tson_delimiters::STRING);
         parser.write_length(id.len() as u32);
         parser.parsed.write_slice(id.as_bytes());
-        parser.write_separator();
+
+        parser.expect = Expect::FirstKey;
 
         parser
     }
@@ -40,136 +83,516 @@ impl JSONParser {
             cursor: ValueCursor::new(json.into_bytes()),
             parsed: Parsed::with_capacity(capacity),
             stack: Vec::new(),
+            expect: Expect::Value,
+            token: 0,
+            offsets: Vec::new(),
+            error: None,
         }
     }
+    /// Parses the JSON, keeping a map from the TSON back to it for reporting
+    /// errors found later on.
+    pub fn parse_with_source(mut self) -> Result<(Vec<u8>, SourceMap), ParseError> {
+        while let Loop::Continue = self.next() {}
+        self.finish()
+    }
+    fn finish(self) -> Result<(Vec<u8>, SourceMap), ParseError> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+        if self.expect != Expect::End {
+            let expected = self.expected();
+            return Err(self.unexpected(self.cursor.get_index(), expected));
+        }
+
+        let source = SourceMap::new(self.cursor.get_value(), self.offsets);
+        Ok((self.parsed.get_parsed(), source))
+    }
 }
 
 impl Parser for JSONParser {
-    type Parsed = Vec<u8>;
+    type Parsed = Result<Vec<u8>, ParseError>;
     fn get_index(&self) -> usize {
         self.cursor.get_index()
     }
     fn get_original_len(&self) -> usize {
         self.cursor.get_value_ref().len()
     }
-    fn get_parsed(self) -> Vec<u8> {
-        self.parsed.get_parsed()
+    fn get_parsed(self) -> Result<Vec<u8>, ParseError> {
+        self.finish().map(|(tson, _)| tson)
     }
     fn parse_next(&mut self) {
-        match self.cursor.read_next() {
-            json_delimiters::OBJECT_BEGIN => self.write_object_begin(),
-            json_delimiters::OBJECT_END => self.write_object_end(),
-            json_delimiters::ARRAY_BEGIN => self.write_array_begin(),
-            json_delimiters::ARRAY_END => self.write_array_end(),
-            json_delimiters::STRING => self.write_string(),
-            json_delimiters::TRUE => self.write_true(),
-            json_delimiters::FALSE => self.write_false(),
-            json_delimiters::NULL => self.write_null(),
-            json_delimiters::PAIR => self.write_pair(),
-            json_delimiters::SEPARATOR => self.write_separator(),
-            _ => self.write_number(),
+        let val = self.cursor.read_next();
+
+        if is_whitespace(val) {
+            return;
+        }
+
+        self.token = self.cursor.get_index() - 1;
+        self.offsets.push((self.parsed.get_parsed_len(), self.token));
+
+        if let Err(err) = self.parse_token(val) {
+            self.fail(err);
         }
     }
 }
 
 impl JSONParser {
+    fn parse_token(&mut self, val: u8) -> Result<(), ParseError> {
+        match (self.expect, val) {
+            (Expect::Value, _) | (Expect::FirstValue, _) if val != json_delimiters::ARRAY_END => self.write_value(val)?,
+            (Expect::FirstValue, json_delimiters::ARRAY_END) => self.write_array_end(),
+            (Expect::Key, json_delimiters::STRING) | (Expect::FirstKey, json_delimiters::STRING) => self.write_key()?,
+            (Expect::FirstKey, json_delimiters::OBJECT_END) => self.write_object_end()?,
+            (Expect::Pair, json_delimiters::PAIR) => self.expect = Expect::Value,
+            (Expect::Next, json_delimiters::SEPARATOR) => self.next_member(),
+            (Expect::Next, json_delimiters::OBJECT_END) if self.in_collection(Collection::Object) => self.write_object_end()?,
+            (Expect::Next, json_delimiters::ARRAY_END) if self.in_collection(Collection::Array) => self.write_array_end(),
+            _ => return Err(self.unexpected(self.token, self.expected())),
+        }
+
+        Ok(())
+    }
     fn write_length(&mut self, length: u32) {
         self.parsed.write_slice(&length.to_le_bytes());
     }
-    fn begin_collection(&mut self) {
+    fn begin_collection(&mut self, collection: Collection) {
         self.write_length(0);
-        self.stack.push(self.parsed.get_parsed_len());
+        self.stack.push((collection, self.parsed.get_parsed_len()));
     }
-    fn end_collection(&mut self) {
-        let start = self.stack.pop().unwrap();
+    /// Fills in the length of the innermost collection and returns where its
+    /// content starts.
+    fn end_collection(&mut self) -> usize {
+        let (_, start) = self.stack.pop().unwrap();
         let len = (self.parsed.get_parsed_len() - start) as u32;
         self.parsed.rewrite_slice(start - 4, &len.to_le_bytes());
+        start
+    }
+    fn in_collection(&self, collection: Collection) -> bool {
+        matches!(self.stack.last(), Some((current, _)) if *current == collection)
+    }
+    fn end_value(&mut self) {
+        self.expect = match self.stack.is_empty() {
+            true => Expect::End,
+            false => Expect::Next,
+        };
     }
 }
 
 impl JSONParser {
+    fn write_value(&mut self, val: u8) -> Result<(), ParseError> {
+        match val {
+            json_delimiters::OBJECT_BEGIN => self.write_object_begin(),
+            json_delimiters::ARRAY_BEGIN => self.write_array_begin(),
+            json_delimiters::STRING => self.write_string()?,
+            json_delimiters::TRUE => self.write_literal(b"true", tson_delimiters::TRUE)?,
+            json_delimiters::FALSE => self.write_literal(b"false", tson_delimiters::FALSE)?,
+            json_delimiters::NULL => self.write_literal(b"null", tson_delimiters::NULL)?,
+            b'-' | b'0'..=b'9' => self.write_number()?,
+            _ => return Err(self.unexpected(self.token, self.expected())),
+        }
+
+        Ok(())
+    }
     fn write_object_begin(&mut self) {
         self.parsed.write(tson_delimiters::OBJECT_BEGIN);
-        self.begin_collection();
+        self.begin_collection(Collection::Object);
+        self.expect = Expect::FirstKey;
     }
-    fn write_object_end(&mut self) {
-        self.end_collection();
+    fn write_object_end(&mut self) -> Result<(), ParseError> {
+        let begin = self.end_collection() - 5; // object_begin and length
         self.parsed.write(tson_delimiters::OBJECT_END);
+
+        let kept = self.offsets.partition_point(|(tson, _)| *tson <= begin);
+        let converted = extended_json::convert(self.parsed.get_slice_from(begin))
+            .map_err(|reason| self.error(self.offsets[kept - 1].1, reason.to_string()))?;
+
+        if let Some(value) = converted {
+            self.parsed.truncate(begin);
+            self.parsed.write_slice(value.as_slice());
+            self.offsets.truncate(kept);
+        }
+
+        self.end_value();
+        Ok(())
     }
     fn write_array_begin(&mut self) {
         self.parsed.write(tson_delimiters::ARRAY_BEGIN);
-        self.begin_collection();
+        self.begin_collection(Collection::Array);
+        self.expect = Expect::FirstValue;
     }
     fn write_array_end(&mut self) {
         self.end_collection();
         self.parsed.write(tson_delimiters::ARRAY_END);
+        self.end_value();
     }
-    fn write_string(&mut self) {
+    fn write_key(&mut self) -> Result<(), ParseError> {
+        self.write_string_value()?;
+        self.expect = Expect::Pair;
+
+        Ok(())
+    }
+    fn write_string(&mut self) -> Result<(), ParseError> {
+        self.write_string_value()?;
+        self.end_value();
+
+        Ok(())
+    }
+    fn write_string_value(&mut self) -> Result<(), ParseError> {
         self.parsed.write(tson_delimiters::STRING);
-        let string = self.read_string();
+        let string = self.read_string()?;
         let length = string.len() as u32;
         self.write_length(length);
         self.parsed.write_slice(string.as_slice());
+
+        Ok(())
     }
-    fn write_true(&mut self) {
-        self.parsed.write(tson_delimiters::TRUE);
-        self.cursor.skip_by(3);
-    }
-    fn write_false(&mut self) {
-        self.parsed.write(tson_delimiters::FALSE);
-        self.cursor.skip_by(4);
-    }
-    fn write_null(&mut self) {
-        self.parsed.write(tson_delimiters::NULL);
-        self.cursor.skip_by(3);
-    }
-    fn write_pair(&mut self) {
-        self.parsed.write(tson_delimiters::PAIR);
+    fn write_literal(&mut self, literal: &[u8], delimiter: u8) -> Result<(), ParseError> {
+        let rest = &self.cursor.get_value_ref()[self.token..];
+        let matched = rest.iter().zip(literal).take_while(|(a, b)| a == b).count();
+
+        if matched != literal.len() {
+            let expected = String::from_utf8_lossy(literal).into_owned();
+            return Err(self.unexpected(self.token + matched, &expected));
+        }
+
+        self.cursor.skip_by(literal.len() - 1);
+        self.parsed.write(delimiter);
+        self.end_value();
+
+        Ok(())
     }
-    fn write_separator(&mut self) {
-        self.parsed.write(tson_delimiters::SEPARATOR);
+    /// After `,`. TSON has no bytes for `:` or `,`, they only move the grammar along.
+    fn next_member(&mut self) {
+        self.expect = match self.in_collection(Collection::Object) {
+            true => Expect::Key,
+            false => Expect::Value,
+        };
     }
-    fn write_number(&mut self) {
+    /// Numbers take the narrowest type holding them exactly. Literals past
+    /// 128 bits can only be approximated by floats, and those past the range
+    /// of floats are rejected.
+    fn write_number(&mut self) -> Result<(), ParseError> {
         self.cursor.skip_reverse_by(1);
-        let number = self.read_number();
-        let number: f64 = String::from_utf8(number.to_vec()).unwrap().parse().unwrap();
-        self.parsed.write(tson_delimiters::NUMBER);
-        self.parsed.write_slice(&number.to_le_bytes());
+        let begin = self.cursor.get_index();
+        let literal = self.read_number()?.to_vec();
+
+        let narrowest = match Decimal::parse(&literal) {
+            Some(decimal) => decimal.narrowest(),
+            None => match approximate(&literal) {
+                Some(number) => Narrowest::Number(number),
+                None => return Err(self.error(begin, "number out of range".to_string())),
+            },
+        };
+
+        match narrowest {
+            Narrowest::Number(number) => {
+                self.parsed.write(tson_delimiters::NUMBER);
+                self.parsed.write_slice(&number.to_le_bytes());
+            },
+            Narrowest::Int64(number) => {
+                self.parsed.write(tson_delimiters::INT64);
+                self.parsed.write_slice(&number.to_le_bytes());
+            },
+            Narrowest::Decimal(decimal) => {
+                self.parsed.write(tson_delimiters::DECIMAL128);
+                self.parsed.write_slice(&decimal.coefficient.to_le_bytes());
+                self.parsed.write(decimal.scale);
+            },
+        }
+
+        self.end_value();
+
+        Ok(())
     }
 }
 
 impl JSONParser {
-    fn read_number(&mut self) -> &[u8] {
+    /// Reads `-? (0 | [1-9][0-9]*) (. [0-9]+)? ([eE] [+-]? [0-9]+)?`.
+    fn read_number(&mut self) -> Result<&[u8], ParseError> {
         let prev = self.cursor.get_index();
 
-        loop {
-            match self.cursor.read_next() {
-                json_delimiters::SEPARATOR => break,
-                json_delimiters::OBJECT_END => break,
-                json_delimiters::ARRAY_END => break,
-                _ => continue,
-            }
+        self.skip_if(|val| val == b'-');
+
+        match self.peek() {
+            Some(b'0') => self.cursor.skip_next(),
+            Some(b'1'..=b'9') => self.skip_digits(),
+            _ => return Err(self.unexpected(self.cursor.get_index(), "a digit")),
+        }
+
+        if self.skip_if(|val| val == b'.') {
+            self.expect_digits()?;
+        }
+
+        if self.skip_if(|val| val == b'e' || val == b'E') {
+            self.skip_if(|val| val == b'+' || val == b'-');
+            self.expect_digits()?;
         }
 
-        self.cursor.skip_reverse_by(1);
         let current = self.cursor.get_index();
-        self.cursor.read_range(prev..current)
+        Ok(self.cursor.read_range(prev..current))
     }
-    fn read_string(& mut self) -> Vec<u8> {
-        let prev = self.cursor.get_index();
+    fn expect_digits(&mut self) -> Result<(), ParseError> {
+        match self.peek() {
+            Some(b'0'..=b'9') => {
+                self.skip_digits();
+                Ok(())
+            },
+            _ => Err(self.unexpected(self.cursor.get_index(), "a digit")),
+        }
+    }
+    fn skip_digits(&mut self) {
+        while self.skip_if(|val| val.is_ascii_digit()) {}
+    }
+    /// Reads the rest of a string, decoding escapes into UTF-8.
+    fn read_string(&mut self) -> Result<Vec<u8>, ParseError> {
+        let begin = self.cursor.get_index() - 1;
+        let mut string = Vec::new();
 
         loop {
-            let val = self.cursor.read_next();
-            if val == b'\\' {
-                self.cursor.skip_next();
-                continue;
+            if self.is_end() {
+                return Err(self.error(begin, "unterminated string".to_string()));
+            }
+
+            match self.cursor.read_next() {
+                json_delimiters::STRING => break,
+                b'\\' => self.read_escape(begin, &mut string)?,
+                val if val < 0x20 => {
+                    let offset = self.cursor.get_index() - 1;
+                    return Err(self.error(offset, "unescaped control character in string".to_string()));
+                },
+                val => string.push(val),
             }
-            if val == json_delimiters::STRING {
-                break;
+        }
+
+        Ok(string)
+    }
+    fn read_escape(&mut self, begin: usize, string: &mut Vec<u8>) -> Result<(), ParseError> {
+        let escape = self.cursor.get_index() - 1;
+
+        if self.is_end() {
+            return Err(self.error(begin, "unterminated string".to_string()));
+        }
+
+        let decoded = match self.cursor.read_next() {
+            b'"' => '"',
+            b'\\' => '\\',
+            b'/' => '/',
+            b'b' => '\u{08}',
+            b'f' => '\u{0C}',
+            b'n' => '\n',
+            b'r' => '\r',
+            b't' => '\t',
+            b'u' => self.read_unicode_escape(escape)?,
+            _ => return Err(self.unexpected(escape + 1, "an escape character")),
+        };
+
+        let mut buffer = [0; 4];
+        string.extend_from_slice(decoded.encode_utf8(&mut buffer).as_bytes());
+
+        Ok(())
+    }
+    /// Reads the hex digits of `\uXXXX`, joining surrogate pairs.
+    fn read_unicode_escape(&mut self, escape: usize) -> Result<char, ParseError> {
+        let high = self.read_hex()?;
+
+        let code = match high {
+            0xD800..=0xDBFF => {
+                let rest = &self.cursor.get_value_ref()[self.cursor.get_index()..];
+                if !rest.starts_with(b"\\u") {
+                    return Err(self.error(escape, "unpaired surrogate in \\u escape".to_string()));
+                }
+                self.cursor.skip_by(2);
+
+                let low = self.read_hex()?;
+                if !(0xDC00..=0xDFFF).contains(&low) {
+                    return Err(self.error(escape, "unpaired surrogate in \\u escape".to_string()));
+                }
+
+                0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+            },
+            0xDC00..=0xDFFF => return Err(self.error(escape, "unpaired surrogate in \\u escape".to_string())),
+            code => code,
+        };
+
+        Ok(std::char::from_u32(code).unwrap())
+    }
+    fn read_hex(&mut self) -> Result<u32, ParseError> {
+        let mut code = 0;
+
+        for _ in 0..4 {
+            let digit = self.peek().and_then(|val| (val as char).to_digit(16));
+            match digit {
+                Some(digit) => code = code * 16 + digit,
+                None => return Err(self.unexpected(self.cursor.get_index(), "a hex digit")),
             }
+            self.cursor.skip_next();
         }
 
-        let current = self.cursor.get_index();
-        self.cursor.read_range(prev..current - 1).to_vec()
+        Ok(code)
+    }
+}
+
+impl JSONParser {
+    fn is_end(&self) -> bool {
+        self.cursor.get_index() >= self.cursor.get_value_ref().len()
+    }
+    fn peek(&self) -> Option<u8> {
+        match self.is_end() {
+            true => None,
+            false => Some(self.cursor.peek()),
+        }
+    }
+    fn skip_if(&mut self, accept: fn(u8) -> bool) -> bool {
+        match self.peek() {
+            Some(val) if accept(val) => {
+                self.cursor.skip_next();
+                true
+            },
+            _ => false,
+        }
+    }
+    fn skip_whitespace(&mut self) {
+        while self.skip_if(is_whitespace) {}
+    }
+    /// Keeps the first error and stops parsing.
+    fn fail(&mut self, err: ParseError) {
+        self.error = Some(err);
+        self.cursor.skip_rest();
+    }
+    fn expected(&self) -> &'static str {
+        match self.expect {
+            Expect::Value => "a value",
+            Expect::FirstValue => "a value or ']'",
+            Expect::Key => "a string key",
+            Expect::FirstKey => "a string key or '}'",
+            Expect::Pair => "':'",
+            Expect::Next if self.in_collection(Collection::Object) => "',' or '}'",
+            Expect::Next => "',' or ']'",
+            Expect::End => "end of input",
+        }
+    }
+    fn unexpected(&self, offset: usize, expected: &str) -> ParseError {
+        let json = self.cursor.get_value_ref();
+
+        let found = match json.get(offset..) {
+            Some(rest) if !rest.is_empty() => {
+                let rest = &rest[..rest.len().min(4)];
+                format!("{:?}", String::from_utf8_lossy(rest).chars().next().unwrap())
+            },
+            _ => "end of input".to_string(),
+        };
+
+        self.error(offset, format!("expected {}, found {}", expected, found))
+    }
+    fn error(&self, offset: usize, message: String) -> ParseError {
+        ParseError::at(CONTEXT, message, self.cursor.get_value_ref(), offset)
     }
-}
\ No newline at end of file
+}
+
+fn is_whitespace(val: u8) -> bool {
+    matches!(val, b' ' | b'\t' | b'\n' | b'\r')
+}
+
+/// The float nearest a literal, unless it overflows or a nonzero literal
+/// underflows to zero.
+fn approximate(literal: &[u8]) -> Option<f64> {
+    let number: f64 = std::str::from_utf8(literal).ok()?.parse().ok()?;
+    let mantissa = literal.split(|&val| val == b'e' || val == b'E').next()?;
+
+    match number.is_finite() && (number != 0.0 || !mantissa.iter().any(|val| matches!(val, b'1'..=b'9'))) {
+        true => Some(number),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::parser::tson_value::{write_array, write_decimal, write_int64, write_number, write_object, write_string};
+
+    fn parse(json: &str) -> Result<Vec<u8>, String> {
+        JSONParser::new(json.to_string()).parse().map_err(|err| err.to_string())
+    }
+
+    fn first_line(json: &str) -> String {
+        parse(json).unwrap_err().lines().next().unwrap().to_string()
+    }
+
+    #[test]
+    fn parses_documents() {
+        let expected = write_object(&[
+            ("a", write_number(1.0)),
+            ("b", write_array(&[write_string(b"x"), vec![tson_delimiters::NULL], vec![tson_delimiters::TRUE]])),
+        ]);
+
+        assert_eq!(parse(" { \"a\" : 1 , \"b\" : [ \"x\" , null , true ] } ").unwrap(), expected);
+    }
+
+    #[test]
+    fn narrows_numbers() {
+        assert_eq!(parse("[0.1]").unwrap(), write_array(&[write_number(0.1)]));
+        assert_eq!(parse("[-2.5e3]").unwrap(), write_array(&[write_number(-2500.0)]));
+        assert_eq!(parse("[9007199254740993]").unwrap(), write_array(&[write_int64(9007199254740993)]));
+        assert_eq!(
+            parse("[0.1000000000000000000001]").unwrap(),
+            write_array(&[write_decimal(Decimal::new(1000000000000000000001, 22))]),
+        );
+    }
+
+    #[test]
+    fn rejects_numbers_out_of_range() {
+        assert_eq!(first_line("[1e400]"), "Invalid JSON at line 1, column 2: number out of range.");
+        assert_eq!(first_line("[-1e400]"), "Invalid JSON at line 1, column 2: number out of range.");
+        assert_eq!(first_line("[1e-400]"), "Invalid JSON at line 1, column 2: number out of range.");
+        assert!(parse("[0e-400]").is_ok());
+    }
+
+    #[test]
+    fn reads_escapes() {
+        let expected = write_array(&[write_string("\"\\/\u{8}\u{c}\n\r\té".as_bytes())]);
+
+        assert_eq!(parse(r#"["\"\\\/\b\f\n\r\t\u00e9"]"#).unwrap(), expected);
+    }
+
+    #[test]
+    fn joins_surrogate_pairs() {
+        assert_eq!(parse(r#"["\ud83d\ude00"]"#).unwrap(), write_array(&[write_string("😀".as_bytes())]));
+    }
+
+    #[test]
+    fn rejects_unpaired_surrogates() {
+        let message = "Invalid JSON at line 1, column 3: unpaired surrogate in \\u escape.";
+
+        assert_eq!(first_line(r#"["\ud83d"]"#), message);
+        assert_eq!(first_line(r#"["\ud83dx"]"#), message);
+        assert_eq!(first_line(r#"["\ude00"]"#), message);
+    }
+
+    #[test]
+    fn points_errors_at_their_position() {
+        assert_eq!(first_line("[1,]"), "Invalid JSON at line 1, column 4: expected a value, found ']'.");
+        assert_eq!(first_line("{\"a\" 1}"), "Invalid JSON at line 1, column 6: expected ':', found '1'.");
+        assert_eq!(first_line("{\"a\":1,}"), "Invalid JSON at line 1, column 8: expected a string key, found '}'.");
+        assert_eq!(first_line("[01]"), "Invalid JSON at line 1, column 3: expected ',' or ']', found '1'.");
+        assert_eq!(first_line("[1.]"), "Invalid JSON at line 1, column 4: expected a digit, found ']'.");
+        assert_eq!(first_line("[\"\\x\"]"), "Invalid JSON at line 1, column 4: expected an escape character, found 'x'.");
+        assert_eq!(first_line("[\"a\u{1}\"]"), "Invalid JSON at line 1, column 4: unescaped control character in string.");
+        assert_eq!(first_line("[\"abc"), "Invalid JSON at line 1, column 2: unterminated string.");
+        assert_eq!(first_line("[1] 2"), "Invalid JSON at line 1, column 5: expected end of input, found '2'.");
+        assert_eq!(first_line("[\n1,\n]"), "Invalid JSON at line 3, column 1: expected a value, found ']'.");
+    }
+
+    #[test]
+    fn shows_the_line_with_a_caret() {
+        assert_eq!(
+            parse("{\n\t\"a\": nul\n}").unwrap_err(),
+            "Invalid JSON at line 2, column 10: expected null, found '\\n'.\n \"a\": nul\n         ^",
+        );
+
+        // Long lines are cut to the context around the caret.
+        let long = format!("[{}x]", "1,".repeat(50));
+        let message = parse(&long).unwrap_err();
+        let snippet: Vec<_> = message.lines().skip(1).collect();
+        assert_eq!(snippet, vec![&long[69..103], &format!("{:>33}", "^")[..]]);
+    }
+}
@@ -0,0 +1,191 @@
+use crate::internal::parser::delimiters::tson_delimiters;
+use crate::internal::parser::tson_validator::{InvalidTSON, MAX_DEPTH};
+use std::convert::TryInto;
+
+// Legacy TSON wrote a pair byte between each key and its value, and a
+// separator byte between members and elements.
+const PAIR: u8 = 0x09;
+const SEPARATOR: u8 = 0x0A;
+
+/// Rewrites a legacy value in the current layout, without trusting any of its
+/// lengths. Strings aren't checked, validate the result for that.
+pub fn convert(tson: &[u8]) -> Result<Vec<u8>, InvalidTSON> {
+    let mut converter = Converter { tson, depth: 0, converted: Vec::with_capacity(tson.len()) };
+    let end = converter.value(0)?;
+
+    if end != tson.len() {
+        return Err(invalid(end, "trailing bytes after the value"));
+    }
+
+    Ok(converter.converted)
+}
+
+struct Converter<'a> {
+    tson: &'a [u8],
+    depth: usize,
+    converted: Vec<u8>,
+}
+
+impl Converter<'_> {
+    /// Converts the value at `i` and returns where it ends.
+    fn value(&mut self, i: usize) -> Result<usize, InvalidTSON> {
+        let end = match self.byte(i)? {
+            tson_delimiters::OBJECT_BEGIN => return self.collection(i, tson_delimiters::OBJECT_END, true),
+            tson_delimiters::ARRAY_BEGIN => return self.collection(i, tson_delimiters::ARRAY_END, false),
+            tson_delimiters::STRING => {
+                let len = self.length(i + 1)?;
+                self.take(i + 5, len)?
+            },
+            tson_delimiters::NUMBER | tson_delimiters::DATE | tson_delimiters::INT64 => self.take(i + 1, 8)?,
+            tson_delimiters::DECIMAL128 => self.take(i + 1, 17)?,
+            tson_delimiters::BINARY => {
+                let len = self.length(i + 1)?;
+                self.take(i + 5, len + 1)?
+            },
+            tson_delimiters::TRUE | tson_delimiters::FALSE | tson_delimiters::NULL => i + 1,
+            _ => return Err(invalid(i, "unknown delimiter")),
+        };
+
+        self.converted.extend_from_slice(&self.tson[i..end]);
+
+        Ok(end)
+    }
+    fn collection(&mut self, begin: usize, end_delimiter: u8, keyed: bool) -> Result<usize, InvalidTSON> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(invalid(begin, "nested too deep"));
+        }
+
+        let content = begin + 5; // begin and length
+        let len = self.length(begin + 1)?;
+        let end = self.take(content, len)?;
+
+        if self.byte(end)? != end_delimiter {
+            return Err(invalid(end, "collection end doesn't match its length"));
+        }
+
+        self.converted.push(self.tson[begin]);
+        let start = self.converted.len() + 4;
+        self.converted.extend_from_slice(&[0; 4]);
+
+        let mut i = content;
+
+        while i < end {
+            if i != content {
+                i = self.expect(i, SEPARATOR, "expected a separator")?;
+            }
+
+            if keyed {
+                if self.byte(i)? != tson_delimiters::STRING {
+                    return Err(invalid(i, "expected a string key"));
+                }
+                i = self.value(i)?;
+                i = self.expect(i, PAIR, "expected a pair")?;
+            }
+
+            i = self.value(i)?;
+        }
+
+        if i != end {
+            return Err(invalid(end, "value runs past the end of its collection"));
+        }
+
+        let len = (self.converted.len() - start) as u32;
+        self.converted[start - 4..start].copy_from_slice(&len.to_le_bytes());
+        self.converted.push(end_delimiter);
+
+        self.depth -= 1;
+
+        Ok(end + 1)
+    }
+    fn expect(&self, i: usize, delimiter: u8, reason: &'static str) -> Result<usize, InvalidTSON> {
+        match self.byte(i)? == delimiter {
+            true => Ok(i + 1),
+            false => Err(invalid(i, reason)),
+        }
+    }
+    fn byte(&self, i: usize) -> Result<u8, InvalidTSON> {
+        self.tson.get(i).copied().ok_or_else(|| invalid(i, "unexpected end of value"))
+    }
+    fn length(&self, i: usize) -> Result<usize, InvalidTSON> {
+        match self.tson.get(i..i + 4) {
+            Some(slice) => Ok(u32::from_le_bytes(slice.try_into().unwrap()) as usize),
+            None => Err(invalid(i, "unexpected end of value")),
+        }
+    }
+    /// Skips `len` bytes from `i`, returning where they end.
+    fn take(&self, i: usize, len: usize) -> Result<usize, InvalidTSON> {
+        match i.checked_add(len) {
+            Some(end) if end <= self.tson.len() => Ok(end),
+            _ => Err(invalid(i, "length runs past the end of the value")),
+        }
+    }
+}
+
+fn invalid(offset: usize, reason: &'static str) -> InvalidTSON {
+    InvalidTSON { offset, reason }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::parser::tson_value::{write_array, write_number, write_object, write_string};
+
+    fn legacy_collection(begin: u8, items: Vec<Vec<u8>>, end: u8) -> Vec<u8> {
+        let content = items.join(&SEPARATOR);
+        let mut collection = vec![begin];
+        collection.extend_from_slice(&(content.len() as u32).to_le_bytes());
+        collection.extend(content);
+        collection.push(end);
+        collection
+    }
+
+    fn legacy_object(members: Vec<(&str, Vec<u8>)>) -> Vec<u8> {
+        let members = members.into_iter()
+            .map(|(key, value)| [write_string(key.as_bytes()), vec![PAIR], value].concat())
+            .collect();
+        legacy_collection(tson_delimiters::OBJECT_BEGIN, members, tson_delimiters::OBJECT_END)
+    }
+
+    fn legacy_array(elements: Vec<Vec<u8>>) -> Vec<u8> {
+        legacy_collection(tson_delimiters::ARRAY_BEGIN, elements, tson_delimiters::ARRAY_END)
+    }
+
+    #[test]
+    fn drops_pairs_and_separators() {
+        let legacy = legacy_object(vec![
+            ("a", write_number(1.0)),
+            ("b", legacy_array(vec![write_string(b"x"), legacy_object(vec![]), vec![tson_delimiters::NULL]])),
+        ]);
+        let expected = write_object(&[
+            ("a", write_number(1.0)),
+            ("b", write_array(&[write_string(b"x"), write_object::<&str, Vec<u8>>(&[]), vec![tson_delimiters::NULL]])),
+        ]);
+
+        assert_eq!(convert(&legacy).unwrap(), expected);
+    }
+
+    #[test]
+    fn rejects_values_in_the_current_layout() {
+        let flat = write_object(&[("a", write_number(1.0)), ("b", write_number(2.0))]);
+
+        assert_eq!(convert(&flat).unwrap_err().reason, "expected a pair");
+    }
+
+    #[test]
+    fn rejects_missing_separators() {
+        let content = [write_number(1.0), write_number(2.0)].concat();
+        let array = legacy_collection(tson_delimiters::ARRAY_BEGIN, vec![content], tson_delimiters::ARRAY_END);
+
+        assert_eq!(convert(&array).unwrap_err().reason, "expected a separator");
+    }
+
+    #[test]
+    fn fails_on_truncated_values() {
+        let legacy = legacy_object(vec![("a", legacy_array(vec![write_number(1.0), write_string(b"x")]))]);
+
+        for len in 0..legacy.len() {
+            assert!(convert(&legacy[..len]).is_err(), "{} bytes", len);
+        }
+    }
+}
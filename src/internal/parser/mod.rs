@@ -1,3 +1,4 @@
+#[allow(clippy::module_inception)]
 pub mod parser;
 pub mod delimiters;
 pub mod value_cursor;
@@ -5,9 +6,26 @@ pub mod parsed;
 pub mod tson_parser;
 pub mod json_parser;
 pub mod query_parser;
+pub mod tson_value;
+pub mod extended_json;
+pub mod decimal;
+pub mod parse_error;
+pub mod output_options;
+pub mod tson_validator;
+pub mod compact;
+pub mod legacy_tson;
+pub mod serde_error;
+pub mod tson_serializer;
+pub mod tson_deserializer;
 
 pub use parser::Parser;
+pub use parse_error::ParseError;
+pub use output_options::{OutputOptions, OutputError};
+pub use serde_error::SerdeError;
+pub use tson_serializer::to_tson;
+pub use tson_deserializer::from_tson;
 pub use json_parser::JSONParser;
 pub use tson_parser::TSONParser;
+pub use tson_value::TSONValue;
 pub use delimiters::json_delimiters;
 pub use delimiters::tson_delimiters;
\ No newline at end of file
@@ -0,0 +1,45 @@
+use crate::internal::parser::tson_validator::InvalidTSON;
+use std::fmt;
+
+/// How `TSONParser` writes JSON.
+#[derive(Clone, Default)]
+pub struct OutputOptions {
+    /// Relaxed Extended JSON, wrapping values plain JSON can't hold.
+    pub extended: bool,
+    /// Escapes every non-ASCII character as `\uXXXX`.
+    pub ascii_only: bool,
+    /// Spaces per level of nesting, compact output when `None`.
+    pub indent: Option<usize>,
+    /// Object keys in byte order, so equal documents give equal output.
+    pub sort_keys: bool,
+    pub max_depth: Option<usize>,
+    /// Most bytes of JSON written.
+    pub max_size: Option<usize>,
+}
+
+#[derive(Debug)]
+pub enum OutputError {
+    TooDeep(usize),
+    TooLarge(usize),
+    Invalid(InvalidTSON),
+}
+
+impl OutputOptions {
+    /// Compact output with sorted keys, for hashing and diffing.
+    pub fn canonical() -> OutputOptions {
+        OutputOptions {
+            sort_keys: true,
+            ..OutputOptions::default()
+        }
+    }
+}
+
+impl fmt::Display for OutputError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OutputError::TooDeep(depth) => write!(f, "Document nests deeper than {} levels.", depth),
+            OutputError::TooLarge(size) => write!(f, "Document is larger than {} bytes as JSON.", size),
+            OutputError::Invalid(err) => write!(f, "{}", err),
+        }
+    }
+}
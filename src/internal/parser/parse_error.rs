@@ -0,0 +1,114 @@
+use crate::internal::parser::tson_validator::InvalidTSON;
+use std::fmt;
+
+// Characters of context shown on either side of the caret.
+const SNIPPET_CONTEXT: usize = 32;
+
+#[derive(Debug)]
+pub struct ParseError {
+    context: &'static str,
+    message: String,
+    position: Option<Position>,
+}
+
+#[derive(Debug)]
+struct Position {
+    line: usize,
+    column: usize,
+    snippet: String,
+    caret: usize,
+}
+
+/// Maps TSON offsets back to the JSON they were parsed from, so errors found
+/// in TSON can point into the source.
+pub struct SourceMap {
+    json: Vec<u8>,
+    /// TSON offset of every token with the JSON offset it came from, sorted.
+    offsets: Vec<(usize, usize)>,
+}
+
+impl ParseError {
+    pub fn new(context: &'static str, message: String) -> ParseError {
+        ParseError {
+            context,
+            message,
+            position: None,
+        }
+    }
+    /// An error at `offset` of `json`.
+    pub fn at(context: &'static str, message: String, json: &[u8], offset: usize) -> ParseError {
+        ParseError {
+            context,
+            message,
+            position: Some(Position::new(json, offset)),
+        }
+    }
+    /// An error in TSON, which has no source to point into.
+    pub fn in_tson(context: &'static str, err: InvalidTSON) -> ParseError {
+        ParseError::new(context, format!("{} at byte {} of the TSON", err.reason, err.offset))
+    }
+}
+
+impl Position {
+    fn new(json: &[u8], offset: usize) -> Position {
+        let offset = offset.min(json.len());
+        let before = &json[..offset];
+
+        let line_begin = before.iter().rposition(|val| *val == b'\n').map_or(0, |i| i + 1);
+        let line_end = json[offset..].iter().position(|val| *val == b'\n').map_or(json.len(), |i| offset + i);
+
+        let line = before.iter().filter(|val| **val == b'\n').count() + 1;
+        let column = String::from_utf8_lossy(&json[line_begin..offset]).chars().count() + 1;
+
+        let text: Vec<char> = String::from_utf8_lossy(&json[line_begin..line_end])
+            .trim_end_matches('\r')
+            .chars()
+            .map(|val| if val == '\t' { ' ' } else { val })
+            .collect();
+
+        let from = (column - 1).saturating_sub(SNIPPET_CONTEXT);
+        let to = (column + SNIPPET_CONTEXT).min(text.len());
+
+        Position {
+            line,
+            column,
+            snippet: text[from.min(to)..to].iter().collect(),
+            caret: column - 1 - from,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.position {
+            Some(position) => write!(
+                f,
+                "Invalid {} at line {}, column {}: {}.\n{}\n{:>width$}",
+                self.context,
+                position.line,
+                position.column,
+                self.message,
+                position.snippet,
+                "^",
+                width = position.caret + 1,
+            ),
+            None => write!(f, "Invalid {}: {}.", self.context, self.message),
+        }
+    }
+}
+
+impl SourceMap {
+    pub fn new(json: Vec<u8>, offsets: Vec<(usize, usize)>) -> SourceMap {
+        SourceMap { json, offsets }
+    }
+    /// An error at the JSON token the TSON at `offset` was written for.
+    pub fn error(&self, context: &'static str, message: String, offset: usize) -> ParseError {
+        let i = self.offsets.partition_point(|(tson, _)| *tson <= offset);
+        let json_offset = match i {
+            0 => 0,
+            i => self.offsets[i - 1].1,
+        };
+
+        ParseError::at(context, message, self.json.as_slice(), json_offset)
+    }
+}
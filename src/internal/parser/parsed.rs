@@ -1,4 +1,5 @@
 
+#[derive(Default)]
 pub struct Parsed {
     parsed: Vec<u8>
 }
@@ -26,12 +27,21 @@ impl Parsed {
     pub fn get_parsed_len(&self) -> usize {
         self.parsed.len()
     }
+    pub fn last(&self) -> Option<u8> {
+        self.parsed.last().copied()
+    }
     pub fn write(&mut self, val: u8) {
         self.parsed.push(val);
     }
     pub fn write_slice(&mut self, slice: &[u8]) {
         self.parsed.extend_from_slice(slice);
     }
+    pub fn get_slice_from(&self, start: usize) -> &[u8] {
+        &self.parsed[start..]
+    }
+    pub fn truncate(&mut self, len: usize) {
+        self.parsed.truncate(len);
+    }
     pub fn rewrite_slice(&mut self, start: usize, slice: &[u8]) {
         let end = start + slice.len();
         self.parsed.splice(start..end, slice.iter().cloned());
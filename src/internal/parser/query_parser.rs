@@ -1,12 +1,20 @@
 use crate::internal::parser::Parser;
 use crate::internal::parser::delimiters::tson_delimiters;
 use crate::internal::parser::value_cursor::ValueCursor;
+use crate::internal::parser::decimal::Decimal;
+use crate::internal::parser::parse_error::ParseError;
+use crate::internal::parser::tson_value::{read_decimal, value_len};
 use std::convert::TryInto;
-use std::{str, mem};
+use std::mem;
 
 pub enum EqualityValue {
     String(Vec<u8>),
     Number(f64),
+    Int64(i64),
+    Decimal128(Decimal),
+    Date(i64),
+    Binary(u8, Vec<u8>),
+    ObjectId(Vec<u8>),
     True,
     False,
     Null
@@ -14,6 +22,10 @@ pub enum EqualityValue {
 pub enum ComparisonValue {
     String(Vec<u8>),
     Number(f64),
+    Int64(i64),
+    Decimal128(Decimal),
+    Date(i64),
+    ObjectId(Vec<u8>),
 }
 pub type ArrayValue = Vec<EqualityValue>;
 pub enum Operation {
@@ -46,29 +58,32 @@ enum QueryType {
     Logic,
 }
 
+const CONTEXT: &str = "query";
+
 pub struct QueryParser {
-    tson: Vec<u8>,
+    cursor: ValueCursor,
 }
 
 impl QueryParser {
-    pub fn new(tson: Vec<u8>) -> QueryParser {
+    /// Takes a cursor over a whole filter object.
+    pub fn new(cursor: ValueCursor) -> QueryParser {
         let begin = 5; // without object_begin
-        let end = tson.len() - 1; // without object_end
+        let end = cursor.get_value_ref().len() - 1; // without object_end
 
-        Self::from_objectless(tson[begin..end].to_vec())
+        Self::from_objectless(cursor.slice(begin..end))
     }
-    fn from_objectless(tson: Vec<u8>) -> QueryParser {
-        QueryParser { tson }
+    fn from_objectless(cursor: ValueCursor) -> QueryParser {
+        QueryParser { cursor }
     }
 }
 impl QueryParser {
-    pub fn parse(self) -> LogicalOperation {
-        let decider = Decider::new(self.tson);
-        let (tson, query_type) = decider.parse();
+    pub fn parse(self) -> Result<LogicalOperation, ParseError> {
+        let decider = Decider::new(self.cursor);
+        let (cursor, query_type) = decider.parse()?;
 
         match query_type {
-            QueryType::Operation => LogicalOperation::No(OperationParser::new(tson).parse()),
-            QueryType::Logic => LogicParser::new(tson).parse(),
+            QueryType::Operation => Ok(LogicalOperation::No(OperationParser::new(cursor).parse()?)),
+            QueryType::Logic => LogicParser::new(cursor).parse(),
         }
     }
 }
@@ -76,19 +91,21 @@ impl QueryParser {
 struct Decider {
     cursor: ValueCursor,
     result: QueryType,
+    error: Option<ParseError>,
 }
 
 impl Decider {
-    fn new(tson: Vec<u8>) -> Decider {
+    fn new(cursor: ValueCursor) -> Decider {
         Decider {
-            cursor: ValueCursor::new(tson),
+            cursor,
             result: QueryType::Operation,
+            error: None,
         }
     }
 }
 
 impl Parser for Decider {
-    type Parsed = (Vec<u8>, QueryType);
+    type Parsed = Result<(ValueCursor, QueryType), ParseError>;
 
     fn get_index(&self) -> usize {
         self.cursor.get_index()
@@ -96,64 +113,44 @@ impl Parser for Decider {
     fn get_original_len(&self) -> usize {
         self.cursor.get_value_ref().len()
     }
-    fn get_parsed(self) -> Self::Parsed {
-        (self.cursor.get_value(), self.result)
+    fn get_parsed(mut self) -> Self::Parsed {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+        self.cursor.rewind();
+        Ok((self.cursor, self.result))
     }
     fn parse_next(&mut self) {
-        match self.cursor.read_next() {
+        let result = match self.cursor.read_next() {
             tson_delimiters::STRING => self.decide(),
-            tson_delimiters::OBJECT_BEGIN => self.skip_collection(),
-            tson_delimiters::ARRAY_BEGIN => self.skip_collection(),
-            tson_delimiters::NUMBER => self.skip_number(),
-            tson_delimiters::SEPARATOR => (),
-            _ => self.skip_single(),
+            val => Err(unexpected(&self.cursor, "a key", val)),
+        };
+
+        if let Err(err) = result {
+            self.error = Some(err);
+            self.cursor.skip_rest();
         }
     }
 }
 
 impl Decider {
-    fn decide(&mut self) {
+    fn decide(&mut self) -> Result<(), ParseError> {
         let string = self.read_string();
 
-        let slice = string.as_slice();
-
-        let slice = unsafe {
-            str::from_utf8_unchecked(slice)
-        };
-
-        match slice {
-            "$or" => self.logic(),
-            "$and" => self.logic(),
-            "$not" => self.logic(),
-            _ => {
-                self.cursor.skip_next();
-                match self.cursor.read_next() {
-                    tson_delimiters::STRING => self.skip_string(),
-                    tson_delimiters::OBJECT_BEGIN => self.skip_collection(),
-                    tson_delimiters::ARRAY_BEGIN => self.skip_collection(),
-                    tson_delimiters::NUMBER => self.skip_number(),
-                    val => panic!("Unexpected error at {}!", val),
-                }
-            }
+        match string.as_slice() {
+            b"$or" | b"$and" | b"$not" => self.logic(),
+            _ => self.skip_value(),
         }
+
+        Ok(())
     }
     fn logic(&mut self) {
         self.cursor.skip_rest();
         self.result = QueryType::Logic;
     }
-    fn skip_string(&mut self) {
-        let len = self.read_length();
-        self.cursor.skip_by(len as usize);
-    }
-    fn skip_collection(&mut self) {
-        let len = self.read_length() + 1; // inclusive collection end
-        self.cursor.skip_by(len as usize);
-    }
-    fn skip_number(&mut self) {
-        self.cursor.skip_by(8);
-    }
-    fn skip_single(&mut self) {
-        self.cursor.skip_next();
+    fn skip_value(&mut self) {
+        let len = value_len(&self.cursor.get_value_ref()[self.get_index()..]);
+        self.cursor.skip_by(len);
     }
 }
 
@@ -171,81 +168,78 @@ impl Decider {
 struct LogicParser {
     cursor: ValueCursor,
     operations: Vec<LogicalOperation>,
+    error: Option<ParseError>,
 }
 
 impl LogicParser {
-    fn new(tson: Vec<u8>) -> LogicParser {
+    fn new(cursor: ValueCursor) -> LogicParser {
         LogicParser {
-            cursor: ValueCursor::new(tson),
+            cursor,
             operations: Vec::new(),
+            error: None,
         }
     }
 }
 
 impl Parser for LogicParser {
-    type Parsed = LogicalOperation;
+    type Parsed = Result<LogicalOperation, ParseError>;
     fn get_index(&self) -> usize {
         self.cursor.get_index()
     }
     fn get_original_len(&self) -> usize {
         self.cursor.get_value_ref().len()
     }
-    fn get_parsed(self) -> LogicalOperation {
+    fn get_parsed(self) -> Self::Parsed {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+
         let mut operations = self.operations;
         match operations.len() {
-            0 => panic!("Unexpected query syntax!"),
-            1 => operations.pop().unwrap(),
-            _ => LogicalOperation::And(operations),
+            0 => Err(self.cursor.error_at(0, CONTEXT, "expected a filter".to_string())),
+            1 => Ok(operations.pop().unwrap()),
+            _ => Ok(LogicalOperation::And(operations)),
         }
     }
     fn parse_next(&mut self) {
-        match self.cursor.read_next() {
+        let result = match self.cursor.read_next() {
             tson_delimiters::STRING => self.decide(),
-            tson_delimiters::SEPARATOR => (),
-            val => panic!("Unexpected query syntax! Error at {}", val),
+            val => Err(unexpected(&self.cursor, "a key", val)),
+        };
+
+        if let Err(err) = result {
+            self.error = Some(err);
+            self.cursor.skip_rest();
         }
     }
 }
 
 impl LogicParser {
-    fn decide(&mut self) {
+    fn decide(&mut self) -> Result<(), ParseError> {
         let begin = self.get_index() - 1;
         let string = self.read_string();
 
-        let slice = string.as_slice();
-
-        let slice = unsafe {
-            str::from_utf8_unchecked(slice)
-        };
-
-        match slice {
-            "$or" => self.op_or(),
-            "$and" => self.op_and(),
-            "$not" => todo!("$not isn't supported yet"),
+        match string.as_slice() {
+            b"$or" => self.op_or(),
+            b"$and" => self.op_and(),
+            b"$not" => Err(self.cursor.error_at(begin, CONTEXT, "$not isn't supported yet".to_string())),
             _ => {
-                self.cursor.skip_next(); // skips PAIR
-
-                let length = match self.cursor.read_next() {
-                    tson_delimiters::OBJECT_BEGIN => self.read_length() + 1, // object_end inclusive
-                    val => panic!("Unexpected query syntax! Error at {}", val),
-                } as usize;
-
-                self.cursor.skip_by(length);
+                let len = value_len(&self.cursor.get_value_ref()[self.get_index()..]);
+                self.cursor.skip_by(len);
                 let end = self.get_index();
 
-                let pair = self.cursor.read_range(begin..end).to_vec();
-                let parser = QueryParser::from_objectless(pair);
+                let parser = QueryParser::from_objectless(self.cursor.slice(begin..end));
+
+                self.operations.push(parser.parse()?);
 
-                self.operations.push(parser.parse());
+                Ok(())
             },
         }
     }
-    fn get_array(&mut self) -> Vec<LogicalOperation> {
-        self.cursor.skip_next();
-
+    fn get_array(&mut self, operator: &str) -> Result<Vec<LogicalOperation>, ParseError> {
         match self.cursor.read_next() {
             tson_delimiters::ARRAY_BEGIN => (),
-            val => panic!("Unexpected query syntax! Error at {}", val),
+            val => return Err(unexpected(&self.cursor, &format!("an array of filters for {}", operator), val)),
         }
 
         self.cursor.skip_by(4);
@@ -261,28 +255,28 @@ impl LogicParser {
                     self.cursor.skip_by(len);
 
                     let end = self.get_index();
-                    let pair = self.cursor.read_range(begin..end).to_vec();
 
-                    let parser = QueryParser::from_objectless(pair);
-                    let logic = parser.parse();
+                    let parser = QueryParser::from_objectless(self.cursor.slice(begin..end));
+                    let logic = parser.parse()?;
                     operations.push(logic);
                 },
-                tson_delimiters::SEPARATOR => (),
                 tson_delimiters::OBJECT_END => (),
                 tson_delimiters::ARRAY_END => break,
-                val => panic!("Unexpected query syntax! Error at {}", val),
+                val => return Err(unexpected(&self.cursor, &format!("a filter object in {}", operator), val)),
             }
         }
 
-        operations
+        Ok(operations)
     }
-    fn op_and(&mut self) {
-        let operations = self.get_array();
+    fn op_and(&mut self) -> Result<(), ParseError> {
+        let operations = self.get_array("$and")?;
         self.operations.push(LogicalOperation::And(operations));
+        Ok(())
     }
-    fn op_or(&mut self) {
-        let operations = self.get_array();
+    fn op_or(&mut self) -> Result<(), ParseError> {
+        let operations = self.get_array("$or")?;
         self.operations.push(LogicalOperation::Or(operations));
+        Ok(())
     }
 }
 
@@ -302,51 +296,55 @@ struct OperationParser {
     in_object: InObject,
     key: Option<Vec<u8>>,
     operations: Vec<NamespacedOperation>,
+    error: Option<ParseError>,
 }
 
 impl OperationParser {
-    fn new(tson: Vec<u8>) -> OperationParser {
+    fn new(cursor: ValueCursor) -> OperationParser {
         OperationParser {
-            cursor: ValueCursor::new(tson),
+            cursor,
             in_object: InObject::No,
             key: None,
             operations: Vec::new(),
+            error: None,
         }
     }
 }
 
 impl Parser for OperationParser {
-    type Parsed = Vec<NamespacedOperation>;
+    type Parsed = Result<Vec<NamespacedOperation>, ParseError>;
     fn get_index(&self) -> usize {
         self.cursor.get_index()
     }
     fn get_original_len(&self) -> usize {
         self.cursor.get_value_ref().len()
     }
-    fn get_parsed(self) -> Vec<NamespacedOperation> {
-        self.operations
+    fn get_parsed(self) -> Self::Parsed {
+        match self.error {
+            Some(err) => Err(err),
+            None => Ok(self.operations),
+        }
     }
     fn parse_next(&mut self) {
-        match self.cursor.read_next() {
-            tson_delimiters::OBJECT_END => self.end_object(),
-            tson_delimiters::STRING => self.write_key_or_operation(),
-            tson_delimiters::SEPARATOR => (),
-            tson_delimiters::ARRAY_END => (),
-            v => {
-                println!("{:?}", self.cursor.get_value_ref());
-                println!("{}", self.get_index());
-                println!("val = {}, {}", v, v as char);
-                panic!("Unexpected error while parsing!");
+        let result = match self.cursor.read_next() {
+            tson_delimiters::OBJECT_END => {
+                self.end_object();
+                Ok(())
             },
+            tson_delimiters::STRING => self.write_key_or_operation(),
+            tson_delimiters::ARRAY_END => Ok(()),
+            val => Err(unexpected(&self.cursor, "a key", val)),
+        };
+
+        if let Err(err) = result {
+            self.error = Some(err);
+            self.cursor.skip_rest();
         }
     }
 }
 
 impl OperationParser {
     fn begin_object(&mut self) {
-        if let InObject::Yes = self.in_object {
-            panic!("Invalid query syntax, use dot notation for nested queries.");
-        }
         self.cursor.skip_by(4);
         self.in_object = InObject::Yes;
     }
@@ -356,28 +354,21 @@ impl OperationParser {
             self.in_object = InObject::No;
         }
     }
-    fn write_key_or_operation(&mut self) {
+    fn write_key_or_operation(&mut self) -> Result<(), ParseError> {
+        let begin = self.get_index() - 1;
         let string = self.read_string();
-        let slice = string.as_slice();
-
-        // Value passed from JSON.stringify is UTF-8, so no need to check.
-        let slice = unsafe {
-            str::from_utf8_unchecked(slice)
-        };
 
-        self.cursor.skip_next();
-
-        match slice {
-            "$eq" => self.op_eq(),
-            "$ne" => self.op_ne(),
-            "$lt" => self.op_lt(),
-            "$lte" => self.op_lte(),
-            "$gt" => self.op_gt(),
-            "$gte" => self.op_gte(),
-            "$in" => self.op_in(),
-            "$nin" => self.op_nin(),
-            "$elemMatch" => todo!("$elemMatch isn't supported yet"),
-            _ => self.no_op(string),
+        match string.as_slice() {
+            b"$eq" => self.op_eq(begin),
+            b"$ne" => self.op_ne(begin),
+            b"$lt" => self.op_lt(begin),
+            b"$lte" => self.op_lte(begin),
+            b"$gt" => self.op_gt(begin),
+            b"$gte" => self.op_gte(begin),
+            b"$in" => self.op_in(begin),
+            b"$nin" => self.op_nin(begin),
+            b"$elemMatch" => Err(self.cursor.error_at(begin, CONTEXT, "$elemMatch isn't supported yet".to_string())),
+            _ => self.no_op(begin, string),
         }
     }
 }
@@ -390,29 +381,43 @@ impl OperationParser {
             operation,
         })
     }
-    fn check_comparison_validity(&mut self) {
-        if let InObject::No = self.in_object {
-            panic!("Comparison operators cannot be used without a preceding key.");
+    fn check_comparison_validity(&self, begin: usize, operator: &str) -> Result<(), ParseError> {
+        match self.in_object {
+            InObject::Yes => Ok(()),
+            InObject::No => Err(self.cursor.error_at(begin, CONTEXT, format!("{} must be used under a key", operator))),
         }
     }
-    fn equality_value(&mut self) -> EqualityValue {
-        match self.cursor.read_next() {
+    fn equality_value(&mut self, operator: &str) -> Result<EqualityValue, ParseError> {
+        let value = match self.cursor.read_next() {
             tson_delimiters::STRING => EqualityValue::String(self.read_string().to_vec()),
             tson_delimiters::NUMBER => EqualityValue::Number(self.read_number()),
+            tson_delimiters::INT64 => EqualityValue::Int64(self.read_i64()),
+            tson_delimiters::DECIMAL128 => EqualityValue::Decimal128(self.read_decimal()),
+            tson_delimiters::DATE => EqualityValue::Date(self.read_i64()),
+            tson_delimiters::BINARY => self.read_binary(),
+            tson_delimiters::OBJECT_ID => EqualityValue::ObjectId(self.cursor.read_by(12).to_vec()),
             tson_delimiters::TRUE => EqualityValue::True,
             tson_delimiters::FALSE => EqualityValue::False,
             tson_delimiters::NULL => EqualityValue::Null,
-            val => panic!("Invalid query syntax! Error at: {}", val),
-        }
+            val => return Err(unexpected(&self.cursor, &format!("a scalar value for {}", operator), val)),
+        };
+
+        Ok(value)
     }
-    fn comparison_value(&mut self) -> ComparisonValue {
-        match self.cursor.read_next() {
+    fn comparison_value(&mut self, operator: &str) -> Result<ComparisonValue, ParseError> {
+        let value = match self.cursor.read_next() {
             tson_delimiters::STRING => ComparisonValue::String(self.read_string().to_vec()),
             tson_delimiters::NUMBER => ComparisonValue::Number(self.read_number()),
-            val => panic!("Invalid query syntax! Error at: {}", val),
-        }
+            tson_delimiters::INT64 => ComparisonValue::Int64(self.read_i64()),
+            tson_delimiters::DECIMAL128 => ComparisonValue::Decimal128(self.read_decimal()),
+            tson_delimiters::DATE => ComparisonValue::Date(self.read_i64()),
+            tson_delimiters::OBJECT_ID => ComparisonValue::ObjectId(self.cursor.read_by(12).to_vec()),
+            val => return Err(unexpected(&self.cursor, &format!("a string, number, date or ObjectId for {}", operator), val)),
+        };
+
+        Ok(value)
     }
-    fn array_value(&mut self) -> ArrayValue {
+    fn array_value(&mut self, operator: &str) -> Result<ArrayValue, ParseError> {
         match self.cursor.read_next() {
             tson_delimiters::ARRAY_BEGIN => {
                 let mut values = ArrayValue::new();
@@ -422,22 +427,26 @@ impl OperationParser {
                     let value = match self.cursor.read_next() {
                         tson_delimiters::STRING => EqualityValue::String(self.read_string().to_vec()),
                         tson_delimiters::NUMBER => EqualityValue::Number(self.read_number()),
+                        tson_delimiters::INT64 => EqualityValue::Int64(self.read_i64()),
+                        tson_delimiters::DECIMAL128 => EqualityValue::Decimal128(self.read_decimal()),
+                        tson_delimiters::DATE => EqualityValue::Date(self.read_i64()),
+                        tson_delimiters::BINARY => self.read_binary(),
+                        tson_delimiters::OBJECT_ID => EqualityValue::ObjectId(self.cursor.read_by(12).to_vec()),
                         tson_delimiters::TRUE => EqualityValue::True,
                         tson_delimiters::FALSE => EqualityValue::False,
                         tson_delimiters::NULL => EqualityValue::Null,
-                        tson_delimiters::SEPARATOR => continue,
                         tson_delimiters::ARRAY_END => break,
-                        val => panic!("Invalid query syntax! Error at: {}", val),
+                        val => return Err(unexpected(&self.cursor, &format!("scalar values in {}", operator), val)),
                     };
                     values.push(value);
                 }
 
-                values
+                Ok(values)
             },
-            val => panic!("Invalid query syntax! Error at: {}", val),
+            val => Err(unexpected(&self.cursor, &format!("an array for {}", operator), val)),
         }
     }
-    fn no_op(&mut self, string: Vec<u8>) {
+    fn no_op(&mut self, begin: usize, string: Vec<u8>) -> Result<(), ParseError> {
         match self.in_object {
             InObject::No => {
                 self.key = Some(string);
@@ -446,53 +455,68 @@ impl OperationParser {
                     _ => {
                         self.cursor.skip_reverse_by(1);
                         self.in_object = InObject::Yes;
-                        self.op_eq();
+                        self.op_eq(begin)?;
                         self.end_object();
                     }
                 }
+                Ok(())
+            },
+            InObject::Yes => {
+                let message = format!(
+                    "unknown operator {:?}, use dot notation for nested queries",
+                    String::from_utf8_lossy(&string),
+                );
+                Err(self.cursor.error_at(begin, CONTEXT, message))
             },
-            InObject::Yes => panic!("Invalid query syntax, use dot notation for nested queries."),
         }
     }
-    fn op_eq(&mut self) {
-        self.check_comparison_validity();
-        let value = self.equality_value();
+    fn op_eq(&mut self, begin: usize) -> Result<(), ParseError> {
+        self.check_comparison_validity(begin, "$eq")?;
+        let value = self.equality_value("$eq")?;
         self.add_operation(Operation::Eq(value));
+        Ok(())
     }
-    fn op_ne(&mut self) {
-        self.check_comparison_validity();
-        let value = self.equality_value();
+    fn op_ne(&mut self, begin: usize) -> Result<(), ParseError> {
+        self.check_comparison_validity(begin, "$ne")?;
+        let value = self.equality_value("$ne")?;
         self.add_operation(Operation::Ne(value));
+        Ok(())
     }
-    fn op_lt(&mut self) {
-        self.check_comparison_validity();
-        let value = self.comparison_value();
+    fn op_lt(&mut self, begin: usize) -> Result<(), ParseError> {
+        self.check_comparison_validity(begin, "$lt")?;
+        let value = self.comparison_value("$lt")?;
         self.add_operation(Operation::Lt(value));
+        Ok(())
     }
-    fn op_lte(&mut self) {
-        self.check_comparison_validity();
-        let value = self.comparison_value();
+    fn op_lte(&mut self, begin: usize) -> Result<(), ParseError> {
+        self.check_comparison_validity(begin, "$lte")?;
+        let value = self.comparison_value("$lte")?;
         self.add_operation(Operation::Lte(value));
+        Ok(())
     }
-    fn op_gt(&mut self) {
-        self.check_comparison_validity();
-        let value = self.comparison_value();
+    fn op_gt(&mut self, begin: usize) -> Result<(), ParseError> {
+        self.check_comparison_validity(begin, "$gt")?;
+        let value = self.comparison_value("$gt")?;
         self.add_operation(Operation::Gt(value));
+        Ok(())
     }
-    fn op_gte(&mut self) {
-        self.check_comparison_validity();
-        let value = self.comparison_value();
+    fn op_gte(&mut self, begin: usize) -> Result<(), ParseError> {
+        self.check_comparison_validity(begin, "$gte")?;
+        let value = self.comparison_value("$gte")?;
         self.add_operation(Operation::Gte(value));
+        Ok(())
     }
-    fn op_in(&mut self) {
-        self.check_comparison_validity();
-        let value = self.array_value();
+    fn op_in(&mut self, begin: usize) -> Result<(), ParseError> {
+        self.check_comparison_validity(begin, "$in")?;
+        let value = self.array_value("$in")?;
         self.add_operation(Operation::In(value));
+        Ok(())
     }
-    fn op_nin(&mut self) {
-        self.check_comparison_validity();
-        let value = self.array_value();
+    fn op_nin(&mut self, begin: usize) -> Result<(), ParseError> {
+        self.check_comparison_validity(begin, "$nin")?;
+        let value = self.array_value("$nin")?;
         self.add_operation(Operation::Nin(value));
+        Ok(())
     }
 }
 
@@ -528,4 +552,35 @@ impl OperationParser {
         let slice = self.cursor.read_by(8);
         f64::from_le_bytes(slice.try_into().unwrap())
     }
-}
\ No newline at end of file
+    fn read_i64(&mut self) -> i64 {
+        let slice = self.cursor.read_by(8);
+        i64::from_le_bytes(slice.try_into().unwrap())
+    }
+    fn read_decimal(&mut self) -> Decimal {
+        read_decimal(self.cursor.read_by(17))
+    }
+    fn read_binary(&mut self) -> EqualityValue {
+        let length = self.read_length();
+        let subtype = self.cursor.read_next();
+        EqualityValue::Binary(subtype, self.cursor.read_by(length as usize).to_vec())
+    }
+}
+
+/// An error at the value just read, naming what was expected instead.
+fn unexpected(cursor: &ValueCursor, expected: &str, val: u8) -> ParseError {
+    let found = match val {
+        tson_delimiters::OBJECT_BEGIN => "an object",
+        tson_delimiters::ARRAY_BEGIN => "an array",
+        tson_delimiters::STRING => "a string",
+        tson_delimiters::NUMBER | tson_delimiters::INT64 | tson_delimiters::DECIMAL128 => "a number",
+        tson_delimiters::DATE => "a date",
+        tson_delimiters::BINARY => "a binary",
+        tson_delimiters::OBJECT_ID => "an ObjectId",
+        tson_delimiters::TRUE | tson_delimiters::FALSE => "a boolean",
+        tson_delimiters::NULL => "null",
+        _ => "malformed TSON",
+    };
+
+    let message = format!("expected {}, found {}", expected, found);
+    cursor.error_at(cursor.get_index() - 1, CONTEXT, message)
+}
@@ -0,0 +1,34 @@
+use std::fmt;
+use serde::{ser, de};
+
+/// Failure converting between Rust values and TSON.
+#[derive(Debug)]
+pub struct SerdeError {
+    message: String,
+}
+
+impl SerdeError {
+    pub fn new<T: fmt::Display>(message: T) -> SerdeError {
+        SerdeError { message: message.to_string() }
+    }
+}
+
+impl fmt::Display for SerdeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for SerdeError {}
+
+impl ser::Error for SerdeError {
+    fn custom<T: fmt::Display>(message: T) -> SerdeError {
+        SerdeError::new(message)
+    }
+}
+
+impl de::Error for SerdeError {
+    fn custom<T: fmt::Display>(message: T) -> SerdeError {
+        SerdeError::new(message)
+    }
+}
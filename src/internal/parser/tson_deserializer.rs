@@ -0,0 +1,416 @@
+use serde::de::{self, Visitor, DeserializeOwned, IntoDeserializer};
+use serde::forward_to_deserialize_any;
+use std::convert::{TryFrom, TryInto};
+use crate::internal::parser::delimiters::tson_delimiters;
+use crate::internal::parser::value_cursor::ValueCursor;
+use crate::internal::parser::decimal::Decimal;
+use crate::internal::parser::tson_value::read_decimal;
+use crate::internal::parser::extended_json::format_object_id;
+use crate::internal::parser::tson_validator::validate;
+use crate::internal::parser::serde_error::SerdeError;
+
+/// Deserializes a TSON value. Any numeric type reads into any integer or
+/// float it fits in exactly, dates read as milliseconds since the epoch.
+pub fn from_tson<T: DeserializeOwned>(tson: &[u8]) -> Result<T, SerdeError> {
+    validate(tson).map_err(SerdeError::new)?;
+
+    let mut deserializer = TSONDeserializer { cursor: ValueCursor::new(tson.to_vec()) };
+    T::deserialize(&mut deserializer)
+}
+
+pub struct TSONDeserializer {
+    cursor: ValueCursor,
+}
+
+impl TSONDeserializer {
+    fn read_length(&mut self) -> usize {
+        u32::from_le_bytes(self.cursor.read_by(4).try_into().unwrap()) as usize
+    }
+    fn read_string(&mut self) -> Result<String, SerdeError> {
+        let length = self.read_length();
+        String::from_utf8(self.cursor.read_by(length).to_vec()).map_err(SerdeError::new)
+    }
+    fn read_f64(&mut self) -> f64 {
+        f64::from_le_bytes(self.cursor.read_by(8).try_into().unwrap())
+    }
+    fn read_i64(&mut self) -> i64 {
+        i64::from_le_bytes(self.cursor.read_by(8).try_into().unwrap())
+    }
+    fn read_decimal(&mut self) -> Decimal {
+        read_decimal(self.cursor.read_by(17))
+    }
+    /// Reads a number of any type that's an integer.
+    fn read_integer(&mut self) -> Result<i128, SerdeError> {
+        match self.cursor.read_next() {
+            tson_delimiters::NUMBER => {
+                let number = self.read_f64();
+                match number.fract() == 0.0 && number.abs() < i128::MAX as f64 {
+                    true => Ok(number as i128),
+                    false => Err(SerdeError::new(format!("expected an integer, found {}", number))),
+                }
+            },
+            tson_delimiters::INT64 | tson_delimiters::DATE => Ok(self.read_i64() as i128),
+            tson_delimiters::DECIMAL128 => {
+                let decimal = self.read_decimal().normalize();
+                match decimal.scale {
+                    0 => Ok(decimal.coefficient),
+                    _ => Err(SerdeError::new(format!("expected an integer, found {}", decimal))),
+                }
+            },
+            val => Err(unexpected("an integer", val)),
+        }
+    }
+    fn read_float(&mut self) -> Result<f64, SerdeError> {
+        match self.cursor.read_next() {
+            tson_delimiters::NUMBER => Ok(self.read_f64()),
+            tson_delimiters::INT64 => Ok(self.read_i64() as f64),
+            tson_delimiters::DECIMAL128 => Ok(self.read_decimal().to_f64()),
+            val => Err(unexpected("a number", val)),
+        }
+    }
+    /// Reads a collection's delimiter and length and returns where it ends.
+    fn begin_collection(&mut self) -> usize {
+        self.cursor.skip_next();
+        let len = self.read_length();
+        self.cursor.get_index() + len
+    }
+}
+
+macro_rules! deserialize_integer {
+    ($($method:ident => $visit:ident: $ty:ty),*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+                let integer = self.read_integer()?;
+                let value = <$ty>::try_from(integer)
+                    .map_err(|_| SerdeError::new(format!("{} doesn't fit in {}", integer, stringify!($ty))))?;
+                visitor.$visit(value)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for &mut TSONDeserializer {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.cursor.peek() {
+            tson_delimiters::OBJECT_BEGIN => {
+                let end = self.begin_collection();
+                let value = visitor.visit_map(Members { deserializer: &mut *self, end })?;
+                self.cursor.skip_next(); // OBJECT_END
+                Ok(value)
+            },
+            tson_delimiters::ARRAY_BEGIN => {
+                let end = self.begin_collection();
+                let value = visitor.visit_seq(Elements { deserializer: &mut *self, end })?;
+                self.cursor.skip_next(); // ARRAY_END
+                Ok(value)
+            },
+            tson_delimiters::STRING => {
+                self.cursor.skip_next();
+                visitor.visit_string(self.read_string()?)
+            },
+            tson_delimiters::NUMBER => {
+                self.cursor.skip_next();
+                visitor.visit_f64(self.read_f64())
+            },
+            tson_delimiters::INT64 | tson_delimiters::DATE => {
+                self.cursor.skip_next();
+                visitor.visit_i64(self.read_i64())
+            },
+            tson_delimiters::DECIMAL128 => {
+                self.cursor.skip_next();
+                let decimal = self.read_decimal().normalize();
+                match decimal.scale {
+                    0 => visitor.visit_i128(decimal.coefficient),
+                    _ => visitor.visit_f64(decimal.to_f64()),
+                }
+            },
+            tson_delimiters::BINARY => {
+                self.cursor.skip_next();
+                let length = self.read_length();
+                self.cursor.skip_next(); // subtype
+                visitor.visit_byte_buf(self.cursor.read_by(length).to_vec())
+            },
+            // ObjectIds deserialize as their hex digits.
+            tson_delimiters::OBJECT_ID => {
+                self.cursor.skip_next();
+                visitor.visit_string(format_object_id(self.cursor.read_by(12)))
+            },
+            tson_delimiters::TRUE => {
+                self.cursor.skip_next();
+                visitor.visit_bool(true)
+            },
+            tson_delimiters::FALSE => {
+                self.cursor.skip_next();
+                visitor.visit_bool(false)
+            },
+            tson_delimiters::NULL => {
+                self.cursor.skip_next();
+                visitor.visit_unit()
+            },
+            val => Err(unexpected("a value", val)),
+        }
+    }
+
+    deserialize_integer! {
+        deserialize_i8 => visit_i8: i8,
+        deserialize_i16 => visit_i16: i16,
+        deserialize_i32 => visit_i32: i32,
+        deserialize_i64 => visit_i64: i64,
+        deserialize_i128 => visit_i128: i128,
+        deserialize_u8 => visit_u8: u8,
+        deserialize_u16 => visit_u16: u16,
+        deserialize_u32 => visit_u32: u32,
+        deserialize_u64 => visit_u64: u64,
+        deserialize_u128 => visit_u128: u128
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_f32(self.read_float()? as f32)
+    }
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_f64(self.read_float()?)
+    }
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.cursor.peek() {
+            tson_delimiters::NULL => {
+                self.cursor.skip_next();
+                visitor.visit_none()
+            },
+            _ => visitor.visit_some(self),
+        }
+    }
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+    /// Unit variants are strings, others objects with the variant as their
+    /// only key.
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        match self.cursor.read_next() {
+            tson_delimiters::STRING => visitor.visit_enum(self.read_string()?.into_deserializer()),
+            tson_delimiters::OBJECT_BEGIN => {
+                let len = self.read_length();
+                let end = self.cursor.get_index() + len;
+
+                self.cursor.skip_next(); // STRING
+                let variant = self.read_string()?;
+                let value = visitor.visit_enum(Variant { deserializer: &mut *self, variant })?;
+
+                if self.cursor.get_index() != end {
+                    return Err(SerdeError::new("expected an object with a single variant key"));
+                }
+                self.cursor.skip_next(); // OBJECT_END
+
+                Ok(value)
+            },
+            val => Err(unexpected("a string or an object for an enum", val)),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool char str string bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct Members<'a> {
+    deserializer: &'a mut TSONDeserializer,
+    end: usize,
+}
+
+impl<'de> de::MapAccess<'de> for Members<'_> {
+    type Error = SerdeError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, SerdeError> {
+        if self.deserializer.cursor.get_index() == self.end {
+            return Ok(None);
+        }
+
+        self.deserializer.cursor.skip_next(); // STRING
+        let key = self.deserializer.read_string()?;
+        seed.deserialize(Key { key }).map(Some)
+    }
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, SerdeError> {
+        seed.deserialize(&mut *self.deserializer)
+    }
+}
+
+struct Elements<'a> {
+    deserializer: &'a mut TSONDeserializer,
+    end: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for Elements<'_> {
+    type Error = SerdeError;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, SerdeError> {
+        if self.deserializer.cursor.get_index() == self.end {
+            return Ok(None);
+        }
+
+        seed.deserialize(&mut *self.deserializer).map(Some)
+    }
+}
+
+struct Variant<'a> {
+    deserializer: &'a mut TSONDeserializer,
+    variant: String,
+}
+
+impl<'de, 'a> de::EnumAccess<'de> for Variant<'a> {
+    type Error = SerdeError;
+    type Variant = &'a mut TSONDeserializer;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self::Variant), SerdeError> {
+        let variant = seed.deserialize(self.variant.into_deserializer())?;
+        Ok((variant, self.deserializer))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for &mut TSONDeserializer {
+    type Error = SerdeError;
+
+    fn unit_variant(self) -> Result<(), SerdeError> {
+        de::Deserialize::deserialize(self)
+    }
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, SerdeError> {
+        seed.deserialize(self)
+    }
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, SerdeError> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+    fn struct_variant<V: Visitor<'de>>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, SerdeError> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
+
+/// Object keys, which also read as integers for maps keyed by them.
+struct Key {
+    key: String,
+}
+
+macro_rules! deserialize_key_integer {
+    ($($method:ident => $visit:ident: $ty:ty),*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+                let value: $ty = self.key.parse()
+                    .map_err(|_| SerdeError::new(format!("expected an integer key, found {:?}", self.key)))?;
+                visitor.$visit(value)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Key {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_string(self.key)
+    }
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        visitor.visit_enum(self.key.into_deserializer())
+    }
+
+    deserialize_key_integer! {
+        deserialize_i8 => visit_i8: i8,
+        deserialize_i16 => visit_i16: i16,
+        deserialize_i32 => visit_i32: i32,
+        deserialize_i64 => visit_i64: i64,
+        deserialize_i128 => visit_i128: i128,
+        deserialize_u8 => visit_u8: u8,
+        deserialize_u16 => visit_u16: u16,
+        deserialize_u32 => visit_u32: u32,
+        deserialize_u64 => visit_u64: u64,
+        deserialize_u128 => visit_u128: u128
+    }
+
+    forward_to_deserialize_any! {
+        bool f32 f64 char str string bytes byte_buf option unit unit_struct
+        seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+fn unexpected(expected: &str, val: u8) -> SerdeError {
+    let found = match val {
+        tson_delimiters::OBJECT_BEGIN => "an object",
+        tson_delimiters::ARRAY_BEGIN => "an array",
+        tson_delimiters::STRING => "a string",
+        tson_delimiters::NUMBER | tson_delimiters::INT64 | tson_delimiters::DECIMAL128 => "a number",
+        tson_delimiters::DATE => "a date",
+        tson_delimiters::BINARY => "a binary",
+        tson_delimiters::OBJECT_ID => "an ObjectId",
+        tson_delimiters::TRUE | tson_delimiters::FALSE => "a boolean",
+        tson_delimiters::NULL => "null",
+        _ => "malformed TSON",
+    };
+
+    SerdeError::new(format!("expected {}, found {}", expected, found))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::parser::{Parser, JSONParser};
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    fn from_json<T: DeserializeOwned>(json: &str) -> Result<T, String> {
+        let tson = JSONParser::new(json.to_string()).parse().unwrap();
+        from_tson(&tson).map_err(|err| err.to_string())
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Point {
+        x: i64,
+        y: f64,
+    }
+
+    #[test]
+    fn reads_any_numeric_type_that_fits() {
+        let numbers: Vec<u64> = from_json(r#"[1, 9007199254740993, {"$numberLong": "2"}, {"$numberDecimal": "3.00"}]"#).unwrap();
+
+        assert_eq!(numbers, vec![1, 9007199254740993, 2, 3]);
+        assert_eq!(from_json::<Point>(r#"{"x": 1, "y": {"$numberDecimal": "2.5"}}"#).unwrap(), Point { x: 1, y: 2.5 });
+    }
+
+    #[test]
+    fn reads_object_ids_as_hex() {
+        let ids: Vec<String> = from_json(r#"[{"$oid": "5f1d7a3b9c1e4a2b3c4d5e6f"}]"#).unwrap();
+
+        assert_eq!(ids, vec!["5f1d7a3b9c1e4a2b3c4d5e6f"]);
+    }
+
+    #[test]
+    fn rejects_numbers_that_dont_fit() {
+        assert!(from_json::<Vec<u8>>("[1.5]").is_err());
+        assert!(from_json::<Vec<u8>>("[300]").is_err());
+        assert!(from_json::<Vec<u64>>("[-1]").is_err());
+    }
+
+    #[test]
+    fn rejects_mismatched_documents() {
+        assert!(from_json::<Point>(r#"{"x": 1}"#).is_err());
+        assert!(from_json::<Point>(r#"{"x": "1", "y": 2}"#).is_err());
+        assert!(from_json::<HashMap<String, bool>>("[true]").is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_tson() {
+        assert!(from_tson::<bool>(&[0x42]).is_err());
+        assert!(from_tson::<Vec<u8>>(&[tson_delimiters::ARRAY_BEGIN, 9, 0, 0, 0]).is_err());
+    }
+}
@@ -2,25 +2,65 @@ use crate::internal::parser::parser::{Parser};
 use crate::internal::parser::delimiters::{tson_delimiters, json_delimiters};
 use crate::internal::parser::value_cursor::ValueCursor;
 use crate::internal::parser::parsed::Parsed;
+use crate::internal::parser::output_options::{OutputOptions, OutputError};
+use crate::internal::parser::extended_json;
+use crate::internal::parser::decimal::Decimal;
+use crate::internal::parser::tson_value::{read_decimal, sort_keys};
+use crate::internal::parser::tson_validator::{validate, InvalidTSON};
+use crate::internal::utils::base64;
 use std::convert::TryInto;
 
 pub struct TSONParser {
     cursor: ValueCursor,
     parsed: Parsed,
+    options: OutputOptions,
+    /// Open collections, with how many values were written in each. Object
+    /// keys count as values, so keys are the even ones.
+    stack: Vec<(u8, usize)>,
+    error: Option<OutputError>,
 }
 
 impl TSONParser {
     pub fn new(tson: Vec<u8>) -> TSONParser {
+        Self::with_options(tson, OutputOptions::default())
+    }
+    /// Emits Relaxed Extended JSON, wrapping values plain JSON can't hold so
+    /// that `JSONParser` reads them back unchanged.
+    pub fn new_extended(tson: Vec<u8>) -> TSONParser {
+        let options = OutputOptions {
+            extended: true,
+            ..OutputOptions::default()
+        };
+        Self::with_options(tson, options)
+    }
+    /// Invalid TSON fails the parse rather than being read.
+    pub fn with_options(tson: Vec<u8>, options: OutputOptions) -> TSONParser {
+        let error = validate(&tson).err();
+
+        let tson = match (options.sort_keys, &error) {
+            (true, None) => sort_keys(tson.as_slice()),
+            _ => tson,
+        };
+
         let capacity = tson.len();
-        TSONParser {
+        let mut parser = TSONParser {
             cursor: ValueCursor::new(tson),
             parsed: Parsed::with_capacity(capacity),
+            options,
+            stack: Vec::new(),
+            error: None,
+        };
+
+        if let Some(err) = error {
+            parser.fail(OutputError::Invalid(err));
         }
+
+        parser
     }
 }
 
 impl Parser for TSONParser {
-    type Parsed = Vec<u8>;
+    type Parsed = Result<Vec<u8>, OutputError>;
     fn get_index(&self) -> usize {
         self.cursor.get_index()
     }
@@ -29,55 +69,231 @@ impl Parser for TSONParser {
         self.cursor.get_value_ref().len()
     }
 
-    fn get_parsed(self) -> Vec<u8> {
-        self.parsed.get_parsed()
+    fn get_parsed(self) -> Result<Vec<u8>, OutputError> {
+        match self.error {
+            Some(err) => Err(err),
+            None => Ok(self.parsed.get_parsed()),
+        }
     }
 
     fn parse_next(&mut self) {
-        match self.cursor.read_next() {
+        let val = self.cursor.read_next();
+        let key = match val {
+            tson_delimiters::OBJECT_END | tson_delimiters::ARRAY_END => false,
+            _ => self.begin_value(),
+        };
+
+        match val {
             tson_delimiters::OBJECT_BEGIN => self.write_object_begin(),
             tson_delimiters::OBJECT_END => self.write_object_end(),
             tson_delimiters::ARRAY_BEGIN => self.write_array_begin(),
             tson_delimiters::ARRAY_END => self.write_array_end(),
             tson_delimiters::STRING => self.write_string(),
             tson_delimiters::NUMBER => self.write_number(),
+            tson_delimiters::DATE => self.write_date(),
+            tson_delimiters::INT64 => self.write_int64(),
+            tson_delimiters::DECIMAL128 => self.write_decimal(),
+            tson_delimiters::BINARY => self.write_binary(),
+            tson_delimiters::OBJECT_ID => self.write_object_id(),
             tson_delimiters::TRUE => self.write_true(),
             tson_delimiters::FALSE => self.write_false(),
             tson_delimiters::NULL => self.write_null(),
-            tson_delimiters::PAIR => self.write_pair(),
-            tson_delimiters::SEPARATOR => self.write_separator(),
-            val => panic!("Unexpected delimiter while parsing TSON: {} = {}", val, val as char),
+            _ => {
+                let offset = self.cursor.get_index() - 1;
+                self.fail(OutputError::Invalid(InvalidTSON { offset, reason: "unknown delimiter" }));
+            },
+        }
+
+        if key {
+            self.write_pair();
+        }
+
+        if let Some(size) = self.options.max_size {
+            if self.parsed.get_parsed_len() > size {
+                self.fail(OutputError::TooLarge(size));
+            }
+        }
+    }
+}
+
+impl TSONParser {
+    /// Separates the value from the previous one in its collection, and
+    /// returns whether it's an object key.
+    fn begin_value(&mut self) -> bool {
+        let (key, separate) = match self.stack.last_mut() {
+            Some((collection, values)) => {
+                let key = *collection == json_delimiters::OBJECT_BEGIN && *values % 2 == 0;
+                let separate = *values > 0 && (key || *collection == json_delimiters::ARRAY_BEGIN);
+                *values += 1;
+                (key, separate)
+            },
+            None => (false, false),
+        };
+
+        if separate {
+            self.write_separator();
+        }
+
+        key
+    }
+    fn begin_collection(&mut self, begin: u8) {
+        self.parsed.write(begin);
+        let empty = self.read_length() == 0;
+
+        self.stack.push((begin, 0));
+        if let Some(depth) = self.options.max_depth {
+            if self.stack.len() > depth {
+                self.fail(OutputError::TooDeep(depth));
+                return;
+            }
+        }
+
+        if !empty {
+            self.write_newline();
+        }
+    }
+    fn end_collection(&mut self, begin: u8, end: u8) {
+        self.stack.pop();
+
+        if self.parsed.last() != Some(begin) {
+            self.write_newline();
         }
+        self.parsed.write(end);
+    }
+    /// Breaks the line and indents it to the current depth, when indenting.
+    fn write_newline(&mut self) {
+        if let Some(indent) = self.options.indent {
+            self.parsed.write(b'\n');
+            self.parsed.write_slice(" ".repeat(indent * self.stack.len()).as_bytes());
+        }
+    }
+    /// Stops the parse, keeping the first error.
+    fn fail(&mut self, err: OutputError) {
+        self.error.get_or_insert(err);
+        self.cursor.skip_rest();
     }
 }
 
 impl TSONParser {
     fn write_object_begin(&mut self) {
-        self.parsed.write(json_delimiters::OBJECT_BEGIN);
-        self.cursor.skip_by(4);
+        self.begin_collection(json_delimiters::OBJECT_BEGIN);
     }
     fn write_object_end(&mut self) {
-        self.parsed.write(json_delimiters::OBJECT_END);
+        self.end_collection(json_delimiters::OBJECT_BEGIN, json_delimiters::OBJECT_END);
     }
     fn write_array_begin(&mut self) {
-        self.parsed.write(json_delimiters::ARRAY_BEGIN);
-        self.cursor.skip_by(4);
+        self.begin_collection(json_delimiters::ARRAY_BEGIN);
     }
     fn write_array_end(&mut self) {
-        self.parsed.write(json_delimiters::ARRAY_END);
+        self.end_collection(json_delimiters::ARRAY_BEGIN, json_delimiters::ARRAY_END);
     }
     fn write_string(&mut self) {
         let string = self.read_string();
         self.parsed.write(json_delimiters::STRING);
-        self.parsed.write_slice(string.as_slice());
+        self.write_escaped(string.as_slice());
         self.parsed.write(json_delimiters::STRING);
     }
+    /// Escapes quotes, backslashes and control characters, and with
+    /// `ascii_only` everything past ASCII too.
+    fn write_escaped(&mut self, string: &[u8]) {
+        for val in String::from_utf8_lossy(string).chars() {
+            match val {
+                '"' => self.parsed.write_slice(b"\\\""),
+                '\\' => self.parsed.write_slice(b"\\\\"),
+                '\u{08}' => self.parsed.write_slice(b"\\b"),
+                '\u{0C}' => self.parsed.write_slice(b"\\f"),
+                '\n' => self.parsed.write_slice(b"\\n"),
+                '\r' => self.parsed.write_slice(b"\\r"),
+                '\t' => self.parsed.write_slice(b"\\t"),
+                val if (val as u32) < 0x20 || (self.options.ascii_only && !val.is_ascii()) => {
+                    let mut units = [0; 2];
+                    for unit in val.encode_utf16(&mut units) {
+                        self.parsed.write_slice(format!("\\u{:04x}", unit).as_bytes());
+                    }
+                },
+                val => {
+                    let mut buffer = [0; 4];
+                    self.parsed.write_slice(val.encode_utf8(&mut buffer).as_bytes());
+                },
+            }
+        }
+    }
+    /// JSON has no NaN or infinities, plain JSON gets `null` for them the same
+    /// as `JSON.stringify`.
     fn write_number(&mut self) {
         let number = self.read_number();
+
+        if !number.is_finite() {
+            match extended_json::write_number_wrapper(number) {
+                Some(wrapper) if self.options.extended => self.parsed.write_slice(wrapper.as_bytes()),
+                _ => self.parsed.write_slice("null".as_bytes()),
+            }
+            return;
+        }
+
         let slice = number.to_string();
         let slice = slice.as_bytes();
         self.parsed.write_slice(slice);
     }
+    /// Plain JSON gets the ISO string `JSON.stringify` would give a `Date`.
+    fn write_date(&mut self) {
+        let millis = self.read_i64();
+
+        if self.options.extended {
+            let wrapper = extended_json::write_date_wrapper(millis);
+            self.parsed.write_slice(wrapper.as_bytes());
+        } else {
+            self.parsed.write(json_delimiters::STRING);
+            self.parsed.write_slice(extended_json::format_date(millis).as_bytes());
+            self.parsed.write(json_delimiters::STRING);
+        }
+    }
+    fn write_int64(&mut self) {
+        let number = self.read_i64();
+
+        let slice = match self.options.extended {
+            true => extended_json::write_int64_wrapper(number),
+            false => number.to_string(),
+        };
+        self.parsed.write_slice(slice.as_bytes());
+    }
+    fn write_decimal(&mut self) {
+        let decimal = self.read_decimal();
+
+        let slice = match self.options.extended {
+            true => extended_json::write_decimal_wrapper(decimal),
+            false => decimal.to_string(),
+        };
+        self.parsed.write_slice(slice.as_bytes());
+    }
+    /// Plain JSON gets the bytes as a base64 string.
+    fn write_binary(&mut self) {
+        let length = self.read_length();
+        let subtype = self.cursor.read_next();
+        let bytes = self.cursor.read_by(length as usize).to_vec();
+
+        if self.options.extended {
+            let wrapper = extended_json::write_binary_wrapper(subtype, bytes.as_slice());
+            self.parsed.write_slice(wrapper.as_bytes());
+        } else {
+            self.parsed.write(json_delimiters::STRING);
+            self.parsed.write_slice(base64::encode(bytes.as_slice()).as_bytes());
+            self.parsed.write(json_delimiters::STRING);
+        }
+    }
+    /// Plain JSON gets the hex digits as a string.
+    fn write_object_id(&mut self) {
+        let id = self.cursor.read_by(12).to_vec();
+
+        if self.options.extended {
+            let wrapper = extended_json::write_object_id_wrapper(id.as_slice());
+            self.parsed.write_slice(wrapper.as_bytes());
+        } else {
+            self.parsed.write(json_delimiters::STRING);
+            self.parsed.write_slice(extended_json::format_object_id(id.as_slice()).as_bytes());
+            self.parsed.write(json_delimiters::STRING);
+        }
+    }
     fn write_true(&mut self) {
         self.parsed.write_slice("true".as_bytes());
     }
@@ -89,9 +305,13 @@ impl TSONParser {
     }
     fn write_pair(&mut self) {
         self.parsed.write(json_delimiters::PAIR);
+        if self.options.indent.is_some() {
+            self.parsed.write(b' ');
+        }
     }
     fn write_separator(&mut self) {
         self.parsed.write(json_delimiters::SEPARATOR);
+        self.write_newline();
     }
 }
 
@@ -108,4 +328,99 @@ impl TSONParser {
         let slice = self.cursor.read_by(8);
         f64::from_le_bytes(slice.try_into().unwrap())
     }
-}
\ No newline at end of file
+    fn read_i64(&mut self) -> i64 {
+        let slice = self.cursor.read_by(8);
+        i64::from_le_bytes(slice.try_into().unwrap())
+    }
+    fn read_decimal(&mut self) -> Decimal {
+        read_decimal(self.cursor.read_by(17))
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::parser::json_parser::JSONParser;
+    use crate::internal::parser::tson_value::{write_array, write_number, write_string};
+
+    fn emit(tson: Vec<u8>, options: OutputOptions) -> String {
+        String::from_utf8(TSONParser::with_options(tson, options).parse().unwrap()).unwrap()
+    }
+
+    fn strings(strings: &[&str]) -> Vec<u8> {
+        write_array(&strings.iter().map(|string| write_string(string.as_bytes())).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn escapes_strings() {
+        let tson = strings(&["\"\\/", "\u{8}\u{c}\n\r\t", "\u{0}\u{1f}", "é😀"]);
+        let json = emit(tson.clone(), OutputOptions::default());
+
+        assert_eq!(json, r#"["\"\\/","\b\f\n\r\t","\u0000\u001f","é😀"]"#);
+        assert_eq!(JSONParser::new(json).parse().unwrap(), tson);
+    }
+
+    #[test]
+    fn escapes_past_ascii_when_asked() {
+        let tson = strings(&["aé😀"]);
+        let options = OutputOptions { ascii_only: true, ..OutputOptions::default() };
+        let json = emit(tson.clone(), options);
+
+        assert_eq!(json, r#"["a\u00e9\ud83d\ude00"]"#);
+        assert_eq!(JSONParser::new(json).parse().unwrap(), tson);
+    }
+
+    #[test]
+    fn writes_non_finite_numbers() {
+        let tson = write_array(&[write_number(f64::NAN), write_number(f64::INFINITY), write_number(f64::NEG_INFINITY)]);
+
+        assert_eq!(emit(tson.clone(), OutputOptions::default()), "[null,null,null]");
+
+        let json = String::from_utf8(TSONParser::new_extended(tson.clone()).parse().unwrap()).unwrap();
+        assert_eq!(
+            json,
+            r#"[{"$numberDouble":"NaN"},{"$numberDouble":"Infinity"},{"$numberDouble":"-Infinity"}]"#,
+        );
+        assert_eq!(JSONParser::new(json).parse().unwrap(), tson);
+    }
+
+    fn tson(json: &str) -> Vec<u8> {
+        JSONParser::new(json.to_string()).parse().unwrap()
+    }
+
+    #[test]
+    fn indents_nested_values() {
+        let options = OutputOptions { indent: Some(2), ..OutputOptions::default() };
+
+        assert_eq!(
+            emit(tson(r#"{"a":[1,{"b":null}],"c":{},"d":[]}"#), options),
+            "{\n  \"a\": [\n    1,\n    {\n      \"b\": null\n    }\n  ],\n  \"c\": {},\n  \"d\": []\n}",
+        );
+    }
+
+    #[test]
+    fn sorts_keys_at_every_depth() {
+        let document = tson(r#"{"b":1,"a":{"z":[{"y":1,"x":2}],"é":3,"e":4}}"#);
+        let sorted = r#"{"a":{"e":4,"z":[{"x":2,"y":1}],"é":3},"b":1}"#;
+
+        assert_eq!(emit(document.clone(), OutputOptions { sort_keys: true, ..OutputOptions::default() }), sorted);
+        assert_eq!(emit(document, OutputOptions::canonical()), sorted);
+        assert_eq!(emit(tson(r#"{"a":{"é":3,"e":4,"z":[{"y":1,"x":2}]},"b":1}"#), OutputOptions::canonical()), sorted);
+    }
+
+    #[test]
+    fn stops_past_the_depth_and_size_limits() {
+        let document = tson(r#"{"a":[[1]]}"#);
+        let emit_with = |options| TSONParser::with_options(document.clone(), options).parse();
+
+        assert!(emit_with(OutputOptions { max_depth: Some(3), ..OutputOptions::default() }).is_ok());
+        assert!(matches!(
+            emit_with(OutputOptions { max_depth: Some(2), ..OutputOptions::default() }),
+            Err(OutputError::TooDeep(2)),
+        ));
+        assert!(emit_with(OutputOptions { max_size: Some(11), ..OutputOptions::default() }).is_ok());
+        assert!(matches!(
+            emit_with(OutputOptions { max_size: Some(10), ..OutputOptions::default() }),
+            Err(OutputError::TooLarge(10)),
+        ));
+    }
+}
@@ -0,0 +1,550 @@
+use serde::ser::{self, Serialize, Impossible};
+use std::convert::TryFrom;
+use crate::internal::parser::delimiters::tson_delimiters;
+use crate::internal::parser::parsed::Parsed;
+use crate::internal::parser::serde_error::SerdeError;
+use crate::internal::parser::decimal::{Decimal, Narrowest};
+
+/// Serializes `value` as TSON. Floats become numbers, and integers take the
+/// narrowest type holding them, the same as in JSON, so a document written
+/// either way gives the same TSON. Enums are written the way serde_json
+/// writes them.
+pub fn to_tson<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, SerdeError> {
+    let mut serializer = TSONSerializer { parsed: Parsed::new() };
+    value.serialize(&mut serializer)?;
+    Ok(serializer.parsed.get_parsed())
+}
+
+pub struct TSONSerializer {
+    parsed: Parsed,
+}
+
+impl TSONSerializer {
+    /// Writes the begin delimiter and a length to fill in, and returns where
+    /// the content starts.
+    fn begin_collection(&mut self, begin: u8) -> usize {
+        self.parsed.write(begin);
+        self.parsed.write_slice(&[0; 4]);
+        self.parsed.get_parsed_len()
+    }
+    fn end_collection(&mut self, start: usize, end: u8) {
+        let len = (self.parsed.get_parsed_len() - start) as u32;
+        self.parsed.rewrite_slice(start - 4, &len.to_le_bytes());
+        self.parsed.write(end);
+    }
+    /// Opens `{variant: ...}` and returns where its content starts.
+    fn begin_variant(&mut self, variant: &str) -> usize {
+        let start = self.begin_collection(tson_delimiters::OBJECT_BEGIN);
+        self.write_string(variant.as_bytes());
+        start
+    }
+    fn write_string(&mut self, string: &[u8]) {
+        self.parsed.write(tson_delimiters::STRING);
+        self.parsed.write_slice(&(string.len() as u32).to_le_bytes());
+        self.parsed.write_slice(string);
+    }
+    fn write_number(&mut self, number: f64) {
+        self.parsed.write(tson_delimiters::NUMBER);
+        self.parsed.write_slice(&number.to_le_bytes());
+    }
+    fn write_int64(&mut self, number: i64) {
+        self.parsed.write(tson_delimiters::INT64);
+        self.parsed.write_slice(&number.to_le_bytes());
+    }
+    fn write_decimal(&mut self, decimal: Decimal) {
+        self.parsed.write(tson_delimiters::DECIMAL128);
+        self.parsed.write_slice(&decimal.coefficient.to_le_bytes());
+        self.parsed.write(decimal.scale);
+    }
+    fn write_integer(&mut self, integer: i128) {
+        match Decimal::new(integer, 0).narrowest() {
+            Narrowest::Number(number) => self.write_number(number),
+            Narrowest::Int64(number) => self.write_int64(number),
+            Narrowest::Decimal(decimal) => self.write_decimal(decimal),
+        }
+    }
+}
+
+impl<'a> ser::Serializer for &'a mut TSONSerializer {
+    type Ok = ();
+    type Error = SerdeError;
+    type SerializeSeq = Collection<'a>;
+    type SerializeTuple = Collection<'a>;
+    type SerializeTupleStruct = Collection<'a>;
+    type SerializeTupleVariant = Collection<'a>;
+    type SerializeMap = Collection<'a>;
+    type SerializeStruct = Collection<'a>;
+    type SerializeStructVariant = Collection<'a>;
+
+    fn serialize_bool(self, value: bool) -> Result<(), SerdeError> {
+        self.parsed.write(if value { tson_delimiters::TRUE } else { tson_delimiters::FALSE });
+        Ok(())
+    }
+    fn serialize_i8(self, value: i8) -> Result<(), SerdeError> {
+        self.serialize_f64(value as f64)
+    }
+    fn serialize_i16(self, value: i16) -> Result<(), SerdeError> {
+        self.serialize_f64(value as f64)
+    }
+    fn serialize_i32(self, value: i32) -> Result<(), SerdeError> {
+        self.serialize_f64(value as f64)
+    }
+    fn serialize_i64(self, value: i64) -> Result<(), SerdeError> {
+        self.serialize_i128(value as i128)
+    }
+    fn serialize_i128(self, value: i128) -> Result<(), SerdeError> {
+        self.write_integer(value);
+        Ok(())
+    }
+    fn serialize_u8(self, value: u8) -> Result<(), SerdeError> {
+        self.serialize_f64(value as f64)
+    }
+    fn serialize_u16(self, value: u16) -> Result<(), SerdeError> {
+        self.serialize_f64(value as f64)
+    }
+    fn serialize_u32(self, value: u32) -> Result<(), SerdeError> {
+        self.serialize_f64(value as f64)
+    }
+    fn serialize_u64(self, value: u64) -> Result<(), SerdeError> {
+        self.serialize_i128(value as i128)
+    }
+    fn serialize_u128(self, value: u128) -> Result<(), SerdeError> {
+        let value = i128::try_from(value)
+            .map_err(|_| SerdeError::new(format!("{} doesn't fit in 128 bit decimals", value)))?;
+        self.serialize_i128(value)
+    }
+    fn serialize_f32(self, value: f32) -> Result<(), SerdeError> {
+        self.serialize_f64(value as f64)
+    }
+    fn serialize_f64(self, value: f64) -> Result<(), SerdeError> {
+        self.write_number(value);
+        Ok(())
+    }
+    fn serialize_char(self, value: char) -> Result<(), SerdeError> {
+        self.serialize_str(value.encode_utf8(&mut [0; 4]))
+    }
+    fn serialize_str(self, value: &str) -> Result<(), SerdeError> {
+        self.write_string(value.as_bytes());
+        Ok(())
+    }
+    fn serialize_bytes(self, value: &[u8]) -> Result<(), SerdeError> {
+        self.parsed.write(tson_delimiters::BINARY);
+        self.parsed.write_slice(&(value.len() as u32).to_le_bytes());
+        self.parsed.write(0); // generic subtype
+        self.parsed.write_slice(value);
+        Ok(())
+    }
+    fn serialize_none(self) -> Result<(), SerdeError> {
+        self.serialize_unit()
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), SerdeError> {
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> Result<(), SerdeError> {
+        self.parsed.write(tson_delimiters::NULL);
+        Ok(())
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), SerdeError> {
+        self.serialize_unit()
+    }
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<(), SerdeError> {
+        self.serialize_str(variant)
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<(), SerdeError> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        let start = self.begin_variant(variant);
+        value.serialize(&mut *self)?;
+        self.end_collection(start, tson_delimiters::OBJECT_END);
+        Ok(())
+    }
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Collection<'a>, SerdeError> {
+        Ok(Collection::new(self, tson_delimiters::ARRAY_BEGIN, None))
+    }
+    fn serialize_tuple(self, len: usize) -> Result<Collection<'a>, SerdeError> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<Collection<'a>, SerdeError> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Collection<'a>, SerdeError> {
+        let outer = self.begin_variant(variant);
+        Ok(Collection::new(self, tson_delimiters::ARRAY_BEGIN, Some(outer)))
+    }
+    fn serialize_map(self, _len: Option<usize>) -> Result<Collection<'a>, SerdeError> {
+        Ok(Collection::new(self, tson_delimiters::OBJECT_BEGIN, None))
+    }
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Collection<'a>, SerdeError> {
+        self.serialize_map(Some(len))
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Collection<'a>, SerdeError> {
+        let outer = self.begin_variant(variant);
+        Ok(Collection::new(self, tson_delimiters::OBJECT_BEGIN, Some(outer)))
+    }
+}
+
+/// An object or array being serialized. Variants are also wrapped in the
+/// object starting at `variant`.
+pub struct Collection<'a> {
+    serializer: &'a mut TSONSerializer,
+    start: usize,
+    end: u8,
+    variant: Option<usize>,
+}
+
+impl<'a> Collection<'a> {
+    fn new(serializer: &'a mut TSONSerializer, begin: u8, variant: Option<usize>) -> Collection<'a> {
+        let start = serializer.begin_collection(begin);
+        let end = match begin {
+            tson_delimiters::OBJECT_BEGIN => tson_delimiters::OBJECT_END,
+            _ => tson_delimiters::ARRAY_END,
+        };
+
+        Collection { serializer, start, end, variant }
+    }
+    fn element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        value.serialize(&mut *self.serializer)
+    }
+    fn field<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<(), SerdeError> {
+        self.serializer.write_string(key.as_bytes());
+        value.serialize(&mut *self.serializer)
+    }
+    fn finish(self) -> Result<(), SerdeError> {
+        self.serializer.end_collection(self.start, self.end);
+        if let Some(outer) = self.variant {
+            self.serializer.end_collection(outer, tson_delimiters::OBJECT_END);
+        }
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for Collection<'_> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.element(value)
+    }
+    fn end(self) -> Result<(), SerdeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for Collection<'_> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.element(value)
+    }
+    fn end(self) -> Result<(), SerdeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for Collection<'_> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.element(value)
+    }
+    fn end(self) -> Result<(), SerdeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for Collection<'_> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.element(value)
+    }
+    fn end(self) -> Result<(), SerdeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeMap for Collection<'_> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SerdeError> {
+        key.serialize(KeySerializer { serializer: &mut *self.serializer })
+    }
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.element(value)
+    }
+    fn end(self) -> Result<(), SerdeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for Collection<'_> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), SerdeError> {
+        self.field(key, value)
+    }
+    fn end(self) -> Result<(), SerdeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for Collection<'_> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), SerdeError> {
+        self.field(key, value)
+    }
+    fn end(self) -> Result<(), SerdeError> {
+        self.finish()
+    }
+}
+
+/// Object keys are strings, so map keys must be strings, chars, unit
+/// variants or integers, written in decimal.
+struct KeySerializer<'a> {
+    serializer: &'a mut TSONSerializer,
+}
+
+impl KeySerializer<'_> {
+    fn write(self, key: &str) -> Result<(), SerdeError> {
+        self.serializer.write_string(key.as_bytes());
+        Ok(())
+    }
+}
+
+fn key_must_be_a_string() -> SerdeError {
+    SerdeError::new("map keys must be strings")
+}
+
+impl ser::Serializer for KeySerializer<'_> {
+    type Ok = ();
+    type Error = SerdeError;
+    type SerializeSeq = Impossible<(), SerdeError>;
+    type SerializeTuple = Impossible<(), SerdeError>;
+    type SerializeTupleStruct = Impossible<(), SerdeError>;
+    type SerializeTupleVariant = Impossible<(), SerdeError>;
+    type SerializeMap = Impossible<(), SerdeError>;
+    type SerializeStruct = Impossible<(), SerdeError>;
+    type SerializeStructVariant = Impossible<(), SerdeError>;
+
+    fn serialize_str(self, value: &str) -> Result<(), SerdeError> {
+        self.write(value)
+    }
+    fn serialize_char(self, value: char) -> Result<(), SerdeError> {
+        self.write(value.encode_utf8(&mut [0; 4]))
+    }
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<(), SerdeError> {
+        self.write(variant)
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<(), SerdeError> {
+        value.serialize(self)
+    }
+    fn serialize_i8(self, value: i8) -> Result<(), SerdeError> {
+        self.write(&value.to_string())
+    }
+    fn serialize_i16(self, value: i16) -> Result<(), SerdeError> {
+        self.write(&value.to_string())
+    }
+    fn serialize_i32(self, value: i32) -> Result<(), SerdeError> {
+        self.write(&value.to_string())
+    }
+    fn serialize_i64(self, value: i64) -> Result<(), SerdeError> {
+        self.write(&value.to_string())
+    }
+    fn serialize_i128(self, value: i128) -> Result<(), SerdeError> {
+        self.write(&value.to_string())
+    }
+    fn serialize_u8(self, value: u8) -> Result<(), SerdeError> {
+        self.write(&value.to_string())
+    }
+    fn serialize_u16(self, value: u16) -> Result<(), SerdeError> {
+        self.write(&value.to_string())
+    }
+    fn serialize_u32(self, value: u32) -> Result<(), SerdeError> {
+        self.write(&value.to_string())
+    }
+    fn serialize_u64(self, value: u64) -> Result<(), SerdeError> {
+        self.write(&value.to_string())
+    }
+    fn serialize_u128(self, value: u128) -> Result<(), SerdeError> {
+        self.write(&value.to_string())
+    }
+    fn serialize_bool(self, _value: bool) -> Result<(), SerdeError> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_f32(self, _value: f32) -> Result<(), SerdeError> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_f64(self, _value: f64) -> Result<(), SerdeError> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_bytes(self, _value: &[u8]) -> Result<(), SerdeError> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_none(self) -> Result<(), SerdeError> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, _value: &T) -> Result<(), SerdeError> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_unit(self) -> Result<(), SerdeError> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), SerdeError> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), SerdeError> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, SerdeError> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, SerdeError> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeTupleStruct, SerdeError> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, SerdeError> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, SerdeError> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct, SerdeError> {
+        Err(key_must_be_a_string())
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, SerdeError> {
+        Err(key_must_be_a_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::parser::{Parser, JSONParser, from_tson};
+    use serde::{Serialize, Deserialize};
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Shape {
+        Empty,
+        Circle(f64),
+        Pair(u8, String),
+        Rect { width: u32, height: Option<u32> },
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Document {
+        name: String,
+        small: i32,
+        large: i64,
+        huge: u64,
+        wide: i128,
+        ratio: f32,
+        flag: bool,
+        missing: Option<i8>,
+        tags: Vec<String>,
+        shapes: Vec<Shape>,
+        counts: BTreeMap<String, u16>,
+        pair: (char, bool),
+        nested: Option<Box<Document>>,
+    }
+
+    fn document(nested: Option<Box<Document>>) -> Document {
+        Document {
+            name: "a \"quoted\" name".to_string(),
+            small: -7,
+            large: 1 << 60,
+            huge: u64::MAX,
+            wide: -(1 << 100),
+            ratio: 0.5,
+            flag: true,
+            missing: None,
+            tags: vec!["x".to_string(), "y".to_string()],
+            shapes: vec![
+                Shape::Empty,
+                Shape::Circle(1.5),
+                Shape::Pair(2, "p".to_string()),
+                Shape::Rect { width: 3, height: None },
+            ],
+            counts: vec![("a".to_string(), 1), ("b".to_string(), 2)].into_iter().collect(),
+            pair: ('é', false),
+            nested,
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let document = document(Some(Box::new(document(None))));
+        let tson = to_tson(&document).unwrap();
+
+        assert_eq!(from_tson::<Document>(&tson).unwrap(), document);
+    }
+
+    #[test]
+    fn writes_integers_the_way_json_does() {
+        let integers: Vec<i128> = vec![0, -1, 1 << 53, (1 << 53) + 1, i64::MIN as i128, i64::MAX as i128 + 1];
+        let json = format!("{:?}", integers).replace(' ', "");
+
+        assert_eq!(to_tson(&integers).unwrap(), JSONParser::new(json).parse().unwrap());
+    }
+
+    #[test]
+    fn narrows_every_integer_type() {
+        let expected = JSONParser::new("[5,5,5,5]".to_string()).parse().unwrap();
+
+        assert_eq!(to_tson(&(5u8, 5i64, 5u64, 5i128)).unwrap(), expected);
+    }
+
+    #[test]
+    fn rejects_what_tson_cant_hold() {
+        assert!(to_tson(&u128::MAX).is_err());
+
+        let keys: BTreeMap<Vec<u8>, u8> = vec![(vec![1], 1)].into_iter().collect();
+        assert!(to_tson(&keys).is_err());
+    }
+}
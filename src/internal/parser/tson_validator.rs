@@ -0,0 +1,187 @@
+use crate::internal::parser::delimiters::tson_delimiters;
+use std::convert::TryInto;
+use std::{fmt, str};
+
+pub const MAX_DEPTH: usize = 128;
+
+#[derive(Debug)]
+pub struct InvalidTSON {
+    pub offset: usize,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for InvalidTSON {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid TSON at byte {}, {}.", self.offset, self.reason)
+    }
+}
+
+/// Checks that `tson` holds exactly one well formed value, without trusting
+/// any of its lengths: structure, delimiters, UTF-8 of strings and nesting up
+/// to `MAX_DEPTH`.
+pub fn validate(tson: &[u8]) -> Result<(), InvalidTSON> {
+    let mut validator = Validator { tson, depth: 0 };
+    let end = validator.value(0)?;
+
+    if end != tson.len() {
+        return Err(invalid(end, "trailing bytes after the value"));
+    }
+
+    Ok(())
+}
+
+/// Same as `validate`, for values that must be documents.
+pub fn validate_document(tson: &[u8]) -> Result<(), InvalidTSON> {
+    if tson.first() != Some(&tson_delimiters::OBJECT_BEGIN) {
+        return Err(invalid(0, "documents must be objects"));
+    }
+
+    validate(tson)
+}
+
+struct Validator<'a> {
+    tson: &'a [u8],
+    depth: usize,
+}
+
+impl Validator<'_> {
+    /// Validates the value at `i` and returns where it ends.
+    fn value(&mut self, i: usize) -> Result<usize, InvalidTSON> {
+        match self.byte(i)? {
+            tson_delimiters::OBJECT_BEGIN => self.collection(i, tson_delimiters::OBJECT_END, true),
+            tson_delimiters::ARRAY_BEGIN => self.collection(i, tson_delimiters::ARRAY_END, false),
+            tson_delimiters::STRING => {
+                let len = self.length(i + 1)?;
+                let end = self.take(i + 5, len)?;
+                match str::from_utf8(&self.tson[i + 5..end]) {
+                    Ok(_) => Ok(end),
+                    Err(_) => Err(invalid(i, "string isn't valid UTF-8")),
+                }
+            },
+            tson_delimiters::NUMBER => self.take(i + 1, 8),
+            tson_delimiters::DATE => self.take(i + 1, 8),
+            tson_delimiters::INT64 => self.take(i + 1, 8),
+            tson_delimiters::DECIMAL128 => self.take(i + 1, 17), // coefficient + scale
+            tson_delimiters::BINARY => {
+                let len = self.length(i + 1)?;
+                self.take(i + 5, len + 1) // subtype inclusive
+            },
+            tson_delimiters::OBJECT_ID => self.take(i + 1, 12),
+            tson_delimiters::TRUE => Ok(i + 1),
+            tson_delimiters::FALSE => Ok(i + 1),
+            tson_delimiters::NULL => Ok(i + 1),
+            _ => Err(invalid(i, "unknown delimiter")),
+        }
+    }
+    fn collection(&mut self, begin: usize, end_delimiter: u8, keyed: bool) -> Result<usize, InvalidTSON> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(invalid(begin, "nested too deep"));
+        }
+
+        let content = begin + 5; // begin and length
+        let len = self.length(begin + 1)?;
+        let end = self.take(content, len)?;
+
+        if self.byte(end)? != end_delimiter {
+            return Err(invalid(end, "collection end doesn't match its length"));
+        }
+
+        let mut i = content;
+
+        while i < end {
+            if keyed {
+                if self.byte(i)? != tson_delimiters::STRING {
+                    return Err(invalid(i, "expected a string key"));
+                }
+                i = self.value(i)?;
+            }
+
+            i = self.value(i)?;
+        }
+
+        if i != end {
+            return Err(invalid(end, "value runs past the end of its collection"));
+        }
+
+        self.depth -= 1;
+
+        Ok(end + 1)
+    }
+    fn byte(&self, i: usize) -> Result<u8, InvalidTSON> {
+        self.tson.get(i).copied().ok_or_else(|| invalid(i, "unexpected end of value"))
+    }
+    fn length(&self, i: usize) -> Result<usize, InvalidTSON> {
+        match self.tson.get(i..i + 4) {
+            Some(slice) => Ok(u32::from_le_bytes(slice.try_into().unwrap()) as usize),
+            None => Err(invalid(i, "unexpected end of value")),
+        }
+    }
+    /// Skips `len` bytes from `i`, returning where they end.
+    fn take(&self, i: usize, len: usize) -> Result<usize, InvalidTSON> {
+        match i.checked_add(len) {
+            Some(end) if end <= self.tson.len() => Ok(end),
+            _ => Err(invalid(i, "length runs past the end of the value")),
+        }
+    }
+}
+
+fn invalid(offset: usize, reason: &'static str) -> InvalidTSON {
+    InvalidTSON { offset, reason }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal::parser::tson_value::{write_array, write_object, write_string, write_number, write_binary};
+
+    fn reason(tson: &[u8]) -> (usize, &'static str) {
+        let err = validate(tson).unwrap_err();
+        (err.offset, err.reason)
+    }
+
+    #[test]
+    fn accepts_well_formed_values() {
+        let tson = write_object(&[
+            ("a", write_array(&[write_number(1.0), vec![tson_delimiters::NULL]])),
+            ("b", write_binary(0, b"\xff")),
+            ("c", write_object::<&str, Vec<u8>>(&[])),
+        ]);
+
+        assert!(validate(&tson).is_ok());
+        assert!(validate_document(&tson).is_ok());
+        assert_eq!(validate_document(&write_array::<Vec<u8>>(&[])).unwrap_err().reason, "documents must be objects");
+    }
+
+    #[test]
+    fn rejects_broken_structure() {
+        let string = write_string(b"ab");
+
+        assert_eq!(reason(&[]), (0, "unexpected end of value"));
+        assert_eq!(reason(&[0x42]), (0, "unknown delimiter"));
+        assert_eq!(reason(&string[..string.len() - 1]), (5, "length runs past the end of the value"));
+        assert_eq!(reason(&[string.as_slice(), &[tson_delimiters::NULL]].concat()), (string.len(), "trailing bytes after the value"));
+        assert_eq!(reason(&write_string(b"\xff")), (0, "string isn't valid UTF-8"));
+
+        let object = write_object(&[("a", write_number(1.0))]);
+        assert_eq!(reason(&object[..15]), (5, "length runs past the end of the value"));
+        assert_eq!(reason(&object[..object.len() - 1]), (object.len() - 1, "unexpected end of value"));
+
+        let mut keyless = write_array(&[write_number(1.0)]);
+        keyless[0] = tson_delimiters::OBJECT_BEGIN;
+        keyless[14] = tson_delimiters::OBJECT_END;
+        assert_eq!(reason(&keyless), (5, "expected a string key"));
+
+        let mut mismatched = write_array::<Vec<u8>>(&[]);
+        mismatched[5] = tson_delimiters::OBJECT_END;
+        assert_eq!(reason(&mismatched), (5, "collection end doesn't match its length"));
+    }
+
+    #[test]
+    fn limits_nesting() {
+        let nested = |depth| (0..depth).fold(write_array::<Vec<u8>>(&[]), |inner, _| write_array(&[inner]));
+
+        assert!(validate(&nested(MAX_DEPTH - 1)).is_ok());
+        assert_eq!(reason(&nested(MAX_DEPTH)).1, "nested too deep");
+    }
+}
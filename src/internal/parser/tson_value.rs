@@ -0,0 +1,287 @@
+use crate::internal::parser::delimiters::tson_delimiters;
+use crate::internal::parser::decimal::{Decimal, Numeric};
+use crate::internal::parser::extended_json::format_object_id;
+use std::convert::TryInto;
+
+// Readers for TSON `tson_validator` has checked, they don't check it again.
+// Entry points taking TSON from outside validate it first.
+
+#[derive(Clone, Copy)]
+pub enum TSONValue<'a> {
+    Object(&'a [u8]),
+    Array(&'a [u8]),
+    String(&'a [u8]),
+    Number(f64),
+    Date(i64),
+    Int64(i64),
+    Decimal128(Decimal),
+    Binary(u8, &'a [u8]),
+    ObjectId(&'a [u8]),
+    True,
+    False,
+    Null,
+}
+
+impl<'a> TSONValue<'a> {
+    /// Reads the value starting at the first byte of `tson`. Objects and arrays
+    /// borrow their whole raw slice, delimiters included.
+    pub fn read(tson: &'a [u8]) -> TSONValue<'a> {
+        let len = value_len(tson);
+
+        match tson[0] {
+            tson_delimiters::OBJECT_BEGIN => TSONValue::Object(&tson[..len]),
+            tson_delimiters::ARRAY_BEGIN => TSONValue::Array(&tson[..len]),
+            tson_delimiters::STRING => TSONValue::String(&tson[5..len]),
+            tson_delimiters::NUMBER => TSONValue::Number(read_f64(&tson[1..])),
+            tson_delimiters::DATE => TSONValue::Date(read_i64(&tson[1..])),
+            tson_delimiters::INT64 => TSONValue::Int64(read_i64(&tson[1..])),
+            tson_delimiters::DECIMAL128 => TSONValue::Decimal128(read_decimal(&tson[1..])),
+            tson_delimiters::BINARY => TSONValue::Binary(tson[5], &tson[6..len]),
+            tson_delimiters::OBJECT_ID => TSONValue::ObjectId(&tson[1..len]),
+            tson_delimiters::TRUE => TSONValue::True,
+            tson_delimiters::FALSE => TSONValue::False,
+            tson_delimiters::NULL => TSONValue::Null,
+            _ => TSONValue::Null, // checked TSON has no other delimiters
+        }
+    }
+}
+
+/// Returns the byte length of the value starting at the first byte of `tson`.
+pub fn value_len(tson: &[u8]) -> usize {
+    match tson[0] {
+        tson_delimiters::OBJECT_BEGIN => read_length(&tson[1..]) + 6, // begin + length + end
+        tson_delimiters::ARRAY_BEGIN => read_length(&tson[1..]) + 6,
+        tson_delimiters::STRING => read_length(&tson[1..]) + 5,
+        tson_delimiters::NUMBER => 9,
+        tson_delimiters::DATE => 9,
+        tson_delimiters::INT64 => 9,
+        tson_delimiters::DECIMAL128 => 18, // delimiter + coefficient + scale
+        tson_delimiters::BINARY => read_length(&tson[1..]) + 6, // delimiter + length + subtype
+        tson_delimiters::OBJECT_ID => 13,
+        tson_delimiters::TRUE => 1,
+        tson_delimiters::FALSE => 1,
+        tson_delimiters::NULL => 1,
+        _ => 1,
+    }
+}
+
+impl TSONValue<'_> {
+    /// Numbers of every numeric type, for comparing across them.
+    pub fn numeric(&self) -> Option<Numeric> {
+        match *self {
+            TSONValue::Number(number) => Some(Numeric::Float(number)),
+            TSONValue::Int64(number) => Some(Numeric::Exact(Decimal::new(number as i128, 0))),
+            TSONValue::Decimal128(decimal) => Some(Numeric::Exact(decimal)),
+            _ => None,
+        }
+    }
+}
+
+/// Splits an object into its raw (key, value) members.
+pub fn object_members(object: &[u8]) -> Vec<(&[u8], &[u8])> {
+    let content = collection_content(object);
+    let mut members = Vec::new();
+    let mut i = 0;
+
+    while i < content.len() && content[i] == tson_delimiters::STRING {
+        let key_len = value_len(&content[i..]);
+        let key = &content[i + 5..i + key_len];
+        i += key_len;
+
+        let len = value_len(&content[i..]);
+        members.push((key, &content[i..i + len]));
+        i += len;
+    }
+
+    members
+}
+
+/// Splits an array into its raw elements.
+pub fn array_elements(array: &[u8]) -> Vec<&[u8]> {
+    let content = collection_content(array);
+    let mut elements = Vec::new();
+    let mut i = 0;
+
+    while i < content.len() {
+        let len = value_len(&content[i..]);
+        elements.push(&content[i..i + len]);
+        i += len;
+    }
+
+    elements
+}
+
+/// Follows a dot notation namespace through nested objects.
+pub fn get_path<'a>(document: &'a [u8], namespace: &[Vec<u8>]) -> Option<&'a [u8]> {
+    let mut current = document;
+
+    for key in namespace.iter() {
+        if current[0] != tson_delimiters::OBJECT_BEGIN {
+            return None;
+        }
+
+        current = object_members(current)
+            .into_iter()
+            .find(|(member, _)| *member == key.as_slice())
+            .map(|(_, value)| value)?;
+    }
+
+    Some(current)
+}
+
+/// Puts `id` as the first member of the document, replacing any `_id` in it.
+/// ObjectIds are stored under their hex digits, an `_id` ObjectId matching
+/// `id` is kept as it is.
+pub fn with_id(id: &[u8], document: &[u8]) -> Vec<u8> {
+    let existing = object_members(document);
+
+    let stored_id = existing.iter()
+        .find(|(key, _)| *key == b"_id")
+        .filter(|(_, value)| match TSONValue::read(value) {
+            TSONValue::ObjectId(object_id) => format_object_id(object_id).as_bytes() == id,
+            _ => false,
+        })
+        .map_or_else(|| write_string(id), |(_, value)| value.to_vec());
+
+    let mut members = vec![(&b"_id"[..], stored_id)];
+
+    members.extend(existing
+        .into_iter()
+        .filter(|(key, _)| *key != b"_id")
+        .map(|(key, value)| (key, value.to_vec())));
+
+    write_object(members.as_slice())
+}
+
+/// Rewrites a value with the keys of every object in it in byte order.
+pub fn sort_keys(tson: &[u8]) -> Vec<u8> {
+    match TSONValue::read(tson) {
+        TSONValue::Object(object) => {
+            let mut members: Vec<(&[u8], Vec<u8>)> = object_members(object)
+                .into_iter()
+                .map(|(key, value)| (key, sort_keys(value)))
+                .collect();
+            members.sort_by_key(|(key, _)| *key);
+            write_object(members.as_slice())
+        },
+        TSONValue::Array(array) => {
+            let elements: Vec<Vec<u8>> = array_elements(array).into_iter().map(sort_keys).collect();
+            write_array(elements.as_slice())
+        },
+        _ => tson[..value_len(tson)].to_vec(),
+    }
+}
+
+/// Builds an object out of raw (key, value) members.
+pub fn write_object<K, V>(members: &[(K, V)]) -> Vec<u8>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+{
+    let mut content = Vec::new();
+
+    for (key, value) in members.iter() {
+        content.extend_from_slice(write_string(key.as_ref()).as_slice());
+        content.extend_from_slice(value.as_ref());
+    }
+
+    write_collection(tson_delimiters::OBJECT_BEGIN, content, tson_delimiters::OBJECT_END)
+}
+
+/// Builds an array out of raw elements.
+pub fn write_array<V: AsRef<[u8]>>(elements: &[V]) -> Vec<u8> {
+    let mut content = Vec::new();
+
+    for element in elements.iter() {
+        content.extend_from_slice(element.as_ref());
+    }
+
+    write_collection(tson_delimiters::ARRAY_BEGIN, content, tson_delimiters::ARRAY_END)
+}
+
+pub fn write_string(string: &[u8]) -> Vec<u8> {
+    let mut value = Vec::with_capacity(string.len() + 5);
+    value.push(tson_delimiters::STRING);
+    value.extend_from_slice(&(string.len() as u32).to_le_bytes());
+    value.extend_from_slice(string);
+    value
+}
+
+pub fn write_number(number: f64) -> Vec<u8> {
+    let mut value = Vec::with_capacity(9);
+    value.push(tson_delimiters::NUMBER);
+    value.extend_from_slice(&number.to_le_bytes());
+    value
+}
+
+/// Dates are milliseconds since the epoch.
+pub fn write_date(millis: i64) -> Vec<u8> {
+    let mut value = Vec::with_capacity(9);
+    value.push(tson_delimiters::DATE);
+    value.extend_from_slice(&millis.to_le_bytes());
+    value
+}
+
+pub fn write_int64(number: i64) -> Vec<u8> {
+    let mut value = Vec::with_capacity(9);
+    value.push(tson_delimiters::INT64);
+    value.extend_from_slice(&number.to_le_bytes());
+    value
+}
+
+/// Decimals are a little-endian i128 coefficient followed by the scale.
+pub fn write_decimal(decimal: Decimal) -> Vec<u8> {
+    let mut value = Vec::with_capacity(18);
+    value.push(tson_delimiters::DECIMAL128);
+    value.extend_from_slice(&decimal.coefficient.to_le_bytes());
+    value.push(decimal.scale);
+    value
+}
+
+/// Binaries are the byte length, the subtype and the bytes.
+pub fn write_binary(subtype: u8, bytes: &[u8]) -> Vec<u8> {
+    let mut value = Vec::with_capacity(bytes.len() + 6);
+    value.push(tson_delimiters::BINARY);
+    value.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    value.push(subtype);
+    value.extend_from_slice(bytes);
+    value
+}
+
+/// ObjectIds are their 12 bytes, as MongoDB stores them.
+pub fn write_object_id(bytes: &[u8; 12]) -> Vec<u8> {
+    let mut value = Vec::with_capacity(13);
+    value.push(tson_delimiters::OBJECT_ID);
+    value.extend_from_slice(bytes);
+    value
+}
+
+fn write_collection(begin: u8, content: Vec<u8>, end: u8) -> Vec<u8> {
+    let mut collection = Vec::with_capacity(content.len() + 6);
+    collection.push(begin);
+    collection.extend_from_slice(&(content.len() as u32).to_le_bytes());
+    collection.extend_from_slice(content.as_slice());
+    collection.push(end);
+    collection
+}
+
+fn collection_content(collection: &[u8]) -> &[u8] {
+    let len = read_length(&collection[1..]);
+    &collection[5..5 + len]
+}
+
+fn read_length(slice: &[u8]) -> usize {
+    u32::from_le_bytes(slice[..4].try_into().unwrap()) as usize
+}
+
+fn read_f64(slice: &[u8]) -> f64 {
+    f64::from_le_bytes(slice[..8].try_into().unwrap())
+}
+
+fn read_i64(slice: &[u8]) -> i64 {
+    i64::from_le_bytes(slice[..8].try_into().unwrap())
+}
+
+pub fn read_decimal(slice: &[u8]) -> Decimal {
+    Decimal::new(i128::from_le_bytes(slice[..16].try_into().unwrap()), slice[16])
+}
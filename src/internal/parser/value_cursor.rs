@@ -1,15 +1,34 @@
 use std::ops::Range;
+use std::rc::Rc;
+use crate::internal::parser::parse_error::{ParseError, SourceMap};
 
 pub struct ValueCursor {
     index: usize,
     value: Vec<u8>,
+    /// Offset of `value` within the TSON the source map covers.
+    origin: usize,
+    source: Option<Rc<SourceMap>>,
 }
 
 impl ValueCursor {
     pub fn new(value: Vec<u8>) -> ValueCursor {
+        Self::with_source(value, None)
+    }
+    pub fn with_source(value: Vec<u8>, source: Option<Rc<SourceMap>>) -> ValueCursor {
         ValueCursor {
             index: 0,
             value,
+            origin: 0,
+            source,
+        }
+    }
+    /// A cursor over part of the value, keeping its place in the source.
+    pub fn slice(&self, range: Range<usize>) -> ValueCursor {
+        ValueCursor {
+            index: 0,
+            origin: self.origin + range.start,
+            value: self.value[range].to_vec(),
+            source: self.source.clone(),
         }
     }
 }
@@ -24,6 +43,9 @@ impl ValueCursor {
     pub fn skip_reverse_by(&mut self, n: usize) {
         self.index -= n;
     }
+    pub fn rewind(&mut self) {
+        self.index = 0;
+    }
     pub fn skip_rest(&mut self) {
         self.index = self.value.len();
     }
@@ -52,4 +74,11 @@ impl ValueCursor {
     pub fn get_value(self) -> Vec<u8> {
         self.value
     }
-}
\ No newline at end of file
+    /// An error at `index`, pointing into the source JSON when there is one.
+    pub fn error_at(&self, index: usize, context: &'static str, message: String) -> ParseError {
+        match &self.source {
+            Some(source) => source.error(context, message, self.origin + index),
+            None => ParseError::new(context, message),
+        }
+    }
+}
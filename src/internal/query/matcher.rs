@@ -0,0 +1,129 @@
+use crate::internal::parser::tson_value::{TSONValue, get_path, array_elements};
+use crate::internal::parser::query_parser::{
+    LogicalOperation,
+    NamespacedOperation,
+    Operation,
+    EqualityValue,
+    ComparisonValue,
+};
+use crate::internal::parser::decimal::{Decimal, Numeric};
+use crate::internal::query::Query;
+use std::cmp::Ordering;
+
+pub trait Matcher {
+    fn matches(&self, document: &[u8]) -> bool;
+}
+
+impl Matcher for Query {
+    fn matches(&self, document: &[u8]) -> bool {
+        match self {
+            Query::All => true,
+            Query::Id(id) => {
+                let value = get_path(document, &[b"_id".to_vec()]).map(TSONValue::read);
+                equals(value, &EqualityValue::String(id.as_bytes().to_vec()))
+            },
+            Query::By(operation) => operation.matches(document),
+        }
+    }
+}
+
+impl Matcher for LogicalOperation {
+    fn matches(&self, document: &[u8]) -> bool {
+        match self {
+            LogicalOperation::No(operations) => operations.iter().all(|op| op.matches(document)),
+            LogicalOperation::And(operations) => operations.iter().all(|op| op.matches(document)),
+            LogicalOperation::Or(operations) => operations.iter().any(|op| op.matches(document)),
+        }
+    }
+}
+
+impl Matcher for NamespacedOperation {
+    fn matches(&self, document: &[u8]) -> bool {
+        let value = get_path(document, &self.namespace).map(TSONValue::read);
+
+        match &self.operation {
+            Operation::Eq(expected) => equals(value, expected),
+            Operation::Ne(expected) => !equals(value, expected),
+            Operation::Lt(expected) => compare(value, expected, |o| o == Ordering::Less),
+            Operation::Lte(expected) => compare(value, expected, |o| o != Ordering::Greater),
+            Operation::Gt(expected) => compare(value, expected, |o| o == Ordering::Greater),
+            Operation::Gte(expected) => compare(value, expected, |o| o != Ordering::Less),
+            Operation::In(values) => values.iter().any(|expected| equals(value, expected)),
+            Operation::Nin(values) => !values.iter().any(|expected| equals(value, expected)),
+        }
+    }
+}
+
+// Missing fields equal null, and arrays match when any of their elements does.
+fn equals(value: Option<TSONValue>, expected: &EqualityValue) -> bool {
+    match value {
+        None => matches!(expected, EqualityValue::Null),
+        Some(TSONValue::Array(array)) => array_elements(array)
+            .into_iter()
+            .any(|element| equals_single(TSONValue::read(element), expected)),
+        Some(value) => equals_single(value, expected),
+    }
+}
+
+fn equals_single(value: TSONValue, expected: &EqualityValue) -> bool {
+    match (value, expected) {
+        (TSONValue::String(a), EqualityValue::String(b)) => a == b.as_slice(),
+        (TSONValue::Date(a), EqualityValue::Date(b)) => a == *b,
+        (TSONValue::Binary(a, a_bytes), EqualityValue::Binary(b, b_bytes)) => a == *b && a_bytes == b_bytes.as_slice(),
+        (TSONValue::ObjectId(a), EqualityValue::ObjectId(b)) => a == b.as_slice(),
+        (TSONValue::True, EqualityValue::True) => true,
+        (TSONValue::False, EqualityValue::False) => true,
+        (TSONValue::Null, EqualityValue::Null) => true,
+        (value, expected) => match (value.numeric(), equality_numeric(expected)) {
+            (Some(a), Some(b)) => a.compare(&b) == Some(Ordering::Equal),
+            _ => false,
+        },
+    }
+}
+
+fn compare(value: Option<TSONValue>, expected: &ComparisonValue, accept: fn(Ordering) -> bool) -> bool {
+    match value {
+        None => false,
+        Some(TSONValue::Array(array)) => array_elements(array)
+            .into_iter()
+            .any(|element| compare_single(TSONValue::read(element), expected, accept)),
+        Some(value) => compare_single(value, expected, accept),
+    }
+}
+
+// Values of different types never compare, same as MongoDB's type bracketing,
+// except numbers which compare across their types by exact value.
+fn compare_single(value: TSONValue, expected: &ComparisonValue, accept: fn(Ordering) -> bool) -> bool {
+    let ordering = match (value, expected) {
+        (TSONValue::String(a), ComparisonValue::String(b)) => a.cmp(b.as_slice()),
+        (TSONValue::Date(a), ComparisonValue::Date(b)) => a.cmp(b),
+        (TSONValue::ObjectId(a), ComparisonValue::ObjectId(b)) => a.cmp(b.as_slice()),
+        (value, expected) => match (value.numeric(), comparison_numeric(expected)) {
+            (Some(a), Some(b)) => match a.compare(&b) {
+                Some(ordering) => ordering,
+                None => return false,
+            },
+            _ => return false,
+        },
+    };
+
+    accept(ordering)
+}
+
+fn equality_numeric(value: &EqualityValue) -> Option<Numeric> {
+    match value {
+        EqualityValue::Number(number) => Some(Numeric::Float(*number)),
+        EqualityValue::Int64(number) => Some(Numeric::Exact(Decimal::new(*number as i128, 0))),
+        EqualityValue::Decimal128(decimal) => Some(Numeric::Exact(*decimal)),
+        _ => None,
+    }
+}
+
+fn comparison_numeric(value: &ComparisonValue) -> Option<Numeric> {
+    match value {
+        ComparisonValue::Number(number) => Some(Numeric::Float(*number)),
+        ComparisonValue::Int64(number) => Some(Numeric::Exact(Decimal::new(*number as i128, 0))),
+        ComparisonValue::Decimal128(decimal) => Some(Numeric::Exact(*decimal)),
+        _ => None,
+    }
+}
@@ -1,3 +1,8 @@
+#[allow(clippy::module_inception)]
 pub mod query;
+pub mod matcher;
+pub mod update;
 
-pub use query::Query;
\ No newline at end of file
+pub use query::Query;
+pub use matcher::Matcher;
+pub use update::Update;
\ No newline at end of file
@@ -1,5 +1,10 @@
-use crate::internal::parser::{Parser, JSONParser};
+use crate::internal::parser::{JSONParser, ParseError, tson_delimiters};
+use crate::internal::parser::parse_error::SourceMap;
+use crate::internal::parser::value_cursor::ValueCursor;
 use crate::internal::parser::query_parser::{QueryParser, LogicalOperation};
+use crate::internal::parser::tson_value::object_members;
+use crate::internal::parser::tson_validator::validate;
+use std::rc::Rc;
 
 pub enum Query {
     All,
@@ -8,13 +13,29 @@ pub enum Query {
 }
 
 impl Query {
-    pub fn new(json: String) -> Query {
+    pub fn new(json: String) -> Result<Query, ParseError> {
         if let "{}" = json.as_str() {
-            Query::All
+            Ok(Query::All)
         } else {
-            let parser = JSONParser::new(json);
-            let parser = QueryParser::new(parser.parse());
-            Query::By(parser.parse())
+            let (tson, source) = JSONParser::new(json).parse_with_source()?;
+            Query::parse(tson, Some(source))
         }
     }
-}
\ No newline at end of file
+    pub fn from_tson(tson: Vec<u8>) -> Result<Query, ParseError> {
+        validate(&tson).map_err(|err| ParseError::in_tson("query", err))?;
+        Query::parse(tson, None)
+    }
+    fn parse(tson: Vec<u8>, source: Option<SourceMap>) -> Result<Query, ParseError> {
+        let cursor = ValueCursor::with_source(tson, source.map(Rc::new));
+
+        if cursor.get_value_ref()[0] != tson_delimiters::OBJECT_BEGIN {
+            return Err(cursor.error_at(0, "query", "expected a filter object".to_string()));
+        }
+
+        if object_members(cursor.get_value_ref()).is_empty() {
+            Ok(Query::All)
+        } else {
+            Ok(Query::By(QueryParser::new(cursor).parse()?))
+        }
+    }
+}
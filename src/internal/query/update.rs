@@ -0,0 +1,117 @@
+use crate::internal::parser::{Parser, JSONParser, ParseError, tson_delimiters};
+use crate::internal::parser::parse_error::SourceMap;
+use crate::internal::parser::value_cursor::ValueCursor;
+use crate::internal::parser::tson_value::{object_members, write_object, with_id};
+use crate::internal::parser::tson_validator::validate;
+use std::rc::Rc;
+use std::str;
+
+const CONTEXT: &str = "update";
+
+pub enum UpdateOperation {
+    Set(Vec<u8>, Vec<u8>),
+    Unset(Vec<u8>),
+}
+
+pub enum Update {
+    Replace(Vec<u8>),
+    Operators(Vec<UpdateOperation>),
+}
+
+impl Update {
+    pub fn new(json: String) -> Result<Update, ParseError> {
+        let (tson, source) = JSONParser::new(json).parse_with_source()?;
+        Update::parse(tson, Some(source))
+    }
+    pub fn from_tson(tson: Vec<u8>) -> Result<Update, ParseError> {
+        validate(&tson).map_err(|err| ParseError::in_tson(CONTEXT, err))?;
+        Update::parse(tson, None)
+    }
+    fn parse(tson: Vec<u8>, source: Option<SourceMap>) -> Result<Update, ParseError> {
+        let cursor = ValueCursor::with_source(tson, source.map(Rc::new));
+        let tson = cursor.get_value_ref();
+
+        if tson[0] != tson_delimiters::OBJECT_BEGIN {
+            return Err(cursor.error_at(0, CONTEXT, "expected an update object".to_string()));
+        }
+
+        // Members borrow from the TSON, so their offsets come from pointers.
+        let error_at = |slice: &[u8], message: String| {
+            cursor.error_at(slice.as_ptr() as usize - tson.as_ptr() as usize, CONTEXT, message)
+        };
+
+        let mut operations = Vec::new();
+
+        for (operator, value) in object_members(tson) {
+            let operator_name = str::from_utf8(operator).unwrap();
+
+            if value[0] != tson_delimiters::OBJECT_BEGIN {
+                return Err(error_at(value, format!("expected an object for {}", operator_name)));
+            }
+
+            for (key, value) in object_members(value) {
+                if key == b"_id" {
+                    return Err(error_at(key, "_id cannot be updated".to_string()));
+                }
+                if key.contains(&b'.') {
+                    return Err(error_at(key, "dot notation isn't supported in updates yet".to_string()));
+                }
+
+                let operation = match operator_name {
+                    "$set" => UpdateOperation::Set(key.to_vec(), value.to_vec()),
+                    "$unset" => UpdateOperation::Unset(key.to_vec()),
+                    op if op.starts_with('$') => return Err(error_at(operator, format!("{} isn't supported yet", op))),
+                    _ => return Err(error_at(operator, "expected an update operator, use replace for whole documents".to_string())),
+                };
+                operations.push(operation);
+            }
+        }
+
+        Ok(Update::Operators(operations))
+    }
+    pub fn replacement(json: String) -> Result<Update, ParseError> {
+        let tson = JSONParser::new(json).parse()?;
+
+        if tson[0] != tson_delimiters::OBJECT_BEGIN {
+            return Err(ParseError::new(CONTEXT, "expected a document object".to_string()));
+        }
+
+        Ok(Update::Replace(tson))
+    }
+    pub fn replacement_from_tson(tson: Vec<u8>) -> Result<Update, ParseError> {
+        validate(&tson).map_err(|err| ParseError::in_tson(CONTEXT, err))?;
+
+        if tson[0] != tson_delimiters::OBJECT_BEGIN {
+            return Err(ParseError::new(CONTEXT, "expected a document object".to_string()));
+        }
+
+        Ok(Update::Replace(tson))
+    }
+}
+
+impl Update {
+    pub fn apply(&self, id: &[u8], document: &[u8]) -> Vec<u8> {
+        match self {
+            Update::Replace(replacement) => with_id(id, replacement),
+            Update::Operators(operations) => {
+                let mut members = object_members(document);
+
+                for operation in operations.iter() {
+                    match operation {
+                        UpdateOperation::Set(key, value) => {
+                            match members.iter_mut().find(|(member, _)| *member == key.as_slice()) {
+                                Some(member) => member.1 = value.as_slice(),
+                                None => members.push((key.as_slice(), value.as_slice())),
+                            }
+                        },
+                        UpdateOperation::Unset(key) => {
+                            members.retain(|(member, _)| *member != key.as_slice());
+                        },
+                    }
+                }
+
+                write_object(members.as_slice())
+            },
+        }
+    }
+}
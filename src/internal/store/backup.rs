@@ -0,0 +1,99 @@
+use rocksdb::DB;
+use rocksdb::backup::{BackupEngine, BackupEngineOptions};
+use rocksdb::checkpoint::Checkpoint;
+use librocksdb_sys as ffi;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+use crate::internal::store::StoreError;
+
+pub struct BackupInfo {
+    pub id: u32,
+    pub timestamp: i64,
+    pub size: u64,
+    pub num_files: u32,
+}
+
+/// Adds an incremental backup of `db` to `dir`, flushing memtables first so
+/// the backup doesn't depend on the write ahead log.
+pub fn backup(db: &DB, dir: &str) -> Result<(), StoreError> {
+    Ok(open_engine(dir)?.create_new_backup_flush(db, true)?)
+}
+
+pub fn list_backups(dir: &str) -> Result<Vec<BackupInfo>, StoreError> {
+    let backups = open_engine(dir)?
+        .get_backup_info()
+        .into_iter()
+        .map(|info| BackupInfo {
+            id: info.backup_id,
+            timestamp: info.timestamp,
+            size: info.size,
+            num_files: info.num_files,
+        })
+        .collect();
+
+    Ok(backups)
+}
+
+/// Restores backup `id` from `dir` into `target`, which must not be open.
+pub fn restore_backup(dir: &str, id: u32, target: &str) -> Result<(), StoreError> {
+    let engine = open_engine(dir)?;
+
+    if !engine.get_backup_info().iter().any(|info| info.backup_id == id) {
+        return Err(StoreError::BackupNotFound(id));
+    }
+
+    drop(engine);
+
+    restore_from_backup(dir, id, target).map_err(StoreError::Backup)
+}
+
+pub fn purge_old_backups(dir: &str, keep: usize) -> Result<(), StoreError> {
+    Ok(open_engine(dir)?.purge_old_backups(keep)?)
+}
+
+/// Hard links the live files of `db` into `dir`, which must not exist yet.
+pub fn create_checkpoint(db: &DB, dir: &str) -> Result<(), StoreError> {
+    Ok(Checkpoint::new(db)?.create_checkpoint(dir)?)
+}
+
+fn open_engine(dir: &str) -> Result<BackupEngine, StoreError> {
+    Ok(BackupEngine::open(&BackupEngineOptions::default(), dir)?)
+}
+
+/// The bindings only restore the latest backup, others are restored through
+/// the C API.
+fn restore_from_backup(dir: &str, id: u32, target: &str) -> Result<(), String> {
+    let dir = CString::new(dir).map_err(|_| "paths cannot contain null bytes".to_string())?;
+    let target = CString::new(target).map_err(|_| "paths cannot contain null bytes".to_string())?;
+    let mut err: *mut c_char = ptr::null_mut();
+
+    unsafe {
+        let options = ffi::rocksdb_options_create();
+        let engine = ffi::rocksdb_backup_engine_open(options, dir.as_ptr(), &mut err);
+
+        if err.is_null() {
+            let restore_options = ffi::rocksdb_restore_options_create();
+            ffi::rocksdb_backup_engine_restore_db_from_backup(
+                engine,
+                target.as_ptr(),
+                target.as_ptr(),
+                restore_options,
+                id,
+                &mut err,
+            );
+            ffi::rocksdb_restore_options_destroy(restore_options);
+            ffi::rocksdb_backup_engine_close(engine);
+        }
+
+        ffi::rocksdb_options_destroy(options);
+
+        if !err.is_null() {
+            let message = CStr::from_ptr(err).to_string_lossy().into_owned();
+            ffi::rocksdb_free(err as *mut c_void);
+            return Err(message);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,284 @@
+use rocksdb::{DB, WriteBatch, IteratorMode, Direction};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::convert::TryInto;
+use crate::internal::byte_helper::concat_bytes;
+use crate::internal::store::{key_controls, Handle, StoreError, CollectionOptions};
+use crate::internal::parser::TSONValue;
+use crate::internal::parser::tson_validator::validate_document;
+use crate::internal::parser::tson_value::{
+    object_members,
+    array_elements,
+    write_object,
+    write_array,
+    write_string,
+    write_number,
+};
+
+const MAX_NAME_LEN: usize = 255;
+
+pub struct CollectionInfo {
+    pub id: u32,
+    pub name: String,
+    pub created_at: u64,
+    pub options: Vec<u8>,
+    pub indexes: Vec<String>,
+}
+
+impl CollectionInfo {
+    pub fn new(id: u32, name: String, options: &CollectionOptions) -> CollectionInfo {
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+
+        CollectionInfo {
+            id,
+            name,
+            created_at,
+            options: options.to_tson(),
+            indexes: Vec::new(),
+        }
+    }
+}
+
+impl CollectionInfo {
+    pub fn options(&self) -> CollectionOptions {
+        CollectionOptions::from_tson(&self.options)
+    }
+    /// Column families holding the values and each index of the collection.
+    pub fn column_families(&self) -> Vec<String> {
+        let mut names = vec![values_cf(self.id)];
+        names.extend(self.indexes.iter().map(|index| index_cf(self.id, index)));
+        names
+    }
+    fn to_tson(&self) -> Vec<u8> {
+        let indexes: Vec<Vec<u8>> = self.indexes.iter()
+            .map(|index| write_string(index.as_bytes()))
+            .collect();
+
+        write_object(&[
+            ("id", write_number(self.id as f64)),
+            ("name", write_string(self.name.as_bytes())),
+            ("createdAt", write_number(self.created_at as f64)),
+            ("options", self.options.clone()),
+            ("indexes", write_array(indexes.as_slice())),
+        ])
+    }
+    /// Fails on entries this version doesn't know how to read.
+    pub fn from_tson(tson: &[u8]) -> Result<CollectionInfo, StoreError> {
+        validate_document(tson).map_err(|err| {
+            StoreError::CorruptMetadata(format!("{} in a catalog entry at byte {}", err.reason, err.offset))
+        })?;
+
+        let mut info = CollectionInfo {
+            id: 0,
+            name: String::new(),
+            created_at: 0,
+            options: Vec::new(),
+            indexes: Vec::new(),
+        };
+
+        for (key, value) in object_members(tson) {
+            match (key, TSONValue::read(value)) {
+                (b"id", TSONValue::Number(id)) => info.id = id as u32,
+                (b"name", TSONValue::String(name)) => info.name = to_string(name),
+                (b"createdAt", TSONValue::Number(created_at)) => info.created_at = created_at as u64,
+                (b"options", TSONValue::Object(options)) => info.options = options.to_vec(),
+                (b"indexes", TSONValue::Array(indexes)) => {
+                    info.indexes = array_elements(indexes)
+                        .into_iter()
+                        .filter_map(|index| match TSONValue::read(index) {
+                            TSONValue::String(index) => Some(to_string(index)),
+                            _ => None,
+                        })
+                        .collect();
+                },
+                (key, _) => {
+                    let reason = format!("unexpected field {} in the catalog entry of {}", String::from_utf8_lossy(key), info.name);
+                    return Err(StoreError::CorruptMetadata(reason));
+                },
+            }
+        }
+
+        Ok(info)
+    }
+}
+
+/// Collection metadata, stored under the reserved metadata namespace so it
+/// can't be reached from any collection's key range.
+pub struct Catalog {
+    db: Arc<Handle>,
+    lock: Mutex<()>,
+}
+
+impl Catalog {
+    pub fn new(db: Arc<Handle>) -> Catalog {
+        Catalog { db, lock: Mutex::new(()) }
+    }
+    pub fn get(&self, name: &str) -> Result<Option<CollectionInfo>, StoreError> {
+        match self.db.read()?.get(entry_key(name))? {
+            Some(value) => Ok(Some(CollectionInfo::from_tson(value.as_slice())?)),
+            None => Ok(None),
+        }
+    }
+    /// Held while an entry is read and written back, so changes to the
+    /// catalog don't overwrite each other.
+    pub fn lock(&self) -> MutexGuard<'_, ()> {
+        self.lock.lock().unwrap()
+    }
+    /// Returns the entry of `name`, registering it with the next collection
+    /// id if there's none.
+    pub fn get_or_create(&self, name: &str, options: &CollectionOptions) -> Result<CollectionInfo, StoreError> {
+        let _lock = self.lock();
+
+        if let Some(info) = self.get(name)? {
+            return Ok(info);
+        }
+
+        let db = self.db.read()?;
+        let id = match db.get(metadata_key(key_controls::NEXT_ID))? {
+            Some(value) => value.as_slice().try_into()
+                .map(u32::from_be_bytes)
+                .map_err(|_| StoreError::CorruptMetadata("unexpected next collection id".to_string()))?,
+            None => key_controls::METADATA + 1,
+        };
+
+        let info = CollectionInfo::new(id, name.to_string(), options);
+        let mut batch = WriteBatch::default();
+        Catalog::set_next_id(&mut batch, id + 1);
+        Catalog::put(&mut batch, &info);
+        self.db.commit(&db, batch)?;
+
+        Ok(info)
+    }
+    pub fn list(&self) -> Result<Vec<CollectionInfo>, StoreError> {
+        Catalog::read_all(&*self.db.read()?)
+    }
+}
+
+impl Catalog {
+    /// Every entry, read straight from `db`.
+    pub fn read_all(db: &DB) -> Result<Vec<CollectionInfo>, StoreError> {
+        let prefix = metadata_key(key_controls::CATALOG);
+
+        db.iterator(IteratorMode::From(&prefix, Direction::Forward))
+            .take_while(|(key, _)| key.starts_with(&prefix))
+            .map(|(_, value)| CollectionInfo::from_tson(&value))
+            .collect()
+    }
+    pub fn set_next_id(batch: &mut WriteBatch, id: u32) {
+        batch.put(metadata_key(key_controls::NEXT_ID), id.to_be_bytes());
+    }
+    pub fn put(batch: &mut WriteBatch, info: &CollectionInfo) {
+        batch.put(entry_key(info.name.as_str()), info.to_tson());
+    }
+    pub fn remove(batch: &mut WriteBatch, name: &str) {
+        batch.delete(entry_key(name));
+    }
+}
+
+pub fn metadata_key(control: &str) -> Vec<u8> {
+    concat_bytes(vec![
+        &key_controls::METADATA.to_be_bytes()[..],
+        control.as_bytes(),
+    ])
+}
+
+pub fn values_cf(id: u32) -> String {
+    id.to_string()
+}
+
+pub fn index_cf(id: u32, index: &str) -> String {
+    format!("{}/{}", id, index)
+}
+
+fn entry_key(name: &str) -> Vec<u8> {
+    concat_bytes(vec![
+        metadata_key(key_controls::CATALOG).as_slice(),
+        name.as_bytes(),
+    ])
+}
+
+pub fn validate_name(name: &str) -> Result<(), StoreError> {
+    let reason = if name.is_empty() {
+        "collection names cannot be empty"
+    } else if name.len() > MAX_NAME_LEN {
+        "collection names cannot be longer than 255 bytes"
+    } else if name.starts_with('$') {
+        "collection names cannot start with $"
+    } else if name.chars().any(is_reserved) {
+        "collection names cannot contain null or reserved characters"
+    } else {
+        return Ok(());
+    };
+
+    Err(StoreError::InvalidName(name.to_string(), reason))
+}
+
+pub fn validate_index(index: &str) -> Result<(), StoreError> {
+    let reason = if index.is_empty() || index.split('.').any(str::is_empty) {
+        "index fields cannot have empty keys"
+    } else if index.chars().any(is_reserved) {
+        "index fields cannot contain null or reserved characters"
+    } else {
+        return Ok(());
+    };
+
+    Err(StoreError::InvalidName(index.to_string(), reason))
+}
+
+// Key control characters live in the last private use plane.
+fn is_reserved(c: char) -> bool {
+    c == '\0' || ('\u{10F400}'..='\u{10F4FF}').contains(&c)
+}
+
+fn to_string(slice: &[u8]) -> String {
+    String::from_utf8(slice.to_vec()).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reason(result: Result<(), StoreError>) -> Option<&'static str> {
+        match result {
+            Err(StoreError::InvalidName(_, reason)) => Some(reason),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn accepts_names_outside_the_key_space() {
+        for name in ["users", "a.b", "naïve", "😀", "x$", &"n".repeat(255)].iter() {
+            assert!(validate_name(name).is_ok(), "{} should be valid", name);
+        }
+    }
+
+    #[test]
+    fn rejects_names_that_could_collide() {
+        assert_eq!(reason(validate_name("")), Some("collection names cannot be empty"));
+        assert_eq!(reason(validate_name(&"n".repeat(256))), Some("collection names cannot be longer than 255 bytes"));
+        assert_eq!(reason(validate_name("$cmd")), Some("collection names cannot start with $"));
+
+        for name in ["a\0b", "a\u{10F41F}b", "\u{10F400}", "\u{10F4FF}"].iter() {
+            assert_eq!(reason(validate_name(name)), Some("collection names cannot contain null or reserved characters"));
+        }
+    }
+
+    #[test]
+    fn rejects_index_fields_with_empty_keys() {
+        assert!(validate_index("a.b").is_ok());
+
+        for index in ["", ".", "a.", ".a", "a..b"].iter() {
+            assert_eq!(reason(validate_index(index)), Some("index fields cannot have empty keys"));
+        }
+        assert_eq!(reason(validate_index("a\0")), Some("index fields cannot contain null or reserved characters"));
+    }
+
+    #[test]
+    fn names_column_families_after_ids() {
+        assert_eq!(values_cf(7), "7");
+        assert_eq!(index_cf(7, "a.b"), "7/a.b");
+    }
+}
@@ -1,47 +1,599 @@
-use neon::prelude::*;
-use rocksdb::{DB, WriteBatch};
-use std::sync::Arc;
+use rocksdb::{DB, ColumnFamily, DBIterator, WriteBatch, IteratorMode, Direction};
+use std::sync::{Arc, Mutex, MutexGuard, RwLockReadGuard};
+use std::mem;
+use std::path::Path;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::collections::HashSet;
+use uuid::Uuid;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
 use crate::internal::byte_helper::concat_bytes;
-use crate::internal::store::key_controls;
+use crate::internal::store::{Handle, KeyDictionary, StoreError, format};
+use crate::internal::store::catalog::{values_cf, index_cf};
+use crate::internal::query::{Query, Matcher, Update};
+use crate::internal::parser::{Parser, JSONParser, TSONParser, TSONValue, OutputOptions, SerdeError, tson_delimiters, to_tson, from_tson};
+use crate::internal::parser::tson_value::{get_path, with_id};
+use crate::internal::parser::extended_json::format_object_id;
+use crate::internal::parser::tson_validator::{validate, validate_document, InvalidTSON};
+use crate::internal::parser::compact::Dictionary;
+
+const BATCH_SIZE: usize = 1000;
+
+pub enum WriteMode {
+    Insert,
+    Replace,
+    Upsert,
+}
+
+/// Where imported documents get their ids from.
+pub enum IdMode {
+    /// Use `_id`, replacing stored documents with the same id.
+    Keep,
+    /// Ignore `_id` and assign new ids.
+    Generate,
+    /// Use `_id`, failing on documents that already exist.
+    FailOnConflict,
+}
+
+pub enum ReturnDocument {
+    Before,
+    After,
+}
+
+/// Id and value of a stored document.
+pub type Entry = (Vec<u8>, Vec<u8>);
+
+pub struct CollectionStats {
+    pub count: usize,
+    pub size: usize,
+    pub index_sizes: Vec<(String, usize)>,
+}
+
+/// A stored document that doesn't validate.
+pub struct CorruptDocument {
+    pub id: Vec<u8>,
+    pub value: Vec<u8>,
+    pub error: InvalidTSON,
+}
+
+/// Index fields of a collection, shared by every `Collection` made for it.
+/// Writes hold the lock until they're committed, so they take turns.
+pub type Indexes = Arc<Mutex<Vec<String>>>;
 
 pub struct Collection {
-    db: Arc<DB>,
+    db: Arc<Handle>,
+    id: u32,
     name: String,
+    indexes: Indexes,
+    dictionary: Option<Arc<KeyDictionary>>,
 }
 
 impl Collection {
-    pub fn new(db: Arc<DB>, name: String) -> Collection {
-        Collection { db, name }
+    pub fn new(db: Arc<Handle>, id: u32, name: String) -> Collection {
+        Collection { db, id, name, indexes: Indexes::default(), dictionary: None }
+    }
+    pub fn with_indexes(mut self, indexes: Indexes) -> Collection {
+        self.indexes = indexes;
+        self
+    }
+    pub fn with_dictionary(mut self, dictionary: Option<Arc<KeyDictionary>>) -> Collection {
+        self.dictionary = dictionary;
+        self
+    }
+    pub fn id(&self) -> u32 {
+        self.id
     }
     pub fn get_name(&self) -> &str {
         self.name.as_str()
     }
-    pub fn insert<K, T>(&self, id: K, value: T)
+    pub fn get<K: AsRef<[u8]>>(&self, id: K) -> Result<Option<Vec<u8>>, StoreError> {
+        let db = self.db.read()?;
+        let id = id.as_ref();
+        db.get_cf(self.values_cf(&db)?, id)?.map(|value| self.decode(&db, id, &value)).transpose()
+    }
+    pub fn insert<K, T>(&self, id: K, value: T) -> Result<(), StoreError>
         where
             K: AsRef<[u8]>,
             T: AsRef<[u8]>,
     {
+        self.write(id, value, WriteMode::Insert).map(|_| ())
+    }
+    /// Inserts `value` serialized as a document, with `id` as its `_id`.
+    pub fn insert_typed<K, T>(&self, id: K, value: &T) -> Result<(), StoreError>
+        where
+            K: AsRef<[u8]>,
+            T: Serialize + ?Sized,
+    {
+        let document = to_tson(value)?;
+        if document[0] != tson_delimiters::OBJECT_BEGIN {
+            return Err(SerdeError::new("documents must be objects").into());
+        }
+
+        self.insert(&id, with_id(id.as_ref(), &document))
+    }
+    pub fn get_typed<K, T>(&self, id: K) -> Result<Option<T>, StoreError>
+        where
+            K: AsRef<[u8]>,
+            T: DeserializeOwned,
+    {
+        match self.get(id)? {
+            Some(document) => Ok(Some(from_tson(&document)?)),
+            None => Ok(None),
+        }
+    }
+    pub fn replace<K, T>(&self, id: K, value: T) -> Result<(), StoreError>
+        where
+            K: AsRef<[u8]>,
+            T: AsRef<[u8]>,
+    {
+        self.write(id, value, WriteMode::Replace).map(|_| ())
+    }
+    pub fn upsert<K, T>(&self, id: K, value: T) -> Result<(), StoreError>
+        where
+            K: AsRef<[u8]>,
+            T: AsRef<[u8]>,
+    {
+        self.write(id, value, WriteMode::Upsert).map(|_| ())
+    }
+    /// Writes the document according to `mode` and returns the one it replaced.
+    pub fn write<K, T>(&self, id: K, value: T, mode: WriteMode) -> Result<Option<Vec<u8>>, StoreError>
+        where
+            K: AsRef<[u8]>,
+            T: AsRef<[u8]>,
+    {
+        validate_document(value.as_ref())?;
+
+        let indexes = self.indexes.lock().unwrap();
+        let db = self.db.read()?;
+
+        self.put(&db, &indexes, id.as_ref(), value.as_ref(), mode)
+    }
+    pub fn find(&self, query: &Query) -> Result<Vec<Vec<u8>>, StoreError> {
+        let db = self.db.read()?;
+
+        let documents = self.matching(&db, query)?
+            .map(|entry| entry.map(|(_, value)| value))
+            .collect();
+
+        documents
+    }
+    /// Returns the id and value of the first document matching `query`.
+    pub fn find_one(&self, query: &Query) -> Result<Option<Entry>, StoreError> {
+        self.first_match(&*self.db.read()?, query)
+    }
+    /// Updates the first document matching `query`. No other write can land
+    /// between finding it and replacing it.
+    pub fn find_one_and_update(
+        &self,
+        query: &Query,
+        update: &Update,
+        return_document: ReturnDocument,
+    ) -> Result<Option<Vec<u8>>, StoreError> {
+        let indexes = self.indexes.lock().unwrap();
+        let db = self.db.read()?;
+
+        let (id, before) = match self.first_match(&db, query)? {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+
+        let after = update.apply(id.as_slice(), before.as_slice());
+        self.put(&db, &indexes, &id, &after, WriteMode::Replace)?;
+
+        match return_document {
+            ReturnDocument::Before => Ok(Some(before)),
+            ReturnDocument::After => Ok(Some(after)),
+        }
+    }
+    /// Deletes every document matching `query` along with its index entries,
+    /// and returns how many were deleted.
+    pub fn delete_many(&self, query: &Query) -> Result<usize, StoreError> {
+        let indexes = self.indexes.lock().unwrap();
+
+        if let Query::All = query {
+            return self.delete_all(&indexes);
+        }
+
+        let db = self.db.read()?;
+        let values = self.values_cf(&db)?;
         let mut batch = WriteBatch::default();
-        batch.put(self.values_key(id), value);
-        self.db.write(batch);
+        let mut deleted = 0;
+
+        for entry in self.matching(&db, query)? {
+            let (id, value) = entry?;
+            self.update_index_entries(&db, &indexes, &mut batch, &id, Some(&value), None)?;
+            batch.delete_cf(values, &id);
+            deleted += 1;
+
+            if deleted % BATCH_SIZE == 0 {
+                self.db.commit(&db, mem::take(&mut batch))?;
+            }
+        }
+
+        self.db.commit(&db, batch)?;
+
+        Ok(deleted)
     }
-    fn values_key<T: AsRef<[u8]>>(&self, id: T) -> Vec<u8> {
-        concat_bytes(vec![
-            self.name.as_bytes(),
-            key_controls::NS_BEGIN.as_bytes(),
-            key_controls::VALUES.as_bytes(),
-            id.as_ref(),
-        ])
+    /// Writes every document matching `query` to `path` as one JSON object per
+    /// line, and returns how many were exported. Lines are always Extended JSON
+    /// without indentation, whatever `options` say.
+    pub fn export_jsonl<P: AsRef<Path>>(&self, path: P, query: &Query, options: OutputOptions) -> Result<usize, StoreError> {
+        let options = OutputOptions {
+            extended: true,
+            indent: None,
+            ..options
+        };
+
+        let db = self.db.read()?;
+        let mut file = BufWriter::new(File::create(path)?);
+        let mut exported = 0;
+
+        for entry in self.matching(&db, query)? {
+            let (_, value) = entry?;
+            file.write_all(&TSONParser::with_options(value, options.clone()).parse()?)?;
+            file.write_all(b"\n")?;
+            exported += 1;
+        }
+
+        file.flush()?;
+
+        Ok(exported)
     }
-    fn index_key(&self, index: String, id: String) -> Vec<u8> {
-        concat_bytes(vec![
-            self.name.as_bytes(),
-            key_controls::NS_BEGIN.as_bytes(),
-            key_controls::INDEX.as_bytes(),
-            index.as_ref(),
-            id.as_ref(),
-        ])
+    /// Writes each JSON object line of `path` as a document, and returns how
+    /// many were imported. Documents without `_id` get a new one, other ids
+    /// must be strings. Batches are committed as they fill up, a failure keeps
+    /// the ones committed before it.
+    pub fn import_jsonl<P: AsRef<Path>>(&self, path: P, mode: IdMode) -> Result<usize, StoreError> {
+        let file = BufReader::new(File::open(path)?);
+        let id_key = [b"_id".to_vec()];
+
+        let indexes = self.indexes.lock().unwrap();
+        let db = self.db.read()?;
+        let values = self.values_cf(&db)?;
+        let mut batch = WriteBatch::default();
+        let mut pending = HashSet::new();
+        let mut imported = 0;
+
+        for (i, line) in file.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let document = JSONParser::new(line).parse()
+                .map_err(|err| StoreError::InvalidLine(i + 1, err))?;
+
+            if document[0] != tson_delimiters::OBJECT_BEGIN {
+                return Err(StoreError::InvalidDocument(i + 1, "documents must be objects"));
+            }
+
+            let id = match (&mode, get_path(&document, &id_key).map(TSONValue::read)) {
+                (IdMode::Generate, _) | (_, None) => Uuid::new_v4().to_string().into_bytes(),
+                (_, Some(TSONValue::String(id))) => id.to_vec(),
+                (_, Some(TSONValue::ObjectId(id))) => format_object_id(id).into_bytes(),
+                _ => return Err(StoreError::InvalidDocument(i + 1, "ids must be strings or ObjectIds")),
+            };
+
+            // Stored documents are looked up outside the batch, so a repeated id
+            // must see the earlier one committed.
+            if pending.contains(&id) {
+                if let IdMode::FailOnConflict = mode {
+                    return Err(StoreError::AlreadyExists(id_string(&id)));
+                }
+                self.db.commit(&db, mem::take(&mut batch))?;
+                pending.clear();
+            }
+
+            let previous = db.get_cf(values, &id)?.map(|value| self.decode(&db, &id, &value)).transpose()?;
+
+            if let (IdMode::FailOnConflict, Some(_)) = (&mode, &previous) {
+                return Err(StoreError::AlreadyExists(id_string(&id)));
+            }
+
+            let document = with_id(&id, &document);
+            self.update_index_entries(&db, &indexes, &mut batch, &id, previous.as_deref(), Some(&document))?;
+            batch.put_cf(values, &id, self.encode(&db, &document)?);
+            pending.insert(id);
+            imported += 1;
+
+            if imported % BATCH_SIZE == 0 {
+                self.db.commit(&db, mem::take(&mut batch))?;
+                pending.clear();
+            }
+        }
+
+        self.db.commit(&db, batch)?;
+
+        Ok(imported)
+    }
+    /// Sizes are as stored, before compression.
+    pub fn stats(&self) -> Result<CollectionStats, StoreError> {
+        let indexes = self.indexes.lock().unwrap().clone();
+        let db = self.db.read()?;
+
+        let (count, size) = self.raw_values(&db)?
+            .fold((0, 0), |(count, size), (key, value)| (count + 1, size + key.len() + value.len()));
+
+        let mut index_sizes = Vec::new();
+        for index in indexes.iter() {
+            let size = db.iterator_cf(self.index_cf(&db, index)?, IteratorMode::Start)
+                .map(|(key, value)| key.len() + value.len())
+                .sum();
+            index_sizes.push((index.clone(), size));
+        }
+
+        Ok(CollectionStats { count, size, index_sizes })
+    }
+    /// Validates every stored document and returns the corrupt ones.
+    pub fn verify(&self) -> Result<Vec<CorruptDocument>, StoreError> {
+        let db = self.db.read()?;
+        if let Some(dictionary) = &self.dictionary {
+            dictionary.refresh(&db)?;
+        }
+        let keys = self.keys();
+
+        let corrupt = self.raw_values(&db)?
+            .filter_map(|(id, value)| match format::validate(&value, keys.as_deref()) {
+                Ok(_) => None,
+                Err(error) => Some(CorruptDocument { id: id.into_vec(), value: value.into_vec(), error }),
+            })
+            .collect();
+
+        Ok(corrupt)
+    }
+    /// Deletes corrupt documents. Their index keys can't be read out of them,
+    /// so the indexes are scanned for entries ending with their ids instead.
+    pub fn delete_corrupt(&self, ids: &[Vec<u8>]) -> Result<(), StoreError> {
+        let indexes = self.indexes.lock().unwrap();
+        let db = self.db.read()?;
+        let values = self.values_cf(&db)?;
+        let mut batch = WriteBatch::default();
+
+        for id in ids.iter() {
+            batch.delete_cf(values, id);
+        }
+
+        for index in indexes.iter() {
+            let cf = self.index_cf(&db, index)?;
+
+            for (key, _) in db.iterator_cf(cf, IteratorMode::Start) {
+                if ids.iter().any(|id| is_index_entry_of(&key, id)) {
+                    batch.delete_cf(cf, key);
+                }
+            }
+        }
+
+        self.db.commit(&db, batch)?;
+
+        Ok(())
+    }
+    /// Rewrites values stored in older formats and returns how many were
+    /// rewritten. Corrupt values are left as they are for `verify` to find.
+    /// Each batch holds the database exclusively, so no write can
+    /// land between reading a value and rewriting it.
+    pub fn upgrade_format(&self) -> Result<usize, StoreError> {
+        let mut upgraded = 0;
+        let mut from = Vec::new();
+
+        loop {
+            let db = self.db.write()?;
+            let values = self.values_cf(&db)?;
+            let mut batch = WriteBatch::default();
+            let mut done = true;
+
+            let entries = db.iterator_cf(values, IteratorMode::From(&from, Direction::Forward));
+            for (scanned, (id, value)) in entries.enumerate() {
+                if scanned == BATCH_SIZE {
+                    from = id.into_vec();
+                    done = false;
+                    break;
+                }
+
+                if format::version(&value) != Some(format::CURRENT) && format::validate(&value, None).is_ok() {
+                    let document = self.decode(&db, &id, &value)?;
+                    batch.put_cf(values, &id, self.encode(&db, &document)?);
+                    upgraded += 1;
+                }
+            }
+
+            self.db.commit(&db, batch)?;
+
+            if done {
+                return Ok(upgraded);
+            }
+        }
+    }
+    /// Locks the collection against writes, returning its index fields.
+    /// Indexes pushed to them are maintained by every later write.
+    pub fn lock_indexes(&self) -> MutexGuard<'_, Vec<String>> {
+        self.indexes.lock().unwrap()
+    }
+    /// Writes entries of a new index for every stored document.
+    pub fn build_index(&self, db: &DB, batch: &mut WriteBatch, index: &str) -> Result<(), StoreError> {
+        let cf = self.index_cf(db, index)?;
+
+        for entry in self.values(db)? {
+            let (id, value) = entry?;
+            if let Some(key) = index_key(index, &value, &id) {
+                batch.put_cf(cf, key, b"");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Collection {
+    /// Writes the document according to `mode`, with the collection locked.
+    fn put(&self, db: &DB, indexes: &[String], id: &[u8], value: &[u8], mode: WriteMode) -> Result<Option<Vec<u8>>, StoreError> {
+        let values = self.values_cf(db)?;
+        let previous = db.get_cf(values, id)?.map(|value| self.decode(db, id, &value)).transpose()?;
+
+        match (mode, &previous) {
+            (WriteMode::Insert, Some(_)) => return Err(StoreError::AlreadyExists(id_string(id))),
+            (WriteMode::Replace, None) => return Err(StoreError::NotFound(id_string(id))),
+            _ => (),
+        }
+
+        let mut batch = WriteBatch::default();
+        self.update_index_entries(db, indexes, &mut batch, id, previous.as_deref(), Some(value))?;
+        batch.put_cf(values, id, self.encode(db, value)?);
+        self.db.commit(db, batch)?;
+
+        Ok(previous)
+    }
+    fn first_match(&self, db: &DB, query: &Query) -> Result<Option<Entry>, StoreError> {
+        self.matching(db, query)?.next().transpose()
+    }
+    /// Ids and decoded values of every stored document. A corrupt one is an
+    /// error in its place.
+    fn values<'a>(&'a self, db: &'a DB) -> Result<impl Iterator<Item = Result<Entry, StoreError>> + 'a, StoreError> {
+        Ok(self.raw_values(db)?.map(move |(id, value)| Ok((id.to_vec(), self.decode(db, &id, &value)?))))
     }
+    /// Same as `values`, for the documents matching `query`.
+    fn matching<'a>(&'a self, db: &'a DB, query: &'a Query) -> Result<impl Iterator<Item = Result<Entry, StoreError>> + 'a, StoreError> {
+        Ok(self.values(db)?.filter(move |entry| entry.as_ref().map_or(true, |(_, value)| query.matches(value))))
+    }
+    /// Decodes a stored value. A key missing from the dictionary may have
+    /// been added by another process, so it's reloaded once before giving up.
+    /// Corrupt values are errors, `verify` finds them all.
+    fn decode(&self, db: &DB, id: &[u8], value: &[u8]) -> Result<Vec<u8>, StoreError> {
+        let decoded = format::decode(value, self.keys().as_deref());
+
+        let decoded = match (decoded, &self.dictionary) {
+            (Err(_), Some(dictionary)) if dictionary.refresh(db).is_ok() => {
+                format::decode(value, self.keys().as_deref())
+            },
+            (decoded, _) => decoded,
+        };
+
+        decoded.map_err(|err| StoreError::CorruptDocument(self.name.clone(), id_string(id), err))
+    }
+    /// Encodes a value for storing, giving its new keys ids first.
+    fn encode(&self, db: &DB, value: &[u8]) -> Result<Vec<u8>, StoreError> {
+        if let Some(dictionary) = &self.dictionary {
+            dictionary.add_keys(&self.db, db, value)?;
+        }
+
+        Ok(format::encode(value, self.keys().as_deref()))
+    }
+    fn keys(&self) -> Option<RwLockReadGuard<'_, Dictionary>> {
+        self.dictionary.as_ref().map(|dictionary| dictionary.read())
+    }
+    fn raw_values<'a>(&self, db: &'a DB) -> Result<DBIterator<'a>, StoreError> {
+        Ok(db.iterator_cf(self.values_cf(db)?, IteratorMode::Start))
+    }
+    /// Deletes the values and every index entry as key ranges, in one batch.
+    /// Counting the deleted documents only walks their keys.
+    fn delete_all(&self, indexes: &[String]) -> Result<usize, StoreError> {
+        let db = self.db.read()?;
+        let values = self.values_cf(&db)?;
+
+        let mut keys = db.raw_iterator_cf(values);
+        let mut deleted = 0;
+        keys.seek_to_first();
+        while keys.valid() {
+            deleted += 1;
+            keys.next();
+        }
+        keys.status()?;
+
+        let mut batch = WriteBatch::default();
+        delete_range(&db, &mut batch, values)?;
+        for index in indexes.iter() {
+            delete_range(&db, &mut batch, self.index_cf(&db, index)?)?;
+        }
+        self.db.commit(&db, batch)?;
+
+        Ok(deleted)
+    }
+    fn update_index_entries(
+        &self,
+        db: &DB,
+        indexes: &[String],
+        batch: &mut WriteBatch,
+        id: &[u8],
+        previous: Option<&[u8]>,
+        next: Option<&[u8]>,
+    ) -> Result<(), StoreError> {
+        for index in indexes.iter() {
+            let previous = previous.and_then(|value| index_key(index, value, id));
+            let next = next.and_then(|value| index_key(index, value, id));
+
+            if previous == next {
+                continue;
+            }
+
+            let cf = self.index_cf(db, index)?;
+
+            if let Some(key) = previous {
+                batch.delete_cf(cf, key);
+            }
+            if let Some(key) = next {
+                batch.put_cf(cf, key, b"");
+            }
+        }
+
+        Ok(())
+    }
+    fn values_cf<'a>(&self, db: &'a DB) -> Result<&'a ColumnFamily, StoreError> {
+        db.cf_handle(&values_cf(self.id))
+            .ok_or_else(|| StoreError::CollectionNotFound(self.name.clone()))
+    }
+    fn index_cf<'a>(&self, db: &'a DB, index: &str) -> Result<&'a ColumnFamily, StoreError> {
+        db.cf_handle(&index_cf(self.id, index))
+            .ok_or_else(|| StoreError::CollectionNotFound(self.name.clone()))
+    }
+}
+
+/// Deletes every key of a column family. Range ends are exclusive, so the
+/// last key is deleted on its own.
+fn delete_range(db: &DB, batch: &mut WriteBatch, column_family: &ColumnFamily) -> Result<(), StoreError> {
+    let mut keys = db.raw_iterator_cf(column_family);
+    keys.seek_to_first();
+    let first = keys.key().map(<[u8]>::to_vec);
+    keys.seek_to_last();
+
+    if let (Some(first), Some(last)) = (first, keys.key()) {
+        batch.delete_range_cf(column_family, first.as_slice(), last);
+        batch.delete_cf(column_family, last);
+    }
+
+    keys.status()?;
+    Ok(())
+}
+
+fn id_string(id: &[u8]) -> String {
+    String::from_utf8_lossy(id).into_owned()
 }
 
-impl Finalize for Collection {}
\ No newline at end of file
+fn index_key(index: &str, document: &[u8], id: &[u8]) -> Option<Vec<u8>> {
+    let indexed = get_path(document, &namespace(index))?;
+    Some(concat_bytes(vec![index_value(indexed).as_slice(), id]))
+}
+
+/// Dates are written big-endian with the sign bit flipped, so their index
+/// entries sort chronologically. Other values keep their TSON bytes.
+fn index_value(value: &[u8]) -> Vec<u8> {
+    match TSONValue::read(value) {
+        TSONValue::Date(millis) => {
+            let sortable = (millis as u64) ^ (1 << 63);
+            concat_bytes(vec![&[tson_delimiters::DATE][..], &sortable.to_be_bytes()])
+        },
+        _ => value.to_vec(),
+    }
+}
+
+/// Whether an index key is the entry of `id`, an indexed value followed by the id.
+fn is_index_entry_of(key: &[u8], id: &[u8]) -> bool {
+    match key.strip_suffix(id) {
+        Some([tson_delimiters::DATE, sortable @ ..]) => sortable.len() == 8,
+        Some(value) if !value.is_empty() => validate(value).is_ok(),
+        _ => false,
+    }
+}
+
+fn namespace(index: &str) -> Vec<Vec<u8>> {
+    index.split('.').map(|key| key.as_bytes().to_vec()).collect()
+}
@@ -1,32 +1,373 @@
-use neon::prelude::*;
-use std::sync::Arc;
-use rocksdb::{DB, ReadOptions, IteratorMode, Direction};
-use crate::internal::parser::{ Parser, TSONParser };
-use crate::internal::store::Collection;
+use std::sync::{Arc, Mutex};
+use std::collections::HashMap;
+use rocksdb::{DB, Options, WriteBatch, ColumnFamilyDescriptor, DEFAULT_COLUMN_FAMILY_NAME};
+use crate::internal::store::{
+    Collection,
+    CollectionStats,
+    CorruptDocument,
+    Catalog,
+    CollectionInfo,
+    CollectionOptions,
+    DatabaseOptions,
+    KeyDictionary,
+    Handle,
+    StoreError,
+    BackupInfo,
+};
+use crate::internal::store::collection::Indexes;
+use crate::internal::store::catalog::{validate_name, validate_index, index_cf};
+use crate::internal::store::{migration, backup, format};
+use crate::internal::store::format::Marker;
+use crate::internal::store::catalog::metadata_key;
+use crate::internal::parser::tson_value::{write_object, write_string, write_binary};
 
+/// Collection corrupt documents are moved to by `Database::verify`.
+pub const CORRUPT_COLLECTION: &str = "_corrupt";
+
+/// Each collection keeps its values and every one of its indexes in column
+/// families of their own, named after the collection id. Metadata lives in the
+/// default column family, under keys prefixed by the big-endian `METADATA` id.
 pub mod key_controls {
     pub const NS_BEGIN:        &str = "\u{10F41F}";
-    pub const NS_END:          &str = "\u{10F420}";
     pub const INDEX:           &str = "0";
     pub const VALUES:          &str = "1";
+
+    pub const METADATA:        u32 = 0;
+    pub const CATALOG:         &str = "0";
+    pub const NEXT_ID:         &str = "1";
+    pub const LAYOUT:          &str = "2";
+    pub const FORMAT:          &str = "3";
+    pub const KEYS:            &str = "4";
+    pub const MIGRATION:       &str = "5";
+
+    pub const LEGACY_METADATA: &str = "\u{10F421}";
+}
+
+/// How the database was opened. Only `ReadWrite` can create collections.
+#[derive(Clone, Copy, PartialEq)]
+enum OpenMode {
+    ReadWrite,
+    ReadOnly,
+    Secondary,
 }
 
 pub struct Database {
-    db: Arc<DB>,
+    db: Arc<Handle>,
+    catalog: Catalog,
+    mode: OpenMode,
+    /// Key dictionaries by collection id, shared by every `Collection` made
+    /// so ids are given out once.
+    dictionaries: Mutex<HashMap<u32, Arc<KeyDictionary>>>,
+    /// Index fields by collection id, shared the same way so writes to a
+    /// collection take turns whichever `Collection` they go through.
+    indexes: Mutex<HashMap<u32, Indexes>>,
 }
 
 impl Database {
-    pub fn new(path: String) -> Database {
-        let db = match DB::open_default(path) {
-            Ok(db) => Arc::new(db),
-            Err(err) => panic!("Unexpected error: {}", err)
+    pub fn new(path: String, options: DatabaseOptions) -> Result<Database, StoreError> {
+        if options.read_only {
+            return Database::open_read_only(path, options);
+        }
+
+        let db_options = options.to_options();
+
+        let descriptors = column_families(&db_options, &path)
+            .into_iter()
+            .map(|name| ColumnFamilyDescriptor::new(name, db_options.clone()));
+        let mut db = DB::open_cf_descriptors(&db_options, &path, descriptors)?;
+
+        migration::migrate(&mut db, &db_options)?;
+        format::mark(&db)?;
+
+        Ok(Database::from_db(db, db_options, &options, OpenMode::ReadWrite))
+    }
+    /// Opens without taking the lock, alongside a process writing to `path`.
+    /// Sees the data as of opening, writes fail. The layout isn't migrated, the
+    /// database must have been opened for writing by this version before.
+    pub fn open_read_only(path: String, options: DatabaseOptions) -> Result<Database, StoreError> {
+        let db_options = options.to_options();
+
+        let column_families = column_families(&db_options, &path);
+        let db = DB::open_cf_for_read_only(&db_options, &path, column_families, false)?;
+        format::check(&db)?;
+
+        Ok(Database::from_db(db, db_options, &options, OpenMode::ReadOnly))
+    }
+    /// Opens as a secondary of the process writing to `path`, keeping its own
+    /// logs under `secondary_path`. Writes fail, `catch_up` replays what the
+    /// primary wrote since. Collections created after opening aren't found,
+    /// even once `catch_up` brings in their catalog entries.
+    pub fn open_as_secondary(path: String, secondary_path: String, options: DatabaseOptions) -> Result<Database, StoreError> {
+        let mut db_options = options.to_options();
+        db_options.set_max_open_files(-1);
+
+        let column_families = column_families(&db_options, &path);
+        let db = DB::open_cf_as_secondary(&db_options, &path, &secondary_path, column_families)?;
+        format::check(&db)?;
+
+        Ok(Database::from_db(db, db_options, &options, OpenMode::Secondary))
+    }
+    pub fn collection(&self, name: String) -> Result<Collection, StoreError> {
+        self.collection_with_options(name, CollectionOptions::default())
+    }
+    /// Returns the collection, registering it in the catalog and creating its
+    /// column families on first use. `options` only apply when it's created.
+    /// Read-only and secondary databases only return existing collections.
+    pub fn collection_with_options(&self, name: String, options: CollectionOptions) -> Result<Collection, StoreError> {
+        let info = match self.catalog.get(&name)? {
+            Some(info) => info,
+            None if self.mode != OpenMode::ReadWrite => return Err(StoreError::CollectionNotFound(name)),
+            None => {
+                validate_name(&name)?;
+                self.catalog.get_or_create(&name, &options)?
+            },
         };
 
-        Database { db }
+        self.create_column_families(&info)?;
+
+        self.open_collection(&info)
     }
-    pub fn collection(&self, name: String) -> Collection {
-        Collection::new(Arc::clone(&self.db), name)
+    pub fn list_collections(&self) -> Result<Vec<CollectionInfo>, StoreError> {
+        self.catalog.list()
+    }
+    pub fn drop_collection(&self, name: &str) -> Result<(), StoreError> {
+        let _lock = self.catalog.lock();
+        let (_, info) = self.existing_collection(name)?;
+
+        let mut batch = WriteBatch::default();
+        Catalog::remove(&mut batch, name);
+
+        let mut db = self.db.write()?;
+        KeyDictionary::remove(&db, &mut batch, info.id);
+        self.db.commit(&db, batch)?;
+        self.dictionaries.lock().unwrap().remove(&info.id);
+        self.indexes.lock().unwrap().remove(&info.id);
+
+        for column_family in info.column_families().iter() {
+            if db.cf_handle(column_family).is_some() {
+                db.drop_cf(column_family)?;
+            }
+        }
+
+        Ok(())
+    }
+    /// Renames only touch the catalog, column families are bound to the collection id.
+    pub fn rename_collection(&self, from: &str, to: String) -> Result<(), StoreError> {
+        let _lock = self.catalog.lock();
+        let (_, mut info) = self.existing_collection(from)?;
+
+        validate_name(&to)?;
+
+        if self.catalog.get(&to)?.is_some() {
+            return Err(StoreError::CollectionExists(to));
+        }
+
+        let mut batch = WriteBatch::default();
+        Catalog::remove(&mut batch, from);
+        info.name = to;
+        Catalog::put(&mut batch, &info);
+        self.db.commit(&*self.db.read()?, batch)?;
+
+        Ok(())
+    }
+    /// Builds the index with the collection locked, so every `Collection` of
+    /// it maintains the index from its next write on.
+    pub fn create_index(&self, name: &str, index: String) -> Result<(), StoreError> {
+        validate_index(&index)?;
+
+        let collection = self.collection(name.to_string())?;
+        let mut indexes = collection.lock_indexes();
+
+        if indexes.contains(&index) {
+            return Ok(());
+        }
+
+        // A failed earlier attempt may have left the column family behind.
+        let column_family = index_cf(collection.id(), &index);
+        let mut db = self.db.write()?;
+        if db.cf_handle(&column_family).is_none() {
+            self.db.create_cf(&mut db, &column_family)?;
+        }
+        drop(db);
+
+        let db = self.db.read()?;
+        let mut batch = WriteBatch::default();
+        collection.build_index(&db, &mut batch, &index)?;
+
+        let _lock = self.catalog.lock();
+        let mut info = self.catalog.get(name)?
+            .ok_or_else(|| StoreError::CollectionNotFound(name.to_string()))?;
+        info.indexes.push(index.clone());
+        Catalog::put(&mut batch, &info);
+        self.db.commit(&db, batch)?;
+        indexes.push(index);
+
+        Ok(())
+    }
+    pub fn collection_stats(&self, name: &str) -> Result<CollectionStats, StoreError> {
+        let (collection, _) = self.existing_collection(name)?;
+        collection.stats()
+    }
+    /// Validates every stored document and returns the corrupt ones with the
+    /// name of their collection. With `quarantine` they are moved into
+    /// `CORRUPT_COLLECTION`, keeping their raw bytes as binaries.
+    pub fn verify(&self, quarantine: bool) -> Result<Vec<(String, CorruptDocument)>, StoreError> {
+        let mut found = Vec::new();
+
+        for info in self.catalog.list()? {
+            let (collection, _) = self.existing_collection(&info.name)?;
+            let corrupt = collection.verify()?;
+
+            if quarantine && !corrupt.is_empty() && info.name != CORRUPT_COLLECTION {
+                let target = self.collection(CORRUPT_COLLECTION.to_string())?;
+
+                for document in corrupt.iter() {
+                    let id = [info.name.as_bytes(), b"/", document.id.as_slice()].concat();
+                    target.upsert(&id, write_object(&[
+                        ("_id", write_string(&id)),
+                        ("collection", write_string(info.name.as_bytes())),
+                        ("id", write_string(&document.id)),
+                        ("error", write_string(document.error.to_string().as_bytes())),
+                        ("value", write_binary(0, &document.value)),
+                    ]))?;
+                }
+
+                let ids: Vec<Vec<u8>> = corrupt.iter().map(|document| document.id.clone()).collect();
+                collection.delete_corrupt(&ids)?;
+            }
+
+            found.extend(corrupt.into_iter().map(|document| (info.name.clone(), document)));
+        }
+
+        Ok(found)
+    }
+    /// Rewrites every value stored in an older format, and returns how many
+    /// were rewritten. Older values stay readable meanwhile, and the database
+    /// can be used while it runs.
+    pub fn upgrade_format(&self) -> Result<usize, StoreError> {
+        let marker = Marker::read(&*self.db.read()?)?;
+        if let Some(Marker { oldest: format::CURRENT, .. }) = marker {
+            return Ok(0);
+        }
+
+        let mut upgraded = 0;
+        for info in self.catalog.list()? {
+            let (collection, _) = self.existing_collection(&info.name)?;
+            upgraded += collection.upgrade_format()?;
+        }
+
+        let marker = Marker { written: format::CURRENT, oldest: format::CURRENT };
+        self.db.read()?.put(metadata_key(key_controls::FORMAT), marker.to_bytes())?;
+
+        Ok(upgraded)
+    }
+    pub fn catch_up(&self) -> Result<(), StoreError> {
+        Ok(self.db.read()?.try_catch_up_with_primary()?)
+    }
+    pub fn backup(&self, dir: &str) -> Result<(), StoreError> {
+        backup::backup(&*self.db.read()?, dir)
+    }
+    pub fn list_backups(dir: &str) -> Result<Vec<BackupInfo>, StoreError> {
+        backup::list_backups(dir)
+    }
+    pub fn restore_backup(dir: &str, id: u32, target: &str) -> Result<(), StoreError> {
+        backup::restore_backup(dir, id, target)
+    }
+    pub fn purge_old_backups(dir: &str, keep: usize) -> Result<(), StoreError> {
+        backup::purge_old_backups(dir, keep)
+    }
+    pub fn create_checkpoint(&self, dir: &str) -> Result<(), StoreError> {
+        backup::create_checkpoint(&*self.db.read()?, dir)
+    }
+    /// Flushes and releases the database. Collections obtained from it, and the
+    /// database itself, fail with `StoreError::Closed` afterwards.
+    pub fn close(&self) -> Result<(), StoreError> {
+        self.db.close()
+    }
+    /// Deletes the database at `path`, which must not be open.
+    pub fn destroy(path: String) -> Result<(), StoreError> {
+        Ok(DB::destroy(&Options::default(), path)?)
+    }
+}
+
+impl Database {
+    fn from_db(db: DB, db_options: Options, options: &DatabaseOptions, mode: OpenMode) -> Database {
+        let db = Arc::new(Handle::new(db, db_options, options.to_write_options()));
+        let catalog = Catalog::new(Arc::clone(&db));
+
+        Database {
+            db,
+            catalog,
+            mode,
+            dictionaries: Mutex::new(HashMap::new()),
+            indexes: Mutex::new(HashMap::new()),
+        }
+    }
+    fn existing_collection(&self, name: &str) -> Result<(Collection, CollectionInfo), StoreError> {
+        let info = match self.catalog.get(name)? {
+            Some(info) => info,
+            None => return Err(StoreError::CollectionNotFound(name.to_string())),
+        };
+
+        Ok((self.open_collection(&info)?, info))
+    }
+    fn open_collection(&self, info: &CollectionInfo) -> Result<Collection, StoreError> {
+        let indexes = Arc::clone(self.indexes.lock().unwrap()
+            .entry(info.id)
+            .or_insert_with(|| Arc::new(Mutex::new(info.indexes.clone()))));
+
+        let collection = Collection::new(Arc::clone(&self.db), info.id, info.name.clone())
+            .with_indexes(indexes)
+            .with_dictionary(self.dictionary(info)?);
+
+        Ok(collection)
+    }
+    /// Loads the key dictionary of the collection on first use, if it has one.
+    fn dictionary(&self, info: &CollectionInfo) -> Result<Option<Arc<KeyDictionary>>, StoreError> {
+        if !info.options().key_dictionary {
+            return Ok(None);
+        }
+
+        let mut dictionaries = self.dictionaries.lock().unwrap();
+
+        if let Some(dictionary) = dictionaries.get(&info.id) {
+            return Ok(Some(Arc::clone(dictionary)));
+        }
+
+        let dictionary = Arc::new(KeyDictionary::load(&*self.db.read()?, info.id)?);
+        dictionaries.insert(info.id, Arc::clone(&dictionary));
+
+        Ok(Some(dictionary))
+    }
+    fn create_column_families(&self, info: &CollectionInfo) -> Result<(), StoreError> {
+        let missing: Vec<String> = {
+            let db = self.db.read()?;
+            info.column_families()
+                .into_iter()
+                .filter(|column_family| db.cf_handle(column_family).is_none())
+                .collect()
+        };
+
+        if missing.is_empty() {
+            return Ok(());
+        }
+        // Read-only and secondary databases only have the column families
+        // that were there when they were opened, and can't create more.
+        if self.mode != OpenMode::ReadWrite {
+            return Err(StoreError::CollectionNotFound(info.name.clone()));
+        }
+
+        let mut db = self.db.write()?;
+        for column_family in missing.iter() {
+            if db.cf_handle(column_family).is_none() {
+                self.db.create_cf(&mut db, column_family)?;
+            }
+        }
+
+        Ok(())
     }
 }
 
-impl Finalize for Database {}
+fn column_families(options: &Options, path: &str) -> Vec<String> {
+    DB::list_cf(options, path).unwrap_or_else(|_| vec![DEFAULT_COLUMN_FAMILY_NAME.to_string()])
+}
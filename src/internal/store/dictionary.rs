@@ -0,0 +1,93 @@
+use rocksdb::{DB, WriteBatch, IteratorMode, Direction};
+use std::sync::{RwLock, RwLockReadGuard};
+use crate::internal::byte_helper::concat_bytes;
+use crate::internal::store::{key_controls, Handle, StoreError};
+use crate::internal::store::catalog::metadata_key;
+use crate::internal::parser::compact::{self, Dictionary};
+
+/// Key dictionary of a collection. Each key is stored under the collection
+/// and key ids, and committed before any value refers to it.
+pub struct KeyDictionary {
+    collection: u32,
+    keys: RwLock<Dictionary>,
+}
+
+impl KeyDictionary {
+    pub fn load(db: &DB, collection: u32) -> Result<KeyDictionary, StoreError> {
+        let dictionary = KeyDictionary { collection, keys: RwLock::new(Dictionary::default()) };
+        dictionary.refresh(db)?;
+
+        Ok(dictionary)
+    }
+    /// Reads keys added since loading, by another process writing to `db`.
+    pub fn refresh(&self, db: &DB) -> Result<(), StoreError> {
+        let mut keys = self.keys.write().unwrap();
+        let prefix = prefix(self.collection);
+        let from = entry_key(self.collection, keys.len() as u32);
+
+        for (key, name) in db.iterator(IteratorMode::From(&from, Direction::Forward)) {
+            if !key.starts_with(&prefix) {
+                break;
+            }
+            keys.insert(name.into_vec());
+        }
+
+        Ok(())
+    }
+    pub fn read(&self) -> RwLockReadGuard<'_, Dictionary> {
+        self.keys.read().unwrap()
+    }
+    /// Gives ids to the keys of `tson` that have none yet.
+    pub fn add_keys(&self, handle: &Handle, db: &DB, tson: &[u8]) -> Result<(), StoreError> {
+        let missing: Vec<&[u8]> = {
+            let keys = self.keys.read().unwrap();
+            compact::keys(tson).into_iter().filter(|key| keys.id(key).is_none()).collect()
+        };
+
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        let mut keys = self.keys.write().unwrap();
+        let len = keys.len();
+        let mut batch = WriteBatch::default();
+
+        for key in missing {
+            if keys.id(key).is_none() {
+                let id = keys.insert(key.to_vec());
+                batch.put(entry_key(self.collection, id), key);
+            }
+        }
+
+        // Ids that didn't make it to disk must not be handed out.
+        if let Err(err) = handle.commit(db, batch) {
+            keys.truncate(len);
+            return Err(err);
+        }
+
+        Ok(())
+    }
+    /// Deletes the keys of a dropped collection.
+    pub fn remove(db: &DB, batch: &mut WriteBatch, collection: u32) {
+        let prefix = prefix(collection);
+
+        for (key, _) in db.iterator(IteratorMode::From(&prefix, Direction::Forward)) {
+            if !key.starts_with(&prefix) {
+                break;
+            }
+            batch.delete(key);
+        }
+    }
+}
+
+fn prefix(collection: u32) -> Vec<u8> {
+    concat_bytes(vec![
+        metadata_key(key_controls::KEYS),
+        collection.to_be_bytes().to_vec(),
+    ])
+}
+
+fn entry_key(collection: u32, id: u32) -> Vec<u8> {
+    concat_bytes(vec![prefix(collection), id.to_be_bytes().to_vec()])
+}
+
@@ -0,0 +1,80 @@
+use std::{fmt, io};
+use crate::internal::parser::{ParseError, OutputError, SerdeError};
+use crate::internal::parser::tson_validator::InvalidTSON;
+
+#[derive(Debug)]
+pub enum StoreError {
+    AlreadyExists(String),
+    NotFound(String),
+    CollectionExists(String),
+    CollectionNotFound(String),
+    InvalidName(String, &'static str),
+    CorruptMetadata(String),
+    Closed,
+    BackupNotFound(u32),
+    Backup(String),
+    InvalidLine(usize, ParseError),
+    InvalidDocument(usize, &'static str),
+    InvalidTSON(InvalidTSON),
+    CorruptDocument(String, String, InvalidTSON),
+    Output(OutputError),
+    Serde(SerdeError),
+    UnsupportedFormat(u8),
+    RocksDB(rocksdb::Error),
+    Io(io::Error),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StoreError::AlreadyExists(id) => write!(f, "Document with id {} already exists.", id),
+            StoreError::NotFound(id) => write!(f, "Document with id {} doesn't exist.", id),
+            StoreError::CollectionExists(name) => write!(f, "Collection {} already exists.", name),
+            StoreError::CollectionNotFound(name) => write!(f, "Collection {} doesn't exist.", name),
+            StoreError::InvalidName(name, reason) => write!(f, "Invalid name {:?}, {}.", name, reason),
+            StoreError::CorruptMetadata(reason) => write!(f, "Corrupt metadata, {}.", reason),
+            StoreError::Closed => write!(f, "Database is closed."),
+            StoreError::BackupNotFound(id) => write!(f, "Backup {} doesn't exist.", id),
+            StoreError::Backup(err) => write!(f, "Backup error: {}", err),
+            StoreError::InvalidLine(line, err) => write!(f, "Line {}: {}", line, err),
+            StoreError::InvalidDocument(line, reason) => write!(f, "Line {}: {}.", line, reason),
+            StoreError::InvalidTSON(err) => write!(f, "{}", err),
+            StoreError::CorruptDocument(name, id, err) => write!(f, "Document {} in {} is corrupt: {}", id, name, err),
+            StoreError::Output(err) => write!(f, "{}", err),
+            StoreError::Serde(err) => write!(f, "{}.", err),
+            StoreError::UnsupportedFormat(version) => write!(f, "Database format {} is newer than this version supports.", version),
+            StoreError::RocksDB(err) => write!(f, "Unexpected error: {}", err),
+            StoreError::Io(err) => write!(f, "IO error: {}", err),
+        }
+    }
+}
+
+impl From<rocksdb::Error> for StoreError {
+    fn from(err: rocksdb::Error) -> StoreError {
+        StoreError::RocksDB(err)
+    }
+}
+
+impl From<io::Error> for StoreError {
+    fn from(err: io::Error) -> StoreError {
+        StoreError::Io(err)
+    }
+}
+
+impl From<InvalidTSON> for StoreError {
+    fn from(err: InvalidTSON) -> StoreError {
+        StoreError::InvalidTSON(err)
+    }
+}
+
+impl From<OutputError> for StoreError {
+    fn from(err: OutputError) -> StoreError {
+        StoreError::Output(err)
+    }
+}
+
+impl From<SerdeError> for StoreError {
+    fn from(err: SerdeError) -> StoreError {
+        StoreError::Serde(err)
+    }
+}
@@ -0,0 +1,103 @@
+use rocksdb::{DB, IteratorMode, Direction};
+use crate::internal::store::{key_controls, StoreError};
+use crate::internal::store::catalog::metadata_key;
+use crate::internal::parser::tson_validator::{validate_document, InvalidTSON};
+use crate::internal::parser::compact::{self, Dictionary};
+use crate::internal::parser::legacy_tson;
+
+/// Every stored value starts with the version of its format. Values written
+/// before versioning start straight with `OBJECT_BEGIN`, which is 0 and so
+/// reads as the legacy version. Version 1 is plain TSON, both in the legacy
+/// layout with pair and separator bytes. Version 2 is compact TSON with keys
+/// from the collection dictionary, if it has one.
+pub const LEGACY: u8 = 0;
+pub const PLAIN: u8 = 1;
+pub const COMPACT: u8 = 2;
+pub const CURRENT: u8 = COMPACT;
+
+/// `None` for an empty value, which no version writes.
+pub fn version(value: &[u8]) -> Option<u8> {
+    value.first().copied()
+}
+
+pub fn encode(value: &[u8], dictionary: Option<&Dictionary>) -> Vec<u8> {
+    let mut encoded = vec![CURRENT];
+    encoded.extend(compact::compress(value, dictionary));
+    encoded
+}
+
+/// Reads a stored value back into TSON. Strings aren't checked, `validate`
+/// does that.
+pub fn decode(value: &[u8], dictionary: Option<&Dictionary>) -> Result<Vec<u8>, InvalidTSON> {
+    match version(value) {
+        Some(LEGACY) => legacy_tson::convert(value),
+        Some(PLAIN) => legacy_tson::convert(&value[1..]).map_err(|err| shift(err, 1)),
+        Some(COMPACT) => compact::expand(&value[1..], dictionary).map_err(|err| shift(err, 1)),
+        Some(_) => Err(InvalidTSON { offset: 0, reason: "unknown format version" }),
+        None => Err(InvalidTSON { offset: 0, reason: "unexpected end of value" }),
+    }
+}
+
+/// Validates a stored value, version included.
+pub fn validate(value: &[u8], dictionary: Option<&Dictionary>) -> Result<(), InvalidTSON> {
+    // Offsets past decoding are in the decoded TSON, not the stored value.
+    validate_document(&decode(value, dictionary)?)
+}
+
+fn shift(err: InvalidTSON, offset: usize) -> InvalidTSON {
+    InvalidTSON { offset: err.offset + offset, reason: err.reason }
+}
+
+/// The database marker holds the version values are written in, and the
+/// oldest version values may still be stored in.
+pub struct Marker {
+    pub written: u8,
+    pub oldest: u8,
+}
+
+impl Marker {
+    pub fn read(db: &DB) -> Result<Option<Marker>, StoreError> {
+        match db.get(metadata_key(key_controls::FORMAT))?.as_deref() {
+            Some(&[written, oldest]) => Ok(Some(Marker { written, oldest })),
+            Some(_) => Err(StoreError::CorruptMetadata("the format marker isn't 2 bytes".to_string())),
+            None => Ok(None),
+        }
+    }
+    pub fn to_bytes(&self) -> [u8; 2] {
+        [self.written, self.oldest]
+    }
+}
+
+/// Fails on databases written in a newer format than this version reads.
+pub fn check(db: &DB) -> Result<(), StoreError> {
+    match Marker::read(db)? {
+        Some(marker) if marker.written > CURRENT => Err(StoreError::UnsupportedFormat(marker.written)),
+        _ => Ok(()),
+    }
+}
+
+/// Marks databases opened for writing by this version. Ones that had
+/// collections before the marker existed may hold legacy values.
+pub fn mark(db: &DB) -> Result<(), StoreError> {
+    check(db)?;
+
+    let oldest = match Marker::read(db)? {
+        Some(marker) if marker.written == CURRENT => return Ok(()),
+        Some(marker) => marker.oldest,
+        None if has_collections(db) => LEGACY,
+        None => CURRENT,
+    };
+
+    let marker = Marker { written: CURRENT, oldest };
+    db.put(metadata_key(key_controls::FORMAT), marker.to_bytes())?;
+
+    Ok(())
+}
+
+fn has_collections(db: &DB) -> bool {
+    let prefix = metadata_key(key_controls::CATALOG);
+
+    let first = db.iterator(IteratorMode::From(&prefix, Direction::Forward)).next();
+
+    matches!(first, Some((key, _)) if key.starts_with(&prefix))
+}
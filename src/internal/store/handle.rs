@@ -0,0 +1,88 @@
+use rocksdb::{DB, Options, WriteOptions, WriteBatch};
+use std::ops::{Deref, DerefMut};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use crate::internal::store::StoreError;
+
+/// The opened database, shared by `Database` and every `Collection` made
+/// from it. Column families can only be created or dropped through `write`.
+/// Once closed, every access fails with `StoreError::Closed`.
+pub struct Handle {
+    db: RwLock<Option<DB>>,
+    options: Options,
+    write_options: WriteOptions,
+}
+
+pub struct ReadGuard<'a>(RwLockReadGuard<'a, Option<DB>>);
+pub struct WriteGuard<'a>(RwLockWriteGuard<'a, Option<DB>>);
+
+impl Handle {
+    pub fn new(db: DB, options: Options, write_options: WriteOptions) -> Handle {
+        Handle {
+            db: RwLock::new(Some(db)),
+            options,
+            write_options,
+        }
+    }
+}
+
+impl Handle {
+    pub fn read(&self) -> Result<ReadGuard<'_>, StoreError> {
+        let guard = self.db.read().unwrap();
+
+        match *guard {
+            Some(_) => Ok(ReadGuard(guard)),
+            None => Err(StoreError::Closed),
+        }
+    }
+    pub fn write(&self) -> Result<WriteGuard<'_>, StoreError> {
+        let guard = self.db.write().unwrap();
+
+        match *guard {
+            Some(_) => Ok(WriteGuard(guard)),
+            None => Err(StoreError::Closed),
+        }
+    }
+    pub fn commit(&self, db: &DB, batch: WriteBatch) -> Result<(), StoreError> {
+        Ok(db.write_opt(batch, &self.write_options)?)
+    }
+    pub fn create_cf(&self, db: &mut DB, name: &str) -> Result<(), StoreError> {
+        Ok(db.create_cf(name, &self.options)?)
+    }
+    /// Flushes every column family and drops the database, releasing its lock
+    /// file. Closing an already closed handle does nothing.
+    pub fn close(&self) -> Result<(), StoreError> {
+        let mut guard = self.db.write().unwrap();
+
+        if let Some(db) = guard.as_ref() {
+            for name in DB::list_cf(&self.options, db.path())?.iter() {
+                if let Some(cf) = db.cf_handle(name) {
+                    db.flush_cf(cf)?;
+                }
+            }
+        }
+
+        *guard = None;
+
+        Ok(())
+    }
+}
+
+impl Deref for ReadGuard<'_> {
+    type Target = DB;
+    fn deref(&self) -> &DB {
+        self.0.as_ref().unwrap()
+    }
+}
+
+impl Deref for WriteGuard<'_> {
+    type Target = DB;
+    fn deref(&self) -> &DB {
+        self.0.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for WriteGuard<'_> {
+    fn deref_mut(&mut self) -> &mut DB {
+        self.0.as_mut().unwrap()
+    }
+}
@@ -0,0 +1,345 @@
+use rocksdb::{DB, WriteBatch, IteratorMode, Direction, Options, DEFAULT_COLUMN_FAMILY_NAME};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::convert::TryInto;
+use crate::internal::byte_helper::concat_bytes;
+use crate::internal::store::{key_controls, Catalog, CollectionInfo, CollectionOptions, StoreError};
+use crate::internal::store::catalog::{metadata_key, values_cf, index_cf};
+use crate::internal::parser::{legacy_tson, tson_delimiters};
+
+const PREFIXED_IDS: u8 = 1;
+const COLUMN_FAMILIES: u8 = 2;
+const FLAT_TSON: u8 = 3;
+
+const BATCH_SIZE: usize = 1000;
+
+/// Brings databases written by older versions up to the current layout, one
+/// step at a time. Steps move keys in batches, each committed with the
+/// progress of the step, so an interrupted step goes on from its last batch.
+pub fn migrate(db: &mut DB, options: &Options) -> Result<(), StoreError> {
+    let layout = match db.get(metadata_key(key_controls::LAYOUT))?.as_deref() {
+        Some(&[layout]) => layout,
+        Some(_) => return Err(StoreError::CorruptMetadata("the layout marker isn't 1 byte".to_string())),
+        None => 0,
+    };
+
+    if layout < PREFIXED_IDS {
+        migrate_to_prefixed_ids(db)?;
+    }
+    if layout < COLUMN_FAMILIES {
+        migrate_to_column_families(db, options)?;
+    }
+    if layout < FLAT_TSON {
+        migrate_to_flat_tson(db, layout >= PREFIXED_IDS)?;
+    }
+
+    Ok(())
+}
+
+/// Moves databases written before collection ids were introduced, where keys
+/// were prefixed by the collection name, over to the id prefixed layout. The
+/// catalog is rewritten first, along with the progress, then keys are moved.
+fn migrate_to_prefixed_ids(db: &DB) -> Result<(), StoreError> {
+    let from = match Progress::read(db, PREFIXED_IDS)? {
+        Some(progress) => progress.key,
+        None => {
+            register_prefixed_collections(db)?;
+            Vec::new()
+        },
+    };
+
+    let ids: HashMap<String, u32> = Catalog::read_all(db)?
+        .into_iter()
+        .map(|info| (info.name, info.id))
+        .collect();
+
+    // Moved keys start with their id, and never with a registered name.
+    migrate_keys(db, PREFIXED_IDS, DEFAULT_COLUMN_FAMILY_NAME, from, |batch, key, value| {
+        if let Some((name, rest)) = split_name(key) {
+            if let Some(id) = ids.get(&name) {
+                batch.delete(key);
+                batch.put(concat_bytes(vec![&id.to_be_bytes()[..], rest]), value);
+            }
+        }
+        Ok(())
+    })?;
+
+    finish(db, PREFIXED_IDS)
+}
+
+/// Gives every collection found in the legacy catalog or in a key prefix an
+/// id, and writes their catalog entries.
+fn register_prefixed_collections(db: &DB) -> Result<(), StoreError> {
+    let legacy_catalog = concat_bytes(vec![
+        key_controls::LEGACY_METADATA.as_bytes(),
+        key_controls::CATALOG.as_bytes(),
+    ]);
+
+    let mut batch = WriteBatch::default();
+    let mut infos: BTreeMap<String, CollectionInfo> = BTreeMap::new();
+    let mut names = BTreeSet::new();
+
+    for (key, value) in db.iterator(IteratorMode::Start) {
+        if key.starts_with(&legacy_catalog) {
+            let info = CollectionInfo::from_tson(&legacy_catalog_entry(&value)?)?;
+            infos.insert(info.name.clone(), info);
+            batch.delete(&key);
+        } else if let Some((name, _)) = split_name(&key) {
+            names.insert(name);
+        }
+    }
+
+    for name in names.into_iter() {
+        if !infos.contains_key(&name) {
+            infos.insert(name.clone(), CollectionInfo::new(0, name, &CollectionOptions::default()));
+        }
+    }
+
+    let mut next_id = key_controls::METADATA + 1;
+    for info in infos.values_mut() {
+        info.id = next_id;
+        next_id += 1;
+        Catalog::put(&mut batch, info);
+    }
+
+    Catalog::set_next_id(&mut batch, next_id);
+    Progress::new(PREFIXED_IDS, DEFAULT_COLUMN_FAMILY_NAME, Vec::new()).put(&mut batch);
+    db.write(batch)?;
+
+    Ok(())
+}
+
+/// Name and rest of a key prefixed by a collection name.
+fn split_name(key: &[u8]) -> Option<(String, &[u8])> {
+    let separator = key_controls::NS_BEGIN.as_bytes();
+    let at = key.windows(separator.len()).position(|window| window == separator)?;
+
+    Some((String::from_utf8_lossy(&key[..at]).into_owned(), &key[at + separator.len()..]))
+}
+
+/// Moves id prefixed keys out of the default column family into the column
+/// families of their collections and indexes. Column families can't be
+/// created while keys are moved, so they're all created first.
+fn migrate_to_column_families(db: &mut DB, options: &Options) -> Result<(), StoreError> {
+    let from = Progress::read(db, COLUMN_FAMILIES)?
+        .map(|progress| progress.key)
+        .unwrap_or_default();
+
+    let mut column_families = BTreeSet::new();
+    for (key, _) in db.iterator(IteratorMode::From(&from, Direction::Forward)) {
+        if let Some((column_family, _)) = column_family_key(&key) {
+            column_families.insert(column_family);
+        }
+    }
+
+    for column_family in column_families.iter() {
+        if db.cf_handle(column_family).is_none() {
+            db.create_cf(column_family, options)?;
+        }
+    }
+
+    let db = &*db;
+    migrate_keys(db, COLUMN_FAMILIES, DEFAULT_COLUMN_FAMILY_NAME, from, |batch, key, value| {
+        if let Some((column_family, new_key)) = column_family_key(key) {
+            batch.delete(key);
+            batch.put_cf(db.cf_handle(&column_family).unwrap(), new_key, value);
+        }
+        Ok(())
+    })?;
+
+    finish(db, COLUMN_FAMILIES)
+}
+
+/// Column family and key an id prefixed key moves to.
+fn column_family_key(key: &[u8]) -> Option<(String, Vec<u8>)> {
+    if key.len() < 5 || key.starts_with(&key_controls::METADATA.to_be_bytes()) {
+        return None;
+    }
+
+    let id = u32::from_be_bytes(key[..4].try_into().unwrap());
+    let rest = &key[5..];
+
+    match &key[4..5] {
+        control if control == key_controls::VALUES.as_bytes() => Some((values_cf(id), rest.to_vec())),
+        control if control == key_controls::INDEX.as_bytes() => {
+            let separator = key_controls::NS_BEGIN.as_bytes();
+            let at = rest.windows(separator.len()).position(|window| window == separator)?;
+            let index = String::from_utf8_lossy(&rest[..at]).into_owned();
+            Some((index_cf(id, &index), rest[at + separator.len()..].to_vec()))
+        },
+        _ => None,
+    }
+}
+
+/// Drops the pair and separator bytes from TSON kept outside of values, in
+/// catalog entries and in index entries of objects and arrays. Values are
+/// converted as they're read, see `format`. The catalog is rewritten first,
+/// along with the progress, then index column families are migrated in name
+/// order. `legacy_catalog` is false when `migrate_to_prefixed_ids` just
+/// rewrote the catalog in the current layout.
+fn migrate_to_flat_tson(db: &DB, legacy_catalog: bool) -> Result<(), StoreError> {
+    let progress = match Progress::read(db, FLAT_TSON)? {
+        Some(progress) => progress,
+        None => flatten_catalog(db, legacy_catalog)?,
+    };
+
+    let mut column_families = Vec::new();
+    for info in Catalog::read_all(db)? {
+        column_families.extend(info.indexes.iter().map(|index| index_cf(info.id, index)));
+    }
+    column_families.sort();
+
+    for column_family in column_families.iter().filter(|name| **name >= progress.column_family) {
+        let cf = match db.cf_handle(column_family) {
+            Some(cf) => cf,
+            None => continue,
+        };
+
+        let from = match *column_family == progress.column_family {
+            true => progress.key.clone(),
+            false => Vec::new(),
+        };
+
+        // A key already converted converts to itself, or not at all.
+        migrate_keys(db, FLAT_TSON, column_family, from, |batch, key, _| {
+            if let Some(converted) = flat_index_key(key) {
+                batch.delete_cf(cf, key);
+                batch.put_cf(cf, converted, b"");
+            }
+            Ok(())
+        })?;
+    }
+
+    finish(db, FLAT_TSON)
+}
+
+/// Rewrites every catalog entry in the current layout, and returns the
+/// progress written with them.
+fn flatten_catalog(db: &DB, legacy_catalog: bool) -> Result<Progress, StoreError> {
+    let prefix = metadata_key(key_controls::CATALOG);
+    let mut batch = WriteBatch::default();
+
+    for (key, value) in db.iterator(IteratorMode::From(&prefix, Direction::Forward)) {
+        if !key.starts_with(&prefix) {
+            break;
+        }
+
+        let info = match legacy_catalog {
+            true => CollectionInfo::from_tson(&legacy_catalog_entry(&value)?)?,
+            false => CollectionInfo::from_tson(&value)?,
+        };
+        Catalog::put(&mut batch, &info);
+    }
+
+    let progress = Progress::new(FLAT_TSON, "", Vec::new());
+    progress.put(&mut batch);
+    db.write(batch)?;
+
+    Ok(progress)
+}
+
+fn legacy_catalog_entry(value: &[u8]) -> Result<Vec<u8>, StoreError> {
+    legacy_tson::convert(value).map_err(|err| {
+        StoreError::CorruptMetadata(format!("{} in a legacy catalog entry at byte {}", err.reason, err.offset))
+    })
+}
+
+/// Index keys are the indexed value followed by the id. Only objects and
+/// arrays changed layout, malformed keys are left as they are.
+fn flat_index_key(key: &[u8]) -> Option<Vec<u8>> {
+    match key.first() {
+        Some(&tson_delimiters::OBJECT_BEGIN) | Some(&tson_delimiters::ARRAY_BEGIN) => {
+            let len = u32::from_le_bytes(key.get(1..5)?.try_into().unwrap()) as usize + 6;
+            let value = legacy_tson::convert(key.get(..len)?).ok()?;
+            Some(concat_bytes(vec![value.as_slice(), &key[len..]]))
+        },
+        _ => None,
+    }
+}
+
+/// Where a step stopped, the last key it migrated in the column family it was
+/// migrating. Only kept while a step is running.
+struct Progress {
+    step: u8,
+    column_family: String,
+    key: Vec<u8>,
+}
+
+impl Progress {
+    fn new(step: u8, column_family: &str, key: Vec<u8>) -> Progress {
+        Progress { step, column_family: column_family.to_string(), key }
+    }
+    /// Progress of an interrupted run of `step`, if there was one.
+    fn read(db: &DB, step: u8) -> Result<Option<Progress>, StoreError> {
+        let value = match db.get(metadata_key(key_controls::MIGRATION))? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+
+        let progress = Progress::from_bytes(&value)
+            .ok_or_else(|| StoreError::CorruptMetadata("unexpected migration progress".to_string()))?;
+
+        Ok(Some(progress).filter(|progress| progress.step == step))
+    }
+    fn put(&self, batch: &mut WriteBatch) {
+        let name = self.column_family.as_bytes();
+
+        batch.put(metadata_key(key_controls::MIGRATION), concat_bytes(vec![
+            &[self.step][..],
+            &(name.len() as u32).to_be_bytes(),
+            name,
+            &self.key,
+        ]));
+    }
+    fn from_bytes(value: &[u8]) -> Option<Progress> {
+        let step = *value.first()?;
+        let len = u32::from_be_bytes(value.get(1..5)?.try_into().unwrap()) as usize;
+        let column_family = String::from_utf8(value.get(5..5 + len)?.to_vec()).ok()?;
+
+        Some(Progress { step, column_family, key: value[5 + len..].to_vec() })
+    }
+}
+
+/// Calls `migrate` on every key of `column_family` after `from`, committing
+/// each batch along with the last key it covered. `migrate` moves a key by
+/// deleting it and writing its new key in the same batch.
+fn migrate_keys<F>(db: &DB, step: u8, column_family: &str, mut from: Vec<u8>, mut migrate: F) -> Result<(), StoreError>
+    where
+        F: FnMut(&mut WriteBatch, &[u8], &[u8]) -> Result<(), StoreError>,
+{
+    let cf = match db.cf_handle(column_family) {
+        Some(cf) => cf,
+        None => return Ok(()),
+    };
+
+    loop {
+        let mut batch = WriteBatch::default();
+        let mut last = None;
+
+        let entries = db.iterator_cf(cf, IteratorMode::From(&from, Direction::Forward))
+            .filter(|(key, _)| from.is_empty() || **key != *from)
+            .take(BATCH_SIZE);
+
+        for (key, value) in entries {
+            migrate(&mut batch, &key, &value)?;
+            last = Some(key);
+        }
+
+        from = match last {
+            Some(key) => key.into_vec(),
+            None => return Ok(()),
+        };
+
+        Progress::new(step, column_family, from.clone()).put(&mut batch);
+        db.write(batch)?;
+    }
+}
+
+/// Marks `step` as done and forgets its progress.
+fn finish(db: &DB, step: u8) -> Result<(), StoreError> {
+    let mut batch = WriteBatch::default();
+    batch.delete(metadata_key(key_controls::MIGRATION));
+    batch.put(metadata_key(key_controls::LAYOUT), [step]);
+    db.write(batch)?;
+
+    Ok(())
+}
@@ -1,5 +1,19 @@
 pub mod database;
 pub mod collection;
+pub mod error;
+pub mod catalog;
+pub mod migration;
+pub mod options;
+pub mod handle;
+pub mod backup;
+pub mod format;
+pub mod dictionary;
 
 pub use database::{ Database, key_controls };
-pub use collection::Collection;
+pub use collection::{ Collection, CollectionStats, CorruptDocument, WriteMode, IdMode, ReturnDocument };
+pub use error::StoreError;
+pub use catalog::{ Catalog, CollectionInfo };
+pub use options::{ DatabaseOptions, CollectionOptions, Compression, WalSync };
+pub use dictionary::KeyDictionary;
+pub use handle::Handle;
+pub use backup::BackupInfo;
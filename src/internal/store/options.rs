@@ -0,0 +1,120 @@
+use rocksdb::{Options, WriteOptions, BlockBasedOptions, DBCompressionType};
+use crate::internal::parser::{TSONValue, tson_delimiters};
+use crate::internal::parser::tson_value::{object_members, write_object};
+
+pub enum Compression {
+    None,
+    Lz4,
+    Zstd,
+}
+
+/// How writes reach the write ahead log.
+pub enum WalSync {
+    /// Every write is synced to disk before returning.
+    Sync,
+    /// Writes are left to the OS buffers, a crash of the machine may lose the last ones.
+    Buffered,
+    /// No write ahead log, anything not flushed is lost on a crash.
+    Disabled,
+}
+
+pub struct DatabaseOptions {
+    pub create_if_missing: bool,
+    pub read_only: bool,
+    /// RocksDB's default compression if not set.
+    pub compression: Option<Compression>,
+    pub block_cache_size: Option<usize>,
+    pub write_buffer_size: Option<usize>,
+    pub max_open_files: Option<i32>,
+    pub bloom_filter_bits: Option<i32>,
+    pub wal_sync: WalSync,
+}
+
+impl Default for DatabaseOptions {
+    fn default() -> DatabaseOptions {
+        DatabaseOptions {
+            create_if_missing: true,
+            read_only: false,
+            compression: None,
+            block_cache_size: None,
+            write_buffer_size: None,
+            max_open_files: None,
+            bloom_filter_bits: None,
+            wal_sync: WalSync::Buffered,
+        }
+    }
+}
+
+impl DatabaseOptions {
+    /// Options for the database and every column family in it.
+    pub fn to_options(&self) -> Options {
+        let mut options = Options::default();
+        options.create_if_missing(self.create_if_missing);
+
+        if let Some(compression) = &self.compression {
+            options.set_compression_type(match compression {
+                Compression::None => DBCompressionType::None,
+                Compression::Lz4 => DBCompressionType::Lz4,
+                Compression::Zstd => DBCompressionType::Zstd,
+            });
+        }
+
+        if let Some(size) = self.write_buffer_size {
+            options.set_write_buffer_size(size);
+        }
+        if let Some(files) = self.max_open_files {
+            options.set_max_open_files(files);
+        }
+
+        if self.block_cache_size.is_some() || self.bloom_filter_bits.is_some() {
+            let mut table = BlockBasedOptions::default();
+            if let Some(size) = self.block_cache_size {
+                table.set_lru_cache(size);
+            }
+            if let Some(bits) = self.bloom_filter_bits {
+                table.set_bloom_filter(bits, false);
+            }
+            options.set_block_based_table_factory(&table);
+        }
+
+        options
+    }
+    pub fn to_write_options(&self) -> WriteOptions {
+        let mut options = WriteOptions::default();
+
+        match self.wal_sync {
+            WalSync::Sync => options.set_sync(true),
+            WalSync::Buffered => options.set_sync(false),
+            WalSync::Disabled => options.disable_wal(true),
+        }
+
+        options
+    }
+}
+
+/// Options a collection is created with, kept in its catalog entry.
+#[derive(Default)]
+pub struct CollectionOptions {
+    /// Stores field names once in a dictionary, documents refer to them by id.
+    pub key_dictionary: bool,
+}
+
+impl CollectionOptions {
+    pub fn to_tson(&self) -> Vec<u8> {
+        let key_dictionary = if self.key_dictionary { tson_delimiters::TRUE } else { tson_delimiters::FALSE };
+
+        write_object(&[("keyDictionary", [key_dictionary])])
+    }
+    /// Options missing from `tson` keep their default.
+    pub fn from_tson(tson: &[u8]) -> CollectionOptions {
+        let mut options = CollectionOptions::default();
+
+        for (key, value) in object_members(tson) {
+            if let (b"keyDictionary", TSONValue::True) = (key, TSONValue::read(value)) {
+                options.key_dictionary = true;
+            }
+        }
+
+        options
+    }
+}
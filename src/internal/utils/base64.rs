@@ -0,0 +1,62 @@
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const PAD: u8 = b'=';
+
+/// Standard base64 with padding.
+pub fn encode(bytes: &[u8]) -> String {
+    let mut encoded = Vec::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+
+        encoded.push(ALPHABET[(n >> 18) as usize & 63]);
+        encoded.push(ALPHABET[(n >> 12) as usize & 63]);
+        encoded.push(if chunk.len() > 1 { ALPHABET[(n >> 6) as usize & 63] } else { PAD });
+        encoded.push(if chunk.len() > 2 { ALPHABET[n as usize & 63] } else { PAD });
+    }
+
+    String::from_utf8(encoded).unwrap()
+}
+
+/// Decodes standard base64, padding is optional. `None` on anything else.
+pub fn decode(encoded: &[u8]) -> Option<Vec<u8>> {
+    let encoded = match encoded.iter().position(|&c| c == PAD) {
+        Some(at) if encoded[at..].iter().all(|&c| c == PAD) && encoded[at..].len() <= 2 => &encoded[..at],
+        Some(_) => return None,
+        None => encoded,
+    };
+
+    if encoded.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut decoded = Vec::with_capacity(encoded.len() * 3 / 4);
+
+    for chunk in encoded.chunks(4) {
+        let mut n = 0u32;
+        for (i, &c) in chunk.iter().enumerate() {
+            n |= (sextet(c)? as u32) << (18 - 6 * i);
+        }
+
+        decoded.push((n >> 16) as u8);
+        if chunk.len() > 2 {
+            decoded.push((n >> 8) as u8);
+        }
+        if chunk.len() > 3 {
+            decoded.push(n as u8);
+        }
+    }
+
+    Some(decoded)
+}
+
+fn sextet(c: u8) -> Option<u8> {
+    match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
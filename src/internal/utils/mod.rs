@@ -1 +1,2 @@
-pub mod byte_helper;
\ No newline at end of file
+pub mod byte_helper;
+pub mod base64;
\ No newline at end of file
@@ -1,19 +1,62 @@
 pub mod internal;
+#[cfg(feature = "node")]
 pub mod callers;
 
-pub type Cx<'a> = FunctionContext<'a>;
+pub use internal::store::{
+    Database,
+    DatabaseOptions,
+    Collection,
+    CollectionOptions,
+    CollectionInfo,
+    WriteMode,
+    IdMode,
+    ReturnDocument,
+    StoreError,
+};
+pub use internal::query::{Query, Update};
+pub use internal::parser::{Parser, JSONParser, TSONParser, OutputOptions, ParseError, to_tson, from_tson};
 
+#[cfg(feature = "node")]
+pub type Cx<'a> = neon::prelude::FunctionContext<'a>;
+
+#[cfg(feature = "node")]
 use neon::prelude::*;
+#[cfg(feature = "node")]
 use callers::*;
 
+#[cfg(feature = "node")]
 #[neon::main]
 fn main(mut cx: ModuleContext) -> NeonResult<()> {
     cx.export_function("databaseNew", DatabaseWrapper::js_new)?;
+    cx.export_function("databaseOpenReadOnly", DatabaseWrapper::js_open_read_only)?;
+    cx.export_function("databaseOpenAsSecondary", DatabaseWrapper::js_open_as_secondary)?;
+    cx.export_function("databaseCatchUp", DatabaseWrapper::js_catch_up)?;
     cx.export_function("databaseCollection", DatabaseWrapper::js_collection)?;
+    cx.export_function("databaseListCollections", DatabaseWrapper::js_list_collections)?;
+    cx.export_function("databaseDropCollection", DatabaseWrapper::js_drop_collection)?;
+    cx.export_function("databaseRenameCollection", DatabaseWrapper::js_rename_collection)?;
+    cx.export_function("databaseCreateIndex", DatabaseWrapper::js_create_index)?;
+    cx.export_function("databaseCollectionStats", DatabaseWrapper::js_collection_stats)?;
+    cx.export_function("databaseUpgradeFormat", DatabaseWrapper::js_upgrade_format)?;
+    cx.export_function("databaseVerify", DatabaseWrapper::js_verify)?;
+    cx.export_function("databaseBackup", DatabaseWrapper::js_backup)?;
+    cx.export_function("databaseListBackups", DatabaseWrapper::js_list_backups)?;
+    cx.export_function("databaseRestoreBackup", DatabaseWrapper::js_restore_backup)?;
+    cx.export_function("databasePurgeOldBackups", DatabaseWrapper::js_purge_old_backups)?;
+    cx.export_function("databaseCreateCheckpoint", DatabaseWrapper::js_create_checkpoint)?;
+    cx.export_function("databaseClose", DatabaseWrapper::js_close)?;
+    cx.export_function("databaseDestroy", DatabaseWrapper::js_destroy)?;
 
     cx.export_function("collectionGetName", CollectionWrapper::js_get_name)?;
     cx.export_function("collectionInsert", CollectionWrapper::js_insert)?;
+    cx.export_function("collectionReplace", CollectionWrapper::js_replace)?;
+    cx.export_function("collectionUpsert", CollectionWrapper::js_upsert)?;
     cx.export_function("collectionQuery", CollectionWrapper::js_query)?;
+    cx.export_function("collectionDeleteMany", CollectionWrapper::js_delete_many)?;
+    cx.export_function("collectionFindOneAndUpdate", CollectionWrapper::js_find_one_and_update)?;
+    cx.export_function("collectionFindOneAndReplace", CollectionWrapper::js_find_one_and_replace)?;
+    cx.export_function("collectionExportJsonl", CollectionWrapper::js_export_jsonl)?;
+    cx.export_function("collectionImportJsonl", CollectionWrapper::js_import_jsonl)?;
 
     Ok(())
 }
@@ -0,0 +1,67 @@
+mod common;
+
+use common::{open, tson};
+use test_db::{Database, StoreError};
+use tempfile::tempdir;
+use std::path::Path;
+
+fn path(path: &Path) -> &str {
+    path.to_str().unwrap()
+}
+
+#[test]
+fn restores_any_backup() {
+    let dir = tempdir().unwrap();
+    let backups = tempdir().unwrap();
+    let restored = tempdir().unwrap();
+
+    let db = open(dir.path());
+    let users = db.collection("users".to_string()).unwrap();
+    users.insert("a", tson("{}")).unwrap();
+    db.backup(path(backups.path())).unwrap();
+    users.insert("b", tson("{}")).unwrap();
+    db.backup(path(backups.path())).unwrap();
+
+    let ids: Vec<u32> = Database::list_backups(path(backups.path())).unwrap().into_iter().map(|info| info.id).collect();
+    assert_eq!(ids, vec![1, 2]);
+
+    Database::restore_backup(path(backups.path()), 1, path(restored.path())).unwrap();
+    let older = open(restored.path());
+    assert_eq!(older.collection_stats("users").unwrap().count, 1);
+    older.close().unwrap();
+
+    Database::restore_backup(path(backups.path()), 2, path(restored.path())).unwrap();
+    assert_eq!(open(restored.path()).collection_stats("users").unwrap().count, 2);
+
+    let missing = Database::restore_backup(path(backups.path()), 7, path(restored.path()));
+    assert!(matches!(missing, Err(StoreError::BackupNotFound(7))));
+}
+
+#[test]
+fn purges_all_but_the_newest_backups() {
+    let dir = tempdir().unwrap();
+    let backups = tempdir().unwrap();
+
+    let db = open(dir.path());
+    for _ in 0..3 {
+        db.backup(path(backups.path())).unwrap();
+    }
+    Database::purge_old_backups(path(backups.path()), 1).unwrap();
+
+    let ids: Vec<u32> = Database::list_backups(path(backups.path())).unwrap().into_iter().map(|info| info.id).collect();
+    assert_eq!(ids, vec![3]);
+}
+
+#[test]
+fn checkpoints_open_as_databases() {
+    let dir = tempdir().unwrap();
+    let checkpoints = tempdir().unwrap();
+    let checkpoint = checkpoints.path().join("checkpoint");
+
+    let db = open(dir.path());
+    db.collection("users".to_string()).unwrap().insert("a", tson("{}")).unwrap();
+    db.create_checkpoint(path(&checkpoint)).unwrap();
+
+    let copy = open(&checkpoint);
+    assert!(copy.collection("users".to_string()).unwrap().get("a").unwrap().is_some());
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use common::{open, tson};
+use test_db::Database;
+use test_db::StoreError;
+use tempfile::tempdir;
+use std::sync::Arc;
+use std::thread;
+
+#[test]
+fn gives_concurrently_created_collections_one_id_each() {
+    let dir = tempdir().unwrap();
+    let db = Arc::new(open(dir.path()));
+
+    let threads: Vec<_> = (0..8).map(|i| {
+        let db = Arc::clone(&db);
+        thread::spawn(move || {
+            let collection = db.collection(format!("c{}", i % 4)).unwrap();
+            collection.insert(i.to_string(), tson("{}")).unwrap();
+            collection.id()
+        })
+    }).collect();
+
+    let mut ids: Vec<u32> = threads.into_iter().map(|thread| thread.join().unwrap()).collect();
+    ids.sort_unstable();
+    ids.dedup();
+    assert_eq!(ids.len(), 4);
+
+    let infos = db.list_collections().unwrap();
+    assert_eq!(infos.len(), 4);
+    for info in infos {
+        assert_eq!(db.collection_stats(&info.name).unwrap().count, 2);
+    }
+}
+
+#[test]
+fn rejects_names_that_could_collide() {
+    let dir = tempdir().unwrap();
+    let db = open(dir.path());
+
+    for name in ["", "$cmd", "a\u{10F41F}b"].iter() {
+        assert!(matches!(db.collection(name.to_string()), Err(StoreError::InvalidName(..))));
+    }
+    assert!(db.list_collections().unwrap().is_empty());
+}
+
+#[test]
+fn fails_with_closed_after_closing() {
+    let dir = tempdir().unwrap();
+    let db = open(dir.path());
+    let users = db.collection("users".to_string()).unwrap();
+
+    db.close().unwrap();
+
+    assert!(matches!(users.insert("a", tson("{}")), Err(StoreError::Closed)));
+    assert!(matches!(db.collection("users".to_string()), Err(StoreError::Closed)));
+    assert!(matches!(db.list_collections(), Err(StoreError::Closed)));
+}
+
+#[test]
+fn destroys_closed_databases() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap().to_string();
+
+    let db = open(dir.path());
+    db.collection("users".to_string()).unwrap().insert("a", tson("{}")).unwrap();
+    db.close().unwrap();
+    Database::destroy(path).unwrap();
+
+    let db = open(dir.path());
+    assert!(db.list_collections().unwrap().is_empty());
+}
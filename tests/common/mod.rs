@@ -0,0 +1,33 @@
+#![allow(dead_code)]
+
+use test_db::{Database, DatabaseOptions, JSONParser, Parser, TSONParser};
+use rocksdb::{DB, Options, IteratorMode, DEFAULT_COLUMN_FAMILY_NAME};
+use std::path::Path;
+
+pub fn open(path: &Path) -> Database {
+    Database::new(path.to_str().unwrap().to_string(), DatabaseOptions::default()).unwrap()
+}
+
+pub fn tson(json: &str) -> Vec<u8> {
+    JSONParser::new(json.to_string()).parse().unwrap()
+}
+
+pub fn json(tson: Vec<u8>) -> String {
+    String::from_utf8(TSONParser::new(tson).parse().unwrap()).unwrap()
+}
+
+/// Opens the database underneath, for writing layouts older versions wrote
+/// and reading back what newer ones did.
+pub fn open_raw(path: &Path) -> DB {
+    let column_families = DB::list_cf(&Options::default(), path)
+        .unwrap_or_else(|_| vec![DEFAULT_COLUMN_FAMILY_NAME.to_string()]);
+    let mut options = Options::default();
+    options.create_if_missing(true);
+    DB::open_cf(&options, path, column_families).unwrap()
+}
+
+pub fn keys(db: &DB, column_family: &str) -> Vec<Vec<u8>> {
+    db.iterator_cf(db.cf_handle(column_family).unwrap(), IteratorMode::Start)
+        .map(|(key, _)| key.into_vec())
+        .collect()
+}
@@ -0,0 +1,126 @@
+mod common;
+
+use common::{open, tson, json};
+use test_db::{Query, Update, ReturnDocument, StoreError, WriteMode};
+use tempfile::tempdir;
+use std::sync::Arc;
+use std::thread;
+
+#[test]
+fn inserts_only_new_ids() {
+    let dir = tempdir().unwrap();
+    let db = open(dir.path());
+    let users = db.collection("users".to_string()).unwrap();
+
+    users.insert("a", tson(r#"{"_id":"a","n":1}"#)).unwrap();
+
+    assert!(matches!(users.insert("a", tson(r#"{"_id":"a","n":2}"#)), Err(StoreError::AlreadyExists(_))));
+    assert_eq!(json(users.get("a").unwrap().unwrap()), r#"{"_id":"a","n":1}"#);
+}
+
+#[test]
+fn inserts_each_id_once_across_threads() {
+    let dir = tempdir().unwrap();
+    let db = Arc::new(open(dir.path()));
+
+    let threads: Vec<_> = (0..8).map(|_| {
+        let db = Arc::clone(&db);
+        thread::spawn(move || {
+            let users = db.collection("users".to_string()).unwrap();
+            (0..50).filter(|i| users.insert(i.to_string(), tson("{}")).is_ok()).count()
+        })
+    }).collect();
+
+    let inserted: usize = threads.into_iter().map(|thread| thread.join().unwrap()).sum();
+    assert_eq!(inserted, 50);
+    assert_eq!(db.collection_stats("users").unwrap().count, 50);
+}
+
+#[test]
+fn replaces_only_existing_ids() {
+    let dir = tempdir().unwrap();
+    let db = open(dir.path());
+    let users = db.collection("users".to_string()).unwrap();
+
+    assert!(matches!(users.replace("a", tson(r#"{"_id":"a"}"#)), Err(StoreError::NotFound(_))));
+    assert!(users.get("a").unwrap().is_none());
+
+    users.insert("a", tson(r#"{"_id":"a","n":1}"#)).unwrap();
+    users.replace("a", tson(r#"{"_id":"a","n":2}"#)).unwrap();
+
+    assert_eq!(json(users.get("a").unwrap().unwrap()), r#"{"_id":"a","n":2}"#);
+}
+
+#[test]
+fn upserts_either_way() {
+    let dir = tempdir().unwrap();
+    let db = open(dir.path());
+    let users = db.collection("users".to_string()).unwrap();
+
+    users.upsert("a", tson(r#"{"_id":"a","n":1}"#)).unwrap();
+    let previous = users.write("a", tson(r#"{"_id":"a","n":2}"#), WriteMode::Upsert).unwrap();
+
+    assert_eq!(json(previous.unwrap()), r#"{"_id":"a","n":1}"#);
+    assert_eq!(json(users.get("a").unwrap().unwrap()), r#"{"_id":"a","n":2}"#);
+}
+
+#[test]
+fn updates_the_first_match() {
+    let dir = tempdir().unwrap();
+    let db = open(dir.path());
+    let users = db.collection("users".to_string()).unwrap();
+    users.insert("a", tson(r#"{"_id":"a","n":1,"tag":"x"}"#)).unwrap();
+
+    let query = Query::new(r#"{"n":1}"#.to_string()).unwrap();
+    let update = Update::new(r#"{"$set":{"n":2},"$unset":{"tag":""}}"#.to_string()).unwrap();
+    let after = users.find_one_and_update(&query, &update, ReturnDocument::After).unwrap();
+
+    assert_eq!(json(after.unwrap()), r#"{"_id":"a","n":2}"#);
+    assert!(users.find_one_and_update(&query, &update, ReturnDocument::After).unwrap().is_none());
+}
+
+#[test]
+fn rejects_invalid_tson() {
+    let dir = tempdir().unwrap();
+    let db = open(dir.path());
+    let users = db.collection("users".to_string()).unwrap();
+
+    assert!(matches!(users.insert("a", vec![0x42]), Err(StoreError::InvalidTSON(_))));
+    assert!(matches!(users.insert("a", tson("[1]")), Err(StoreError::InvalidTSON(_))));
+    assert!(users.get("a").unwrap().is_none());
+}
+
+#[test]
+fn deletes_matching_documents() {
+    let dir = tempdir().unwrap();
+    let db = open(dir.path());
+    let numbers = db.collection("numbers".to_string()).unwrap();
+
+    for i in 0..2500 {
+        let id = format!("{:04}", i);
+        numbers.insert(&id, tson(&format!(r#"{{"_id":"{}","even":{}}}"#, id, i % 2 == 0))).unwrap();
+    }
+
+    assert_eq!(numbers.delete_many(&Query::new(r#"{"even":true}"#.to_string()).unwrap()).unwrap(), 1250);
+    assert_eq!(numbers.find(&Query::All).unwrap().len(), 1250);
+    assert!(numbers.get("0000").unwrap().is_none());
+    assert!(numbers.get("0001").unwrap().is_some());
+
+    assert_eq!(numbers.delete_many(&Query::All).unwrap(), 1250);
+    assert!(numbers.find(&Query::All).unwrap().is_empty());
+    assert_eq!(numbers.delete_many(&Query::All).unwrap(), 0);
+}
+
+#[test]
+fn keeps_documents_across_reopening() {
+    let dir = tempdir().unwrap();
+
+    let db = open(dir.path());
+    db.collection("users".to_string()).unwrap().insert("a", tson(r#"{"_id":"a","d":0.1}"#)).unwrap();
+    db.close().unwrap();
+
+    let db = open(dir.path());
+    let users = db.collection("users".to_string()).unwrap();
+
+    assert_eq!(json(users.get("a").unwrap().unwrap()), r#"{"_id":"a","d":0.1}"#);
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use common::{open, open_raw, tson, json};
+use test_db::internal::store::{catalog::metadata_key, format, key_controls};
+use test_db::{Database, StoreError};
+use tempfile::tempdir;
+
+fn open_result(dir: &tempfile::TempDir) -> Result<Database, StoreError> {
+    Database::new(dir.path().to_str().unwrap().to_string(), Default::default())
+}
+
+#[test]
+fn marks_values_and_databases_with_their_format() {
+    let dir = tempdir().unwrap();
+
+    let db = open(dir.path());
+    let users = db.collection("users".to_string()).unwrap();
+    users.insert("a", tson(r#"{"_id":"a"}"#)).unwrap();
+    let id = users.id();
+    db.close().unwrap();
+
+    let raw = open_raw(dir.path());
+    assert_eq!(raw.get(metadata_key(key_controls::FORMAT)).unwrap().unwrap(), vec![format::CURRENT; 2]);
+    assert_eq!(raw.get_cf(raw.cf_handle(&id.to_string()).unwrap(), "a").unwrap().unwrap()[0], format::CURRENT);
+}
+
+#[test]
+fn reads_and_upgrades_plain_values() {
+    let dir = tempdir().unwrap();
+
+    let db = open(dir.path());
+    let users = db.collection("users".to_string()).unwrap();
+    let id = users.id();
+    db.close().unwrap();
+
+    // `{"n":1}` in the legacy layout, with a PAIR byte between key and value.
+    let key = [&[0x04][..], &1u32.to_le_bytes(), b"n"].concat();
+    let member = [key, vec![0x09, 0x05], 1f64.to_le_bytes().to_vec()].concat();
+    let object = [&[0x00][..], &(member.len() as u32).to_le_bytes(), &member, &[0x01]].concat();
+
+    let raw = open_raw(dir.path());
+    raw.put_cf(raw.cf_handle(&id.to_string()).unwrap(), "a", [&[format::PLAIN][..], &object].concat()).unwrap();
+    raw.put(metadata_key(key_controls::FORMAT), [format::CURRENT, format::PLAIN]).unwrap();
+    drop(raw);
+
+    let db = open(dir.path());
+    let users = db.collection("users".to_string()).unwrap();
+    assert_eq!(json(users.get("a").unwrap().unwrap()), r#"{"n":1}"#);
+    assert_eq!(db.upgrade_format().unwrap(), 1);
+    assert_eq!(db.upgrade_format().unwrap(), 0);
+    assert_eq!(json(users.get("a").unwrap().unwrap()), r#"{"n":1}"#);
+    db.close().unwrap();
+
+    let raw = open_raw(dir.path());
+    assert_eq!(raw.get(metadata_key(key_controls::FORMAT)).unwrap().unwrap(), vec![format::CURRENT; 2]);
+    assert_eq!(raw.get_cf(raw.cf_handle(&id.to_string()).unwrap(), "a").unwrap().unwrap()[0], format::CURRENT);
+}
+
+#[test]
+fn refuses_newer_and_corrupt_format_markers() {
+    let dir = tempdir().unwrap();
+
+    let raw = open_raw(dir.path());
+    raw.put(metadata_key(key_controls::FORMAT), [format::CURRENT + 1, format::CURRENT]).unwrap();
+    drop(raw);
+    assert!(matches!(open_result(&dir), Err(StoreError::UnsupportedFormat(version)) if version == format::CURRENT + 1));
+
+    let raw = open_raw(dir.path());
+    raw.put(metadata_key(key_controls::FORMAT), [format::CURRENT]).unwrap();
+    drop(raw);
+    assert!(matches!(open_result(&dir), Err(StoreError::CorruptMetadata(_))));
+}
@@ -0,0 +1,128 @@
+mod common;
+
+use common::{open, open_raw, keys, tson};
+use test_db::Query;
+use tempfile::tempdir;
+
+/// Index keys are the indexed TSON followed by the id.
+fn entry(value: &str, id: &str) -> Vec<u8> {
+    let document = tson(&format!(r#"{{"v":{}}}"#, value));
+    // The value of the only member, past the object header and the key.
+    [&document[5 + 6..document.len() - 1], id.as_bytes()].concat()
+}
+
+#[test]
+fn builds_indexes_of_existing_documents() {
+    let dir = tempdir().unwrap();
+
+    let db = open(dir.path());
+    let items = db.collection("items".to_string()).unwrap();
+    items.insert("a", tson(r#"{"_id":"a","v":1}"#)).unwrap();
+    items.insert("b", tson(r#"{"_id":"b"}"#)).unwrap();
+    db.create_index("items", "v".to_string()).unwrap();
+    let id = items.id();
+    db.close().unwrap();
+
+    let raw = open_raw(dir.path());
+    assert_eq!(keys(&raw, &format!("{}/v", id)), vec![entry("1", "a")]);
+}
+
+#[test]
+fn maintains_indexes_on_every_write() {
+    let dir = tempdir().unwrap();
+
+    let db = open(dir.path());
+    let items = db.collection("items".to_string()).unwrap();
+    db.create_index("items", "v".to_string()).unwrap();
+
+    items.insert("a", tson(r#"{"_id":"a","v":1}"#)).unwrap();
+    items.insert("b", tson(r#"{"_id":"b","v":"x"}"#)).unwrap();
+    items.insert("c", tson(r#"{"_id":"c","v":[1,2]}"#)).unwrap();
+    items.insert("d", tson(r#"{"_id":"d","v":true}"#)).unwrap();
+    items.replace("a", tson(r#"{"_id":"a","v":3}"#)).unwrap();
+    items.upsert("b", tson(r#"{"_id":"b"}"#)).unwrap();
+    items.upsert("e", tson(r#"{"_id":"e","v":null}"#)).unwrap();
+    items.delete_many(&Query::new(r#"{"_id":"c"}"#.to_string()).unwrap()).unwrap();
+    let id = items.id();
+    db.close().unwrap();
+
+    let raw = open_raw(dir.path());
+    let mut expected = vec![entry("3", "a"), entry("true", "d"), entry("null", "e")];
+    expected.sort();
+    assert_eq!(keys(&raw, &format!("{}/v", id)), expected);
+}
+
+#[test]
+fn handles_made_before_an_index_maintain_it() {
+    let dir = tempdir().unwrap();
+
+    let db = open(dir.path());
+    let before = db.collection("items".to_string()).unwrap();
+    db.create_index("items", "v".to_string()).unwrap();
+    before.insert("a", tson(r#"{"_id":"a","v":1}"#)).unwrap();
+    let id = before.id();
+    db.close().unwrap();
+
+    let raw = open_raw(dir.path());
+    assert_eq!(keys(&raw, &format!("{}/v", id)), vec![entry("1", "a")]);
+}
+
+#[test]
+fn clears_indexes_with_their_documents() {
+    let dir = tempdir().unwrap();
+
+    let db = open(dir.path());
+    let items = db.collection("items".to_string()).unwrap();
+    db.create_index("items", "v".to_string()).unwrap();
+    for i in 0..10 {
+        items.insert(i.to_string(), tson(&format!(r#"{{"v":{}}}"#, i))).unwrap();
+    }
+    assert_eq!(items.delete_many(&Query::All).unwrap(), 10);
+    items.insert("a", tson(r#"{"v":1}"#)).unwrap();
+    let id = items.id();
+    db.close().unwrap();
+
+    let raw = open_raw(dir.path());
+    assert_eq!(keys(&raw, &format!("{}/v", id)), vec![entry("1", "a")]);
+}
+
+#[test]
+fn sorts_date_entries_chronologically() {
+    let dir = tempdir().unwrap();
+
+    let db = open(dir.path());
+    let events = db.collection("events".to_string()).unwrap();
+    db.create_index("events", "at".to_string()).unwrap();
+    events.insert("late", tson(r#"{"at":{"$date":"2020-01-01T00:00:00Z"}}"#)).unwrap();
+    events.insert("early", tson(r#"{"at":{"$date":"1969-07-20T20:17:40Z"}}"#)).unwrap();
+    events.insert("epoch", tson(r#"{"at":{"$date":"1970-01-01T00:00:00Z"}}"#)).unwrap();
+    let id = events.id();
+    db.close().unwrap();
+
+    let raw = open_raw(dir.path());
+    let ids: Vec<_> = keys(&raw, &format!("{}/at", id)).into_iter()
+        .map(|key| {
+            assert_eq!(key[0], 0x0B);
+            String::from_utf8(key[9..].to_vec()).unwrap()
+        })
+        .collect();
+    assert_eq!(ids, vec!["early", "epoch", "late"]);
+}
+
+#[test]
+fn indexes_binary_values_by_their_bytes() {
+    let dir = tempdir().unwrap();
+
+    let db = open(dir.path());
+    let files = db.collection("files".to_string()).unwrap();
+    db.create_index("files", "v".to_string()).unwrap();
+    files.insert("a", tson(r#"{"v":{"$binary":{"base64":"AQI=","subType":"80"}}}"#)).unwrap();
+    let id = files.id();
+    db.close().unwrap();
+
+    let raw = open_raw(dir.path());
+    assert_eq!(
+        keys(&raw, &format!("{}/v", id)),
+        vec![entry(r#"{"$binary":{"base64":"AQI=","subType":"80"}}"#, "a")],
+    );
+}
@@ -0,0 +1,116 @@
+mod common;
+
+use common::{open, tson, json};
+use test_db::{Database, IdMode, OutputOptions, Query, StoreError};
+use tempfile::{tempdir, TempDir};
+use std::fs;
+use std::path::PathBuf;
+
+fn users(dir: &TempDir) -> (Database, test_db::Collection) {
+    let db = open(dir.path());
+    let users = db.collection("users".to_string()).unwrap();
+    (db, users)
+}
+
+fn lines(dir: &TempDir, lines: &str) -> PathBuf {
+    let path = dir.path().join("import.jsonl");
+    fs::write(&path, lines).unwrap();
+    path
+}
+
+#[test]
+fn round_trips_through_export_and_import() {
+    let dir = tempdir().unwrap();
+    let (_db, users) = users(&dir);
+    users.insert("a", tson(r#"{"_id":"a","n":1,"at":{"$date":"2020-01-01T00:00:00Z"}}"#)).unwrap();
+    users.insert("b", tson(r#"{"_id":"b","big":{"$numberLong":"9007199254740993"}}"#)).unwrap();
+
+    let path = dir.path().join("export.jsonl");
+    assert_eq!(users.export_jsonl(&path, &Query::All, OutputOptions::default()).unwrap(), 2);
+    users.delete_many(&Query::All).unwrap();
+
+    assert_eq!(users.import_jsonl(&path, IdMode::FailOnConflict).unwrap(), 2);
+    assert_eq!(json(users.get("a").unwrap().unwrap()), r#"{"_id":"a","n":1,"at":"2020-01-01T00:00:00.000Z"}"#);
+    assert_eq!(json(users.get("b").unwrap().unwrap()), r#"{"_id":"b","big":9007199254740993}"#);
+}
+
+#[test]
+fn skips_blank_lines_and_counts_from_one() {
+    let dir = tempdir().unwrap();
+    let (_db, users) = users(&dir);
+
+    let path = lines(&dir, "{\"_id\":\"a\"}\n\n  \n{\"_id\":3}\n");
+    let error = users.import_jsonl(&path, IdMode::Keep).unwrap_err();
+    assert!(matches!(error, StoreError::InvalidDocument(4, "ids must be strings or ObjectIds")));
+    assert_eq!(error.to_string(), "Line 4: ids must be strings or ObjectIds.");
+}
+
+#[test]
+fn rejects_lines_that_are_not_objects() {
+    let dir = tempdir().unwrap();
+    let (_db, users) = users(&dir);
+
+    let error = users.import_jsonl(lines(&dir, "{\"_id\":\"a\"}\n[1]\n"), IdMode::Keep).unwrap_err();
+    assert_eq!(error.to_string(), "Line 2: documents must be objects.");
+
+    let error = users.import_jsonl(lines(&dir, "3\n"), IdMode::Generate).unwrap_err();
+    assert_eq!(error.to_string(), "Line 1: documents must be objects.");
+}
+
+#[test]
+fn reports_invalid_json_with_its_line() {
+    let dir = tempdir().unwrap();
+    let (_db, users) = users(&dir);
+
+    let error = users.import_jsonl(lines(&dir, "{}\n{\"a\":}\n"), IdMode::Generate).unwrap_err();
+    assert!(matches!(error, StoreError::InvalidLine(2, _)));
+    assert!(error.to_string().starts_with("Line 2: "), "{}", error);
+}
+
+#[test]
+fn fails_on_conflicts_only_when_asked() {
+    let dir = tempdir().unwrap();
+    let (_db, users) = users(&dir);
+    users.insert("a", tson(r#"{"_id":"a","n":1}"#)).unwrap();
+
+    let path = lines(&dir, "{\"_id\":\"a\",\"n\":2}\n");
+    assert!(matches!(users.import_jsonl(&path, IdMode::FailOnConflict), Err(StoreError::AlreadyExists(_))));
+    assert_eq!(json(users.get("a").unwrap().unwrap()), r#"{"_id":"a","n":1}"#);
+
+    assert_eq!(users.import_jsonl(&path, IdMode::Keep).unwrap(), 1);
+    assert_eq!(json(users.get("a").unwrap().unwrap()), r#"{"_id":"a","n":2}"#);
+    assert_eq!(users.import_jsonl(&path, IdMode::Generate).unwrap(), 1);
+    assert_eq!(users.find(&Query::All).unwrap().len(), 2);
+
+    let repeated = lines(&dir, "{\"_id\":\"b\"}\n{\"_id\":\"b\"}\n");
+    assert!(matches!(users.import_jsonl(&repeated, IdMode::FailOnConflict), Err(StoreError::AlreadyExists(_))));
+    assert!(users.get("b").unwrap().is_none());
+}
+
+#[test]
+fn reports_missing_files() {
+    let dir = tempdir().unwrap();
+    let (_db, users) = users(&dir);
+
+    let missing = users.import_jsonl(dir.path().join("missing.jsonl"), IdMode::Keep);
+    assert!(matches!(missing, Err(StoreError::Io(_))));
+}
+
+#[test]
+fn keeps_object_ids_through_mongoexport_round_trips() {
+    let dir = tempdir().unwrap();
+    let (_db, users) = users(&dir);
+
+    let exported = "{\"_id\":{\"$oid\":\"5f1d7a3b9c1e4a2b3c4d5e6f\"},\"at\":{\"$date\":\"2020-01-01T00:00:00.000Z\"}}\n";
+    assert_eq!(users.import_jsonl(lines(&dir, exported), IdMode::Keep).unwrap(), 1);
+    assert!(users.get("5f1d7a3b9c1e4a2b3c4d5e6f").unwrap().is_some());
+
+    users.replace("5f1d7a3b9c1e4a2b3c4d5e6f", tson(r#"{"_id":{"$oid":"5f1d7a3b9c1e4a2b3c4d5e6f"},"n":1}"#)).unwrap();
+
+    let path = dir.path().join("export.jsonl");
+    users.export_jsonl(&path, &Query::All, OutputOptions::default()).unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "{\"_id\":{\"$oid\":\"5f1d7a3b9c1e4a2b3c4d5e6f\"},\"n\":1}\n");
+
+    let by_object_id = Query::new(r#"{"_id":{"$oid":"5f1d7a3b9c1e4a2b3c4d5e6f"}}"#.to_string()).unwrap();
+    assert_eq!(users.find(&by_object_id).unwrap().len(), 1);
+}
@@ -0,0 +1,124 @@
+mod common;
+
+use common::{open, open_raw, json, tson};
+use test_db::internal::store::{catalog::metadata_key, key_controls};
+use test_db::{Database, StoreError};
+use tempfile::tempdir;
+
+// Legacy TSON, written by versions before FLAT_TSON, puts a PAIR delimiter
+// between keys and values and a SEPARATOR between members and elements.
+const PAIR: u8 = 0x09;
+const SEPARATOR: u8 = 0x0A;
+
+fn legacy_collection(begin: u8, items: Vec<Vec<u8>>, end: u8) -> Vec<u8> {
+    let content = items.join(&SEPARATOR);
+    let mut collection = vec![begin];
+    collection.extend_from_slice(&(content.len() as u32).to_le_bytes());
+    collection.extend(content);
+    collection.push(end);
+    collection
+}
+
+fn legacy_object(members: Vec<(&str, Vec<u8>)>) -> Vec<u8> {
+    let members = members.into_iter()
+        .map(|(key, value)| [legacy_string(key), vec![PAIR], value].concat())
+        .collect();
+    legacy_collection(0x00, members, 0x01)
+}
+
+fn legacy_array(elements: Vec<Vec<u8>>) -> Vec<u8> {
+    legacy_collection(0x02, elements, 0x03)
+}
+
+fn legacy_string(string: &str) -> Vec<u8> {
+    [&[0x04][..], &(string.len() as u32).to_le_bytes(), string.as_bytes()].concat()
+}
+
+fn legacy_number(number: f64) -> Vec<u8> {
+    [&[0x05][..], &number.to_le_bytes()].concat()
+}
+
+#[test]
+fn migrates_name_prefixed_keys() {
+    let dir = tempdir().unwrap();
+
+    // Keys were the collection name, NS_BEGIN, VALUES and the id.
+    let raw = open_raw(dir.path());
+    for i in 0..2500 {
+        let key = ["users", key_controls::NS_BEGIN, key_controls::VALUES, &format!("{:05}", i)].concat();
+        raw.put(key, legacy_object(vec![("n", legacy_number(i as f64)), ("m", legacy_number(2.0))])).unwrap();
+    }
+    let key = ["other", key_controls::NS_BEGIN, key_controls::VALUES, "x"].concat();
+    raw.put(key, legacy_object(vec![])).unwrap();
+    drop(raw);
+
+    let db = open(dir.path());
+    let names: Vec<String> = db.list_collections().unwrap().into_iter().map(|info| info.name).collect();
+    assert_eq!(names, vec!["other", "users"]);
+    assert_eq!(db.collection_stats("users").unwrap().count, 2500);
+
+    let users = db.collection("users".to_string()).unwrap();
+    assert_eq!(json(users.get("00042").unwrap().unwrap()), r#"{"n":42,"m":2}"#);
+    db.close().unwrap();
+
+    let db = open(dir.path());
+    assert_eq!(db.collection_stats("users").unwrap().count, 2500);
+    assert_eq!(db.collection_stats("other").unwrap().count, 1);
+}
+
+#[test]
+fn flattens_column_family_layouts() {
+    let dir = tempdir().unwrap();
+
+    let mut raw = open_raw(dir.path());
+    raw.create_cf("5", &rocksdb::Options::default()).unwrap();
+    raw.create_cf("5/tags", &rocksdb::Options::default()).unwrap();
+    raw.put(metadata_key(key_controls::LAYOUT), [2]).unwrap();
+
+    let entry = legacy_object(vec![
+        ("id", legacy_number(5.0)),
+        ("name", legacy_string("posts")),
+        ("createdAt", legacy_number(0.0)),
+        ("options", legacy_object(vec![])),
+        ("indexes", legacy_array(vec![legacy_string("tags")])),
+    ]);
+    raw.put([metadata_key(key_controls::CATALOG), b"posts".to_vec()].concat(), entry).unwrap();
+
+    for i in 0..2500 {
+        let id = format!("{:05}", i);
+        let tags = legacy_array(vec![legacy_number(i as f64), legacy_number(1.0)]);
+        raw.put_cf(raw.cf_handle("5").unwrap(), &id, legacy_object(vec![("tags", tags.clone())])).unwrap();
+        raw.put_cf(raw.cf_handle("5/tags").unwrap(), [tags, id.into_bytes()].concat(), b"").unwrap();
+    }
+    drop(raw);
+
+    let db = open(dir.path());
+    let posts = db.collection("posts".to_string()).unwrap();
+    assert_eq!(posts.id(), 5);
+    assert_eq!(json(posts.get("00007").unwrap().unwrap()), r#"{"tags":[7,1]}"#);
+
+    // Every entry is now the flat array followed by the id.
+    let entry_len = tson("[7,1]").len() + 5;
+    let stats = db.collection_stats("posts").unwrap();
+    assert_eq!(stats.count, 2500);
+    assert_eq!(stats.index_sizes, vec![("tags".to_string(), 2500 * entry_len)]);
+
+    posts.replace("00007", tson("{}")).unwrap();
+    assert_eq!(db.collection_stats("posts").unwrap().index_sizes[0].1, 2499 * entry_len);
+    db.close().unwrap();
+
+    let db = open(dir.path());
+    assert_eq!(db.collection_stats("posts").unwrap().count, 2500);
+}
+
+#[test]
+fn rejects_corrupt_layout_markers() {
+    let dir = tempdir().unwrap();
+
+    let raw = open_raw(dir.path());
+    raw.put(metadata_key(key_controls::LAYOUT), [2, 0]).unwrap();
+    drop(raw);
+
+    let opened = Database::new(dir.path().to_str().unwrap().to_string(), Default::default());
+    assert!(matches!(opened, Err(StoreError::CorruptMetadata(_))));
+}
@@ -0,0 +1,50 @@
+mod common;
+
+use common::{open, tson};
+use test_db::{Database, DatabaseOptions, StoreError};
+use tempfile::tempdir;
+
+#[test]
+fn reads_alongside_a_primary_when_read_only() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap().to_string();
+
+    let primary = open(dir.path());
+    primary.collection("users".to_string()).unwrap().insert("a", tson(r#"{"n":1}"#)).unwrap();
+
+    let read_only = Database::open_read_only(path, DatabaseOptions::default()).unwrap();
+    let users = read_only.collection("users".to_string()).unwrap();
+
+    assert!(users.get("a").unwrap().is_some());
+    assert!(users.insert("b", tson("{}")).is_err());
+    assert!(read_only.create_index("users", "n".to_string()).is_err());
+    assert!(matches!(read_only.collection("missing".to_string()), Err(StoreError::CollectionNotFound(_))));
+
+    let names: Vec<String> = primary.list_collections().unwrap().into_iter().map(|info| info.name).collect();
+    assert_eq!(names, vec!["users"]);
+}
+
+#[test]
+fn catches_up_with_the_primary_as_a_secondary() {
+    let dir = tempdir().unwrap();
+    let secondary_dir = tempdir().unwrap();
+    let path = dir.path().to_str().unwrap().to_string();
+    let secondary_path = secondary_dir.path().to_str().unwrap().to_string();
+
+    let primary = open(dir.path());
+    let primary_users = primary.collection("users".to_string()).unwrap();
+    primary_users.insert("a", tson("{}")).unwrap();
+
+    let secondary = Database::open_as_secondary(path, secondary_path, DatabaseOptions::default()).unwrap();
+    let users = secondary.collection("users".to_string()).unwrap();
+    assert!(users.get("a").unwrap().is_some());
+
+    primary_users.insert("b", tson("{}")).unwrap();
+    primary.collection("later".to_string()).unwrap().insert("c", tson("{}")).unwrap();
+    secondary.catch_up().unwrap();
+
+    assert!(users.get("b").unwrap().is_some());
+    assert!(matches!(secondary.collection("later".to_string()), Err(StoreError::CollectionNotFound(_))));
+    assert!(matches!(secondary.collection("missing".to_string()), Err(StoreError::CollectionNotFound(_))));
+    assert!(users.insert("d", tson("{}")).is_err());
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use common::{open, tson, json};
+use test_db::Query;
+use tempfile::tempdir;
+
+fn query(json: &str) -> Query {
+    Query::new(json.to_string()).unwrap()
+}
+
+#[test]
+fn compares_dates_by_time() {
+    let dir = tempdir().unwrap();
+    let db = open(dir.path());
+    let events = db.collection("events".to_string()).unwrap();
+
+    events.insert("a", tson(r#"{"_id":"a","at":{"$date":"1969-12-31T23:59:59Z"}}"#)).unwrap();
+    events.insert("b", tson(r#"{"_id":"b","at":{"$date":"2020-01-01T00:00:00Z"}}"#)).unwrap();
+    events.insert("c", tson(r#"{"_id":"c","at":"2021-01-01T00:00:00Z"}"#)).unwrap();
+    events.insert("d", tson(r#"{"_id":"d","at":1600000000000}"#)).unwrap();
+
+    let after = events.find(&query(r#"{"at":{"$gt":{"$date":"1970-01-01T00:00:00Z"}}}"#)).unwrap();
+    assert_eq!(after.into_iter().map(json).collect::<Vec<_>>(), vec![r#"{"_id":"b","at":"2020-01-01T00:00:00.000Z"}"#]);
+
+    let before = events.find(&query(r#"{"at":{"$lt":{"$date":{"$numberLong":"0"}}}}"#)).unwrap();
+    assert_eq!(before.len(), 1);
+    assert_eq!(events.find(&query(r#"{"at":{"$date":"2020-01-01T00:00:00.000Z"}}"#)).unwrap().len(), 1);
+}
+
+#[test]
+fn matches_binary_by_subtype_and_bytes() {
+    let dir = tempdir().unwrap();
+    let db = open(dir.path());
+    let files = db.collection("files".to_string()).unwrap();
+
+    files.insert("a", tson(r#"{"_id":"a","data":{"$binary":{"base64":"AQI=","subType":"00"}}}"#)).unwrap();
+    files.insert("b", tson(r#"{"_id":"b","data":{"$binary":{"base64":"AQI=","subType":"05"}}}"#)).unwrap();
+    files.insert("c", tson(r#"{"_id":"c","data":"AQI="}"#)).unwrap();
+
+    let found = files.find(&query(r#"{"data":{"$binary":{"base64":"AQI=","subType":"00"}}}"#)).unwrap();
+    assert_eq!(found.into_iter().map(json).collect::<Vec<_>>(), vec![r#"{"_id":"a","data":"AQI="}"#]);
+
+    let any = files.find(&query(r#"{"data":{"$in":[{"$binary":"AQI=","$type":"05"},"AQI="]}}"#)).unwrap();
+    assert_eq!(any.len(), 2);
+    assert!(Query::new(r#"{"data":{"$gt":{"$binary":{"base64":"AQI=","subType":"00"}}}}"#.to_string()).is_err());
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use common::{open, open_raw, keys, tson, json};
+use test_db::internal::store::database::CORRUPT_COLLECTION;
+use test_db::internal::store::format;
+use tempfile::tempdir;
+
+#[test]
+fn reports_and_quarantines_corrupt_documents() {
+    let dir = tempdir().unwrap();
+
+    let db = open(dir.path());
+    let users = db.collection("users".to_string()).unwrap();
+    db.create_index("users", "n".to_string()).unwrap();
+    users.insert("a", tson(r#"{"_id":"a","n":1}"#)).unwrap();
+    users.insert("b", tson(r#"{"_id":"b","n":2}"#)).unwrap();
+    let id = users.id();
+    db.close().unwrap();
+
+    // Half a write, as a full disk would leave it.
+    let raw = open_raw(dir.path());
+    raw.put_cf(raw.cf_handle(&id.to_string()).unwrap(), "b", [format::CURRENT, 0x00, 0x02]).unwrap();
+    drop(raw);
+
+    let db = open(dir.path());
+    let corrupt = db.verify(false).unwrap();
+    assert_eq!(corrupt.len(), 1);
+    assert_eq!(corrupt[0].0, "users");
+    assert_eq!(corrupt[0].1.id, b"b".to_vec());
+    assert_eq!(corrupt[0].1.value, vec![format::CURRENT, 0x00, 0x02]);
+
+    // Reporting alone leaves the document in place.
+    let users = db.collection("users".to_string()).unwrap();
+    assert!(users.get("b").is_err());
+
+    assert_eq!(db.verify(true).unwrap().len(), 1);
+    assert!(db.verify(false).unwrap().is_empty());
+    assert_eq!(json(users.get("a").unwrap().unwrap()), r#"{"_id":"a","n":1}"#);
+    assert!(users.get("b").unwrap().is_none());
+
+    let quarantined = db.collection(CORRUPT_COLLECTION.to_string()).unwrap();
+    let document = json(quarantined.get("users/b").unwrap().unwrap());
+    assert!(document.starts_with(r#"{"_id":"users/b","collection":"users","id":"b","error":""#));
+    db.close().unwrap();
+
+    let raw = open_raw(dir.path());
+    assert_eq!(keys(&raw, &format!("{}/n", id)).len(), 1);
+}